@@ -1,22 +1,173 @@
 use crate::client::ClientConfig;
 use crate::error::{Error, Result};
-use crate::types::chat::{ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse};
+use crate::api::request::RequestPayload;
+use crate::mcp::client::ContextStrategy;
+use crate::models::tool::ToolCall;
+use crate::types::chat::{
+    ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, ChatStreamAccumulator, Message,
+};
+use crate::types::models::ModelInfo;
+use crate::types::provider::ProviderPreferences;
+use crate::utils::http::{self, ResponseMetadata};
 use crate::utils::validation;
 use async_stream::try_stream;
 use futures::stream::Stream;
 use futures::StreamExt;
 use futures::TryStreamExt;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
 use serde_json;
 use std::pin::Pin;
 use std::time::Duration;
-use tokio::time::sleep;
-use tokio_util::codec::{FramedRead, LinesCodec};
+use tokio::time::{sleep, timeout_at, Instant};
+use tokio_util::codec::{FramedRead, LinesCodec, LinesCodecError};
 use tokio_util::io::StreamReader;
 
+/// A single server-sent event, assembled from one or more `data:` lines.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SseEvent {
+    /// The `event:` type, if the server supplied one.
+    pub event: Option<String>,
+    /// The event payload; multiple `data:` lines are joined with newlines.
+    pub data: String,
+}
+
+/// Incremental SSE parser that buffers lines until a blank line terminates an event.
+///
+/// Comment lines (starting with `:`) and unknown fields are ignored. Bare JSON lines received
+/// outside of an event are passed through as single-line events to tolerate non-SSE streams.
+#[derive(Debug, Default)]
+pub(crate) struct SseParser {
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Feeds a single line into the parser, returning an event once one is complete.
+    pub(crate) fn push_line(&mut self, line: &str) -> Option<SseEvent> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        if line.is_empty() {
+            return self.flush();
+        }
+
+        if line.starts_with(':') {
+            return None;
+        }
+
+        if line.starts_with('{') && self.data.is_empty() && self.event.is_none() {
+            return Some(SseEvent {
+                event: None,
+                data: line.to_string(),
+            });
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            // `id`, `retry` and unknown fields carry nothing we need.
+            _ => {}
+        }
+
+        None
+    }
+
+    /// Returns the buffered event, if any, and resets the parser.
+    pub(crate) fn flush(&mut self) -> Option<SseEvent> {
+        if self.data.is_empty() && self.event.is_none() {
+            return None;
+        }
+
+        let data = self.data.join("\n");
+        self.data.clear();
+        Some(SseEvent {
+            event: self.event.take(),
+            data,
+        })
+    }
+}
+
+/// Interprets a stream payload of the form `{"error": {...}}` as an API error.
+///
+/// Returns `None` when the payload is not an error object.
+pub(crate) fn stream_error_from_payload(data: &str) -> Option<Error> {
+    let value: serde_json::Value = serde_json::from_str(data).ok()?;
+    let error = value.get("error")?;
+
+    let code = error
+        .get("code")
+        .and_then(|c| c.as_u64())
+        .and_then(|c| u16::try_from(c).ok())
+        .unwrap_or(500);
+    let message = error
+        .get("message")
+        .and_then(|m| m.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| error.to_string());
+
+    Some(Error::ApiError {
+        code,
+        message,
+        metadata: Some(error.clone()),
+    })
+}
+
+/// Passes successful responses through and turns others into the error for their status.
+async fn ensure_success(response: reqwest::Response, config: &ClientConfig) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = http::read_body(response, config).await?;
+    Err(Error::from_status(status.as_u16(), body))
+}
+
+/// Fails if the client reserves completion tokens and the request's cached model has no
+/// room left for them.
+fn check_completion_budget(request: &ChatCompletionRequest, config: &ClientConfig) -> Result<()> {
+    let Some(reserve) = config.reserve_completion_tokens else {
+        return Ok(());
+    };
+    match config.models_cache.find_model(&request.model) {
+        Some(model) => validation::validate_completion_budget(request, &model, Some(reserve)),
+        None => Ok(()),
+    }
+}
+
+/// Awaits `future`, failing with `Error::TimeoutError` if `deadline` passes first.
+async fn before_deadline<F: std::future::Future>(
+    deadline: Option<(Instant, Duration)>,
+    future: F,
+) -> Result<F::Output> {
+    match deadline {
+        Some((at, limit)) => timeout_at(at, future).await.map_err(|_| {
+            Error::TimeoutError(format!("Stream did not finish within {:?}", limit))
+        }),
+        None => Ok(future.await),
+    }
+}
+
+/// Client-side timings of a streamed chat completion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamTiming {
+    /// Time from sending the request until the first chunk carrying content or a tool
+    /// call, or `None` if no chunk did.
+    pub time_to_first_token: Option<Duration>,
+    /// Time from sending the request until the stream ended.
+    pub total: Duration,
+}
+
+#[derive(Clone)]
 pub struct ChatApi {
     pub client: Client,
     pub config: ClientConfig,
+    /// Provider preferences applied to requests that don't set their own.
+    pub provider_preferences: Option<ProviderPreferences>,
 }
 
 impl ChatApi {
@@ -24,18 +175,189 @@ impl ChatApi {
         Self {
             client,
             config: config.clone(),
+            provider_preferences: None,
+        }
+    }
+
+    /// Sets the default provider preferences, merged into every request's `provider` field.
+    pub fn with_provider_preferences(mut self, preferences: Option<ProviderPreferences>) -> Self {
+        self.provider_preferences = preferences;
+        self
+    }
+
+    /// Sets the timeout for requests made through this `ChatApi`, overriding the client's.
+    ///
+    /// Streams with a deadline set by `with_stream_deadline` are bounded by the deadline instead.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Merges the default provider preferences into the request's, fills in `max_tokens`
+    /// and the default system prompt unless the request sets its own, and tells the
+    /// context overflow handler, if any, when the request may not fit a cached model's
+    /// context window. With a reserved completion budget that check is left to
+    /// [`check_completion_budget`], which fails instead.
+    fn apply_defaults(&self, request: &mut ChatCompletionRequest) {
+        if let Some(prompt) = &self.config.default_system_prompt {
+            let has_system = request
+                .messages
+                .first()
+                .is_some_and(|message| message.role == "system");
+            if !has_system {
+                request.messages.insert(
+                    0,
+                    crate::types::chat::Message::system(prompt.clone()),
+                );
+            }
+        }
+        if let Some(defaults) = &self.provider_preferences {
+            request.provider = Some(match request.provider.take() {
+                Some(preferences) => preferences.merged_with(defaults),
+                None => defaults.clone(),
+            });
+        }
+        if request.max_tokens.is_none() {
+            request.max_tokens = self.config.default_max_tokens;
+        }
+        if self.config.reserve_completion_tokens.is_some() {
+            return;
+        }
+        let Some(handler) = &self.config.context_overflow_handler else {
+            return;
+        };
+        let overflow = self
+            .config
+            .models_cache
+            .find_model(&request.model)
+            .and_then(|model| validation::context_overflow(request, &model));
+        if let Some(overflow) = overflow {
+            handler.notify(&overflow);
         }
     }
 
     /// Sends a chat completion request and returns a complete ChatCompletionResponse.
+    ///
+    /// If the client has a context strategy configured, the messages are fitted to its
+    /// budget first.
     pub async fn chat_completion(
         &self,
         request: ChatCompletionRequest,
     ) -> Result<ChatCompletionResponse> {
+        Ok(self.chat_completion_with_meta(request).await?.0)
+    }
+
+    /// Like [`chat_completion`](Self::chat_completion), with `timeout` instead of the
+    /// client's request timeout, e.g. for one call with a very long document.
+    pub async fn chat_completion_with_timeout(
+        &self,
+        request: ChatCompletionRequest,
+        timeout: Duration,
+    ) -> Result<ChatCompletionResponse> {
+        self.clone().with_timeout(timeout).chat_completion(request).await
+    }
+
+    /// Like [`chat_completion`](Self::chat_completion), also returning the wall-clock time
+    /// the call took, including any retries and the backoff between them.
+    pub async fn chat_completion_timed(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<(ChatCompletionResponse, Duration)> {
+        let started = Instant::now();
+        let response = self.chat_completion(request).await?;
+        Ok((response, started.elapsed()))
+    }
+
+    /// Like [`chat_completion`](Self::chat_completion), also returning the rate-limit and
+    /// request metadata from the response headers, e.g. to throttle before hitting 429s.
+    pub async fn chat_completion_with_meta(
+        &self,
+        mut request: ChatCompletionRequest,
+    ) -> Result<(ChatCompletionResponse, ResponseMetadata)> {
+        if let Some(context) = &self.config.context_config {
+            request.messages = context
+                .strategy
+                .fit_to_context(request.messages, context.max_tokens)
+                .await?;
+        }
+
+        self.send_chat_completion(request).await
+    }
+
+    /// Fits the request's messages to `max_tokens` using `strategy`, then sends it.
+    ///
+    /// The given strategy replaces any strategy configured on the client.
+    pub async fn chat_completion_with_strategy(
+        &self,
+        mut request: ChatCompletionRequest,
+        strategy: &dyn ContextStrategy,
+        max_tokens: usize,
+    ) -> Result<ChatCompletionResponse> {
+        request.messages = strategy.fit_to_context(request.messages, max_tokens).await?;
+        Ok(self.send_chat_completion(request).await?.0)
+    }
+
+    /// Validates and sends a chat completion request as-is.
+    async fn send_chat_completion(
+        &self,
+        mut request: ChatCompletionRequest,
+    ) -> Result<(ChatCompletionResponse, ResponseMetadata)> {
+        self.apply_defaults(&mut request);
+        // The response is decoded as one JSON object, so never ask for an event stream.
+        request.stream = Some(false);
+
         // Validate the request
         validation::validate_chat_request(&request)?;
         validation::check_token_limits(&request)?;
-        
+        check_completion_budget(&request, &self.config)?;
+
+        // Fail fast while the model's circuit is open.
+        let Some(breaker) = &self.config.circuit_breaker else {
+            return self.post_chat(&request).await;
+        };
+        breaker.check(&request.model)?;
+        let result = self.post_chat(&request).await;
+        breaker.record(&request.model, &result);
+        result
+    }
+
+    /// Sends a payload built with [`RequestBuilder`](crate::api::request::RequestBuilder)
+    /// to the chat completions endpoint.
+    ///
+    /// The payload is posted as built, extra parameters included; no context strategy or
+    /// request validation is applied.
+    pub async fn send_payload<T: Serialize + Sync>(
+        &self,
+        payload: RequestPayload<T>,
+    ) -> Result<ChatCompletionResponse> {
+        Ok(self.post_chat(&payload).await?.0)
+    }
+
+    /// Streaming variant of [`send_payload`](Self::send_payload).
+    pub fn send_payload_stream<T: Serialize>(
+        &self,
+        payload: RequestPayload<T>,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk>> + Send>> {
+        match serde_json::to_value(&payload) {
+            Ok(mut req_body) => {
+                req_body["stream"] = serde_json::Value::Bool(true);
+                Self::stream_body(self.client.clone(), self.config.clone(), req_body)
+            }
+            Err(e) => Box::pin(futures::stream::once(async move {
+                Err(Error::ApiError {
+                    code: 500,
+                    message: format!("Request serialization error: {}", e),
+                    metadata: None,
+                })
+            })),
+        }
+    }
+
+    /// POSTs `body` to the chat completions endpoint with retries and decodes the response.
+    async fn post_chat<B: Serialize + ?Sized + Sync>(
+        &self,
+        body: &B,
+    ) -> Result<(ChatCompletionResponse, ResponseMetadata)> {
         // Build the complete URL for the chat completions endpoint.
         let url = self
             .config
@@ -51,77 +373,72 @@ impl ChatApi {
         let mut retry_count = 0;
         let mut backoff_ms = self.config.retry_config.initial_backoff_ms;
         
+        // One key per logical request, reused by every retry so the server can deduplicate them.
+        let idempotency_key = self
+            .config
+            .idempotency
+            .then(|| uuid::Uuid::new_v4().to_string());
+
         let response = loop {
             // Issue the POST request with appropriate headers and JSON body.
-            let response = self
+            let mut request = self
                 .client
                 .post(url.clone())
                 .headers(self.config.build_headers()?)
-                .json(&request)
-                .send()
-                .await?;
-                
-            let status = response.status();
-            
-            // Check if we should retry based on status code
-            if self.config.retry_config.retry_on_status_codes.contains(&status.as_u16()) 
-               && retry_count < self.config.retry_config.max_retries {
-                // Increment retry counter and exponential backoff
-                retry_count += 1;
-                
-                // Log retry attempt
-                eprintln!(
-                    "Retrying request ({}/{}) after {} ms due to status code {}",
-                    retry_count,
-                    self.config.retry_config.max_retries,
-                    backoff_ms,
-                    status.as_u16()
-                );
-                
-                // Wait before retrying
-                sleep(Duration::from_millis(backoff_ms)).await;
-                
-                // Calculate next backoff with exponential increase
-                backoff_ms = std::cmp::min(
-                    backoff_ms * 2,
-                    self.config.retry_config.max_backoff_ms
-                );
-                
-                continue;
+                .json(body);
+            if let Some(key) = &idempotency_key {
+                request = request.header("Idempotency-Key", key);
             }
+
+            // Connection failures happen before the server sees the request, so they are
+            // always safe to retry. A status is only retried if the request cannot have been
+            // acted on twice: a rate limit, or any listed status when idempotency keys are sent.
+            let outcome = http::send(&self.client, &self.config, request).await;
+            let retry_reason = match &outcome {
+                Ok(response) => {
+                    let status = response.status();
+                    let retryable = self.config.retry_config.retry_on_status_codes.contains(&status.as_u16())
+                        && (self.config.idempotency || status == StatusCode::TOO_MANY_REQUESTS);
+                    retryable.then(|| format!("status code {}", status.as_u16()))
+                }
+                Err(Error::HttpError(e)) if e.is_connect() => Some(format!("connection error: {}", e)),
+                Err(_) => None,
+            };
+            let retry_reason = match retry_reason {
+                Some(reason) if retry_count < self.config.retry_config.max_retries => reason,
+                _ => break outcome?,
+            };
+
+            // Increment retry counter and exponential backoff
+            retry_count += 1;
+            
+            // Log retry attempt
+            eprintln!(
+                "Retrying request ({}/{}) after {} ms due to {}",
+                retry_count,
+                self.config.retry_config.max_retries,
+                backoff_ms,
+                retry_reason
+            );
             
-            break response;
+            // Wait before retrying
+            sleep(Duration::from_millis(backoff_ms)).await;
+            
+            // Calculate next backoff with exponential increase
+            backoff_ms = std::cmp::min(
+                backoff_ms * 2,
+                self.config.retry_config.max_backoff_ms
+            );
         };
 
-        // Capture the HTTP status.
+        // Capture the HTTP status and header metadata.
         let status = response.status();
+        let metadata = ResponseMetadata::from_headers(response.headers());
 
         // Retrieve the response body.
-        let body = response.text().await?;
-
-        // Check if the HTTP response is successful.
-        if !status.is_success() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: body.clone(),
-                metadata: None,
-            });
-        }
+        let body = http::read_body(response, &self.config).await?;
 
-        if body.trim().is_empty() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: "Empty response body".into(),
-                metadata: None,
-            });
-        }
-
-        // Deserialize the JSON response into ChatCompletionResponse.
-        let chat_response = serde_json::from_str::<ChatCompletionResponse>(&body).map_err(|e| Error::ApiError {
-            code: status.as_u16(),
-            message: format!("Failed to decode JSON: {}. Body was: {}", e, body),
-            metadata: None,
-        })?;
+        let chat_response: ChatCompletionResponse = http::decode_json(status, body)?;
         
         // Validate any tool calls in the response
         for choice in &chat_response.choices {
@@ -137,34 +454,36 @@ impl ChatApi {
             }
         }
         
-        Ok(chat_response)
+        Ok((chat_response, metadata))
     }
 
     /// Returns a stream for a chat completion request.
-    /// Each yielded item is a ChatCompletionChunk.
+    /// Each yielded item is a ChatCompletionChunk. A context strategy configured on the
+    /// client is applied before the request is sent, and the client's defaults after it,
+    /// just as in [`chat_completion`](Self::chat_completion).
     pub fn chat_completion_stream(
         &self,
         request: ChatCompletionRequest,
     ) -> Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk>> + Send>> {
+        let api = self.clone();
         let client = self.client.clone();
         let config = self.config.clone();
-        
-        // Validate the request before streaming
-        if let Err(e) = validation::validate_chat_request(&request) {
-            return Box::pin(futures::stream::once(async { Err(e) }));
-        }
-        
-        if let Err(e) = validation::check_token_limits(&request) {
-            return Box::pin(futures::stream::once(async { Err(e) }));
-        }
+        let mut request = request;
 
         let stream = try_stream! {
-            // Build the URL for the chat completions endpoint.
-            let url = config.base_url.join("chat/completions").map_err(|e| Error::ApiError {
-                code: 400,
-                message: format!("Invalid URL: {}", e),
-                metadata: None,
-            })?;
+            // Fit the messages to the configured context budget, if any.
+            if let Some(context) = &config.context_config {
+                request.messages = context
+                    .strategy
+                    .fit_to_context(request.messages, context.max_tokens)
+                    .await?;
+            }
+            api.apply_defaults(&mut request);
+
+            // Validate the request before streaming
+            validation::validate_chat_request(&request)?;
+            validation::check_token_limits(&request)?;
+            check_completion_budget(&request, &config)?;
 
             // Serialize the request with streaming enabled.
             let mut req_body = serde_json::to_value(&request).map_err(|e| Error::ApiError {
@@ -174,56 +493,137 @@ impl ChatApi {
             })?;
             req_body["stream"] = serde_json::Value::Bool(true);
 
+            let mut chunks = Self::stream_body(client, config, req_body);
+            while let Some(chunk) = chunks.next().await {
+                yield chunk?;
+            }
+        };
+
+        Box::pin(stream)
+    }
+
+    /// Streams chunks for an already serialized request body with `"stream": true` set.
+    ///
+    /// The client's circuit breaker is checked for the body's `model` first. A stream counts
+    /// as a success once it completes, or as a failure at its first error.
+    pub(crate) fn stream_body(
+        client: Client,
+        config: ClientConfig,
+        req_body: serde_json::Value,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk>> + Send>> {
+        let Some(breaker) = config.circuit_breaker.clone() else {
+            return Self::stream_events(client, config, req_body);
+        };
+        let model = req_body["model"].as_str().unwrap_or_default().to_string();
+
+        let stream = try_stream! {
+            breaker.check(&model)?;
+            let mut chunks = Self::stream_events(client, config, req_body);
+            while let Some(chunk) = chunks.next().await {
+                if chunk.is_err() {
+                    breaker.record(&model, &chunk);
+                }
+                yield chunk?;
+            }
+            breaker.record(&model, &Ok(()));
+        };
+
+        Box::pin(stream)
+    }
+
+    /// Sends `req_body` and decodes the event stream, without the circuit breaker.
+    fn stream_events(
+        client: Client,
+        config: ClientConfig,
+        req_body: serde_json::Value,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk>> + Send>> {
+        let stream = try_stream! {
+            // Build the URL for the chat completions endpoint.
+            let url = config.base_url.join("chat/completions").map_err(|e| Error::ApiError {
+                code: 400,
+                message: format!("Invalid URL: {}", e),
+                metadata: None,
+            })?;
+
+            // The deadline covers the whole stream, from connecting to the final event.
+            let deadline = config.stream_deadline.map(|limit| (Instant::now() + limit, limit));
+
             // Issue the POST request with error-for-status checking.
-            let response = client
+            let mut request = client
                 .post(url)
                 .headers(config.build_headers()?)
-                .json(&req_body)
-                .send()
-                .await?
-                .error_for_status()
-                .map_err(|e| {
-                    Error::ApiError {
-                        code: e.status().map(|s| s.as_u16()).unwrap_or(500),
-                        message: e.to_string(),
-                        metadata: None,
-                    }
-                })?;
+                .json(&req_body);
+            if let Some((_, limit)) = deadline {
+                // Lift the client-wide request timeout past the deadline so ours fires first.
+                request = request.timeout(limit + Duration::from_secs(1));
+            }
+            let response = before_deadline(deadline, async {
+                ensure_success(http::send(&client, &config, request).await?, &config).await
+            })
+            .await??;
+
+            let status = response.status().as_u16();
 
             // Process the bytes stream as an asynchronous line stream.
-            let byte_stream = response.bytes_stream().map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
+            let byte_stream = response.bytes_stream().map_err(std::io::Error::other);
             let stream_reader = StreamReader::new(byte_stream);
-            let mut lines = FramedRead::new(stream_reader, LinesCodec::new());
+            let codec = match config.max_response_bytes {
+                Some(limit) => LinesCodec::new_with_max_length(limit),
+                None => LinesCodec::new(),
+            };
+            let mut lines = FramedRead::new(stream_reader, codec);
 
-            while let Some(line_result) = lines.next().await {
-                let line = line_result.map_err(|e| Error::StreamingError(format!("Failed to read stream line: {}", e)))?;
-                
-                if line.trim().is_empty() {
-                    continue;
-                }
-                
-                if line.starts_with("data:") {
-                    let data_part = line.trim_start_matches("data:").trim();
-                    if data_part == "[DONE]" {
-                        break;
+            let mut parser = SseParser::default();
+            let mut finished = false;
+
+            while !finished {
+                // Assemble the next complete event, flushing any buffered event at end of stream.
+                let event = match before_deadline(deadline, lines.next()).await? {
+                    Some(line_result) => {
+                        let line = line_result.map_err(|e| match (e, config.max_response_bytes) {
+                            (LinesCodecError::MaxLineLengthExceeded, Some(limit)) => Error::ResponseTooLarge { limit },
+                            (e, _) => Error::StreamingError(format!("Failed to read stream line: {}", e)),
+                        })?;
+                        match parser.push_line(&line) {
+                            Some(event) => event,
+                            None => continue,
+                        }
                     }
-                    
-                    match serde_json::from_str::<ChatCompletionChunk>(data_part) {
-                        Ok(chunk) => yield chunk,
-                        Err(e) => {
-                            // Log parsing error but continue processing stream
-                            eprintln!("Failed to parse chunk: {} - Data: {}", e, data_part);
-                            continue;
+                    None => {
+                        finished = true;
+                        match parser.flush() {
+                            Some(event) => event,
+                            None => break,
                         }
                     }
-                } else if line.starts_with(":") {
-                    // Ignore SSE comment lines.
+                };
+
+                if event.data.trim() == "[DONE]" {
+                    break;
+                }
+
+                if event.event.as_deref() == Some("error") {
+                    Err(Error::StreamingError(event.data.clone()))?;
+                }
+
+                if event.data.trim().is_empty() {
                     continue;
-                } else {
-                    // Try to parse as a regular JSON message (non-SSE format)
-                    match serde_json::from_str::<ChatCompletionChunk>(&line) {
-                        Ok(chunk) => yield chunk,
-                        Err(_) => continue,
+                }
+
+                match serde_json::from_str::<ChatCompletionChunk>(&event.data) {
+                    Ok(chunk) => yield chunk,
+                    Err(e) => {
+                        // A provider error frame ends the stream; propagate it to the consumer.
+                        if let Some(error) = stream_error_from_payload(&event.data) {
+                            Err(error)?;
+                        }
+
+                        // Skipping the frame would silently drop content.
+                        Err(Error::DeserializationError {
+                            status,
+                            body: event.data.clone(),
+                            source: e,
+                        })?;
                     }
                 }
             }
@@ -232,36 +632,172 @@ impl ChatApi {
         Box::pin(stream)
     }
     
+    /// Streams a chat completion, calling `on_delta` with each piece of content as it
+    /// arrives and `on_tool_call` with each tool call once all its fragments have, and
+    /// returns the assembled response.
+    ///
+    /// Only the first choice's content and tool calls are passed to the callbacks; every
+    /// choice is assembled into the response. The first stream error is returned as is.
+    pub async fn chat_completion_with_callback(
+        &self,
+        request: ChatCompletionRequest,
+        mut on_delta: impl FnMut(&str),
+        mut on_tool_call: impl FnMut(&ToolCall),
+    ) -> Result<ChatCompletionResponse> {
+        let model = request.model.clone();
+        let mut stream = self.chat_completion_stream(request);
+        let mut accumulator = ChatStreamAccumulator::new();
+
+        let mut reported = 0;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            let first = chunk
+                .choices
+                .iter()
+                .enumerate()
+                .find(|(position, choice)| choice.index.unwrap_or(*position) == 0);
+            if let Some((_, choice)) = first {
+                if !choice.delta.content.is_empty() {
+                    on_delta(&choice.delta.content);
+                }
+            }
+            accumulator.push(chunk);
+
+            let completed = accumulator.completed_tool_calls(0);
+            completed[reported..].iter().for_each(&mut on_tool_call);
+            reported = completed.len();
+        }
+
+        let response = accumulator.finish(model);
+        if let Some(tool_calls) = response.tool_calls() {
+            tool_calls.iter().skip(reported).for_each(&mut on_tool_call);
+        }
+        Ok(response)
+    }
+
+    /// Streams a chat completion and returns the assembled response with its timings,
+    /// including the time to first token.
+    pub async fn chat_completion_stream_timed(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<(ChatCompletionResponse, StreamTiming)> {
+        let model = request.model.clone();
+        let started = Instant::now();
+        let mut stream = self.chat_completion_stream(request);
+        let mut accumulator = ChatStreamAccumulator::new();
+        let mut time_to_first_token = None;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            let has_token = chunk.choices.iter().any(|choice| {
+                !choice.delta.content.is_empty() || choice.delta.tool_calls.is_some()
+            });
+            if has_token && time_to_first_token.is_none() {
+                time_to_first_token = Some(started.elapsed());
+            }
+            accumulator.push(chunk);
+        }
+
+        let timing = StreamTiming {
+            time_to_first_token,
+            total: started.elapsed(),
+        };
+        Ok((accumulator.finish(model), timing))
+    }
+
+    /// Estimates the cost in USD of sending `request` to `model`.
+    ///
+    /// Prompt tokens are estimated locally. When `request.max_tokens` is set the reply is
+    /// assumed to use all of it, so the result is an upper bound; otherwise only the prompt
+    /// is priced. Fails if the model has no pricing information.
+    pub fn estimate_cost(&self, request: &ChatCompletionRequest, model: &ModelInfo) -> Result<f64> {
+        if model.pricing.is_none() {
+            return Err(Error::ConfigError(format!(
+                "No pricing information for model '{}'",
+                model.id
+            )));
+        }
+
+        Ok(model.estimate_cost(
+            validation::estimate_request_tokens(request),
+            request.max_tokens.unwrap_or(0),
+        ))
+    }
+
     /// Simple function to complete a chat with a single user message
-    pub async fn simple_completion(&self, model: &str, user_message: &str) -> Result<String> {
-        let request = ChatCompletionRequest {
-            model: model.to_string(),
-            messages: vec![crate::types::chat::Message {
-                role: "user".to_string(),
-                content: user_message.to_string(),
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-            }],
-            stream: None,
-            response_format: None,
-            tools: None,
-            provider: None,
-            models: None,
-            transforms: None,
+    pub async fn simple_completion(
+        &self,
+        model: impl Into<String>,
+        user_message: &str,
+    ) -> Result<String> {
+        self.simple_completion_messages(model, &[("user", user_message)]).await
+    }
+
+    /// Like [`simple_completion`](Self::simple_completion), with a system prompt sent first.
+    pub async fn simple_completion_with_system(
+        &self,
+        model: impl Into<String>,
+        system_prompt: &str,
+        user_message: &str,
+    ) -> Result<String> {
+        self.simple_completion_messages(model, &[("system", system_prompt), ("user", user_message)])
+            .await
+    }
+
+    /// Sends `user_input` as the next turn of `history` and appends the reply.
+    ///
+    /// The user message and the assistant's reply, including any tool calls and reasoning,
+    /// are pushed onto `history`, and the reply is returned. On failure `history` is left
+    /// as it was, so the turn can simply be retried.
+    pub async fn continue_conversation<'h>(
+        &self,
+        history: &'h mut Vec<Message>,
+        user_input: &str,
+        model: impl Into<String>,
+    ) -> Result<&'h Message> {
+        history.push(Message::user(user_input));
+
+        let request = ChatCompletionRequest::new(model, history.clone());
+        let reply = match self.chat_completion(request).await {
+            Ok(response) => response.choices.into_iter().next().map(|choice| choice.message),
+            Err(e) => {
+                history.pop();
+                return Err(e);
+            }
         };
-        
-        let response = self.chat_completion(request).await?;
-        
-        if response.choices.is_empty() {
+        let Some(reply) = reply else {
+            history.pop();
             return Err(Error::ApiError {
                 code: 500,
                 message: "No choices returned in response".into(),
                 metadata: None,
             });
-        }
+        };
+
+        history.push(reply);
+        Ok(&history[history.len() - 1])
+    }
+
+    /// Completes a chat given as `(role, content)` pairs and returns the reply text.
+    pub async fn simple_completion_messages(
+        &self,
+        model: impl Into<String>,
+        messages: &[(&str, &str)],
+    ) -> Result<String> {
+        let messages = messages
+            .iter()
+            .map(|&(role, content)| Message::new(role, content))
+            .collect();
+        let request = ChatCompletionRequest::new(model, messages);
         
-        Ok(response.choices[0].message.content.clone())
+        let response = self.chat_completion(request).await?;
+        
+        response.content().map(str::to_string).ok_or_else(|| Error::ApiError {
+            code: 500,
+            message: "No choices returned in response".into(),
+            metadata: None,
+        })
     }
 }
 
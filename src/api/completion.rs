@@ -2,6 +2,7 @@
 use crate::client::ClientConfig;
 use crate::error::{Error, Result};
 use crate::types::completion::{CompletionRequest, CompletionResponse};
+use crate::utils::http;
 use reqwest::Client;
 
 /// API endpoint for text completions.
@@ -34,42 +35,35 @@ impl CompletionApi {
             })?;
 
         // Send the POST request.
-        let response = self
+        let http_request = self
             .client
             .post(url)
             .headers(self.config.build_headers()?)
-            .json(&request)
-            .send()
-            .await?;
+            .json(&request);
+        let response = http::send(&self.client, &self.config, http_request).await?;
 
         // Capture the status code before consuming the response body.
         let status = response.status();
 
         // Get the response body.
-        let body = response.text().await?;
+        let body = http::read_body(response, &self.config).await?;
 
-        // Check if the HTTP response was successful.
-        if !status.is_success() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: body.clone(),
-                metadata: None,
-            });
-        }
+        http::decode_json(status, body)
+    }
 
-        if body.trim().is_empty() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: "Empty response body".into(),
-                metadata: None,
-            });
-        }
+    /// Completes `prompt` with `model` and returns the text of the first choice.
+    pub async fn simple_completion(&self, model: impl Into<String>, prompt: impl Into<String>) -> Result<String> {
+        let response = self.text_completion(CompletionRequest::new(model, prompt)).await?;
 
-        // Deserialize the body.
-        serde_json::from_str::<CompletionResponse>(&body).map_err(|e| Error::ApiError {
-            code: status.as_u16(),
-            message: format!("Failed to decode JSON: {}. Body was: {}", e, body),
-            metadata: None,
-        })
+        response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.text)
+            .ok_or_else(|| Error::ApiError {
+                code: 500,
+                message: "No choices returned in response".into(),
+                metadata: None,
+            })
     }
 }
@@ -1,6 +1,7 @@
 pub mod chat;
 pub mod completion;
 pub mod models;
+pub mod moderation;
 pub mod request;
 pub mod structured;
 pub mod web_search;
@@ -9,6 +10,7 @@ pub mod web_search;
 pub use chat::ChatApi;
 pub use completion::CompletionApi;
 pub use models::ModelsApi;
+pub use moderation::ModerationApi;
 pub use structured::StructuredApi;
 pub use web_search::WebSearchApi;
 
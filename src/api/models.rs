@@ -1,7 +1,80 @@
 use crate::client::ClientConfig;
 use crate::error::{Error, Result};
-use crate::types::models::{ModelsRequest, ModelsResponse};
+use crate::types::models::{ModelId, ModelInfo, ModelsRequest, ModelsResponse, ProviderEndpoint};
+use crate::types::routing::ModelCoverageProfile;
+use crate::utils::http;
 use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long model listings are cached by default.
+pub const DEFAULT_MODELS_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// In-memory cache of model listings, keyed by request parameters.
+///
+/// Clones share the same entries, so every `ModelsApi` created from a client sees the
+/// same cache.
+#[derive(Debug, Clone)]
+pub struct ModelsCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<String, (Instant, ModelsResponse)>>>,
+}
+
+impl Default for ModelsCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MODELS_CACHE_TTL)
+    }
+}
+
+impl ModelsCache {
+    /// Creates an empty cache whose entries expire after `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// How long entries stay fresh.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Removes all cached listings.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    /// Looks up `id` in the fresh cached listings without fetching anything.
+    pub(crate) fn find_model(&self, id: &str) -> Option<ModelInfo> {
+        let base = ModelId::parse(id).ok()?.base().to_string();
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .values()
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+            .flat_map(|(_, response)| response.models.iter())
+            .find(|model| model.id == base)
+            .cloned()
+    }
+
+    fn get(&self, key: &str) -> Option<ModelsResponse> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(key)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+            .map(|(_, response)| response.clone())
+    }
+
+    fn insert(&self, key: String, response: ModelsResponse) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, (Instant::now(), response));
+    }
+}
 
 /// API endpoint for model management.
 pub struct ModelsApi {
@@ -18,13 +91,122 @@ impl ModelsApi {
         }
     }
 
-    /// Lists available models, optionally filtered by capability or provider.
+    /// Lists available models like [`list_models`](Self::list_models), answering from
+    /// the client's cache while its entry for the same parameters is fresh.
+    pub async fn list_models_cached(&self, request: Option<ModelsRequest>) -> Result<ModelsResponse> {
+        let key = Self::cache_key(request.as_ref())?;
+        let response = match self.config.models_cache.get(&key) {
+            Some(response) => response,
+            None => {
+                // Cache the unfiltered listing; client-side filters aren't part of the key.
+                let response: ModelsResponse = self.get_json("models", request.as_ref()).await?;
+                self.config.models_cache.insert(key, response.clone());
+                response
+            }
+        };
+        Ok(Self::filter(response, request.as_ref()))
+    }
+
+    /// Drops every cached listing and reloads the one for `request`.
+    pub async fn refresh(&self, request: Option<ModelsRequest>) -> Result<ModelsResponse> {
+        self.config.models_cache.clear();
+        self.list_models_cached(request).await
+    }
+
+    fn cache_key(request: Option<&ModelsRequest>) -> Result<String> {
+        serde_json::to_string(&request).map_err(Error::SerializationError)
+    }
+
+    /// Lists available models, optionally filtered by capability or provider, and
+    /// client-side by context length and input modality.
     pub async fn list_models(&self, request: Option<ModelsRequest>) -> Result<ModelsResponse> {
+        let response = self.get_json("models", request.as_ref()).await?;
+        Ok(Self::filter(response, request.as_ref()))
+    }
+
+    /// Returns the models in the (cached) listing for which `predicate` holds.
+    pub async fn find_models(&self, predicate: impl Fn(&ModelInfo) -> bool) -> Result<Vec<ModelInfo>> {
+        Ok(self
+            .list_models_cached(None)
+            .await?
+            .models
+            .into_iter()
+            .filter(|model| predicate(model))
+            .collect())
+    }
+
+    /// Drops the models that fail `request`'s client-side filters.
+    fn filter(mut response: ModelsResponse, request: Option<&ModelsRequest>) -> ModelsResponse {
+        if let Some(request) = request {
+            response.models.retain(|model| request.matches(model));
+        }
+        response
+    }
+
+    /// Lists every model matching `request`, following `next_cursor` across pages.
+    ///
+    /// The request's `limit` sets the page size; its `cursor` is where listing starts.
+    pub async fn list_all_models(&self, request: Option<ModelsRequest>) -> Result<ModelsResponse> {
+        let mut request = request.unwrap_or_default();
+        let mut models = Vec::new();
+        let mut seen_cursors = std::collections::HashSet::new();
+
+        loop {
+            let page = self.list_models(Some(request.clone())).await?;
+            models.extend(page.models);
+            match page.next_cursor {
+                // A repeated cursor would loop forever; treat it as the end of the listing.
+                Some(cursor) if seen_cursors.insert(cursor.clone()) => request.cursor = Some(cursor),
+                _ => break,
+            }
+        }
+
+        Ok(ModelsResponse {
+            models,
+            next_cursor: None,
+        })
+    }
+
+    /// Looks up a single model by id in the (cached) model listing.
+    ///
+    /// Any routing suffix such as `:free` is ignored when matching.
+    pub async fn get_model(&self, id: &str) -> Result<ModelInfo> {
+        let base = ModelId::parse(id)?.base().to_string();
+
+        self.list_models_cached(None)
+            .await?
+            .models
+            .into_iter()
+            .find(|model| model.id == base)
+            .ok_or_else(|| Error::ModelNotAvailable(id.to_string()))
+    }
+
+    /// Checks a coverage profile's primary and fallback models against the (cached)
+    /// catalog.
+    pub async fn validate_profile(&self, profile: &ModelCoverageProfile) -> Result<()> {
+        profile.validate_against(&self.list_models_cached(None).await?)
+    }
+
+    /// Lists the provider endpoints serving a model, with their pricing and limits.
+    pub async fn list_model_endpoints(&self, id: &str) -> Result<Vec<ProviderEndpoint>> {
+        let base = ModelId::parse(id)?.base();
+        let response: ModelEndpointsResponse = self
+            .get_json(&format!("models/{}/endpoints", base), None::<&()>)
+            .await?;
+        Ok(response.data.endpoints)
+    }
+
+    /// Sends a GET request to `path` under the base URL and decodes the JSON response.
+    async fn get_json<T, Q>(&self, path: &str, query: Option<&Q>) -> Result<T>
+    where
+        T: DeserializeOwned,
+        Q: Serialize + ?Sized,
+    {
         // Build the URL.
         let url = self
             .config
             .base_url
-            .join("models")
+            .join(path)
             .map_err(|e| Error::ApiError {
                 code: 400,
                 message: format!("Invalid URL for models endpoint: {}", e),
@@ -34,42 +216,31 @@ impl ModelsApi {
         // Build the request with optional query parameters.
         let mut req_builder = self.client.get(url).headers(self.config.build_headers()?);
         
-        if let Some(req) = request {
-            req_builder = req_builder.query(&req);
+        if let Some(query) = query {
+            req_builder = req_builder.query(query);
         }
 
         // Send the request.
-        let response = req_builder.send().await?;
+        let response = http::send(&self.client, &self.config, req_builder).await?;
 
         // Capture the status code before consuming the response body.
         let status = response.status();
 
         // Get the response body.
-        let body = response.text().await?;
-
-        // Check if the HTTP response was successful.
-        if !status.is_success() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: body.clone(),
-                metadata: None,
-            });
-        }
+        let body = http::read_body(response, &self.config).await?;
 
-        if body.trim().is_empty() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: "Empty response body".into(),
-                metadata: None,
-            });
-        }
-
-        // Deserialize the body.
-        serde_json::from_str::<ModelsResponse>(&body).map_err(|e| Error::ApiError {
-            code: status.as_u16(),
-            message: format!("Failed to decode JSON: {}. Body was: {}", e, body),
-            metadata: None,
-        })
+        http::decode_json(status, body)
     }
 }
 
+/// Body of a `models/{id}/endpoints` response.
+#[derive(Debug, Deserialize)]
+struct ModelEndpointsResponse {
+    data: ModelEndpoints,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEndpoints {
+    #[serde(default)]
+    endpoints: Vec<ProviderEndpoint>,
+}
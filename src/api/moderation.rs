@@ -0,0 +1,53 @@
+// File: openrouter_api/src/api/moderation.rs
+use crate::{
+    client::ClientConfig,
+    error::{Error, Result},
+    types::moderation::{ModerationRequest, ModerationResponse},
+    utils::http,
+};
+use reqwest::Client;
+
+pub struct ModerationApi {
+    pub client: Client,
+    pub config: ClientConfig,
+}
+
+impl ModerationApi {
+    /// Creates a new ModerationApi instance given a reqwest client and a client configuration.
+    pub fn new(client: Client, config: &ClientConfig) -> Self {
+        Self {
+            client,
+            config: config.clone(),
+        }
+    }
+
+    /// Classifies the request's input and returns per-category flags and scores.
+    pub async fn moderate(&self, request: ModerationRequest) -> Result<ModerationResponse> {
+        // Join the base URL with the relative path "moderations".
+        let url = self
+            .config
+            .base_url
+            .join("moderations")
+            .map_err(|e| Error::ApiError {
+                code: 400,
+                message: format!("Invalid URL for moderation: {}", e),
+                metadata: None,
+            })?;
+
+        let http_request = self
+            .client
+            .post(url)
+            .headers(self.config.build_headers()?)
+            .json(&request);
+        let response = http::send(&self.client, &self.config, http_request).await?;
+
+        let status = response.status();
+        let body = http::read_body(response, &self.config).await?;
+        http::decode_json(status, body)
+    }
+
+    /// Screens a single piece of text, returning whether it was flagged.
+    pub async fn is_flagged(&self, input: impl Into<String>) -> Result<bool> {
+        Ok(self.moderate(ModerationRequest::new(input)).await?.flagged())
+    }
+}
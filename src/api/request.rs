@@ -21,7 +21,6 @@ use serde_json::Value;
 
 /// Representation for enabling structured outputs in the request payload.
 #[derive(Debug, Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
 pub struct ResponseFormatConfig {
     /// Fixed string indicating the type of response format.
     #[serde(rename = "type")]
@@ -39,7 +38,6 @@ pub struct ResponseFormatConfig {
 /// - Optionally, tool calling instructions.
 /// - Any extra parameters merged using flattening.
 #[derive(Debug, Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
 pub struct RequestPayload<T: Serialize> {
     /// The model ID to use.
     pub model: String,
@@ -58,10 +56,12 @@ pub struct RequestPayload<T: Serialize> {
 
 /// A unified request builder for non‑interactive endpoints.
 ///
+/// The built payload can be sent with [`ChatApi::send_payload`](crate::api::chat::ChatApi::send_payload).
+///
 /// This builder supports configuration for:
-/// - **Structured Outputs:** By invoking [with_structured_output], clients can enable a specific JSON Schema for responses.
-/// - **Tool Calling:** By invoking [with_tools], clients can supply a list of callable tools.
-/// - **Provider Preferences:** By invoking [with_provider_preferences], clients can configure provider routing.
+/// - **Structured Outputs:** By invoking [`with_structured_output`](Self::with_structured_output), clients can enable a specific JSON Schema for responses.
+/// - **Tool Calling:** By invoking [`with_tools`](Self::with_tools), clients can supply a list of callable tools.
+/// - **Provider Preferences:** By invoking [`with_provider_preferences`](RequestBuilder::with_provider_preferences), clients can configure provider routing.
 pub struct RequestBuilder<T: Serialize> {
     model: String,
     messages: Vec<Message>,
@@ -148,7 +148,7 @@ impl<T: Serialize> RequestBuilder<T> {
 impl RequestBuilder<Value> {
     /// Adds provider preferences into the request payload.
     ///
-    /// This method accepts a strongly‑typed [ProviderPreferences] instance and serializes it
+    /// This method accepts a strongly‑typed [`ProviderPreferences`](crate::models::provider_preferences::ProviderPreferences) instance and serializes it
     /// into the JSON payload under the "provider" key. It validates the preferences and returns
     /// an error if validation fails.
     ///
@@ -187,7 +187,7 @@ impl RequestBuilder<Value> {
 
         // Serialize to JSON
         let provider_value = serde_json::to_value(preferences)
-            .map_err(|e| crate::error::Error::SerializationError(e))?;
+            .map_err(crate::error::Error::SerializationError)?;
 
         // Add to the extra params
         if let Value::Object(ref mut map) = self.extra_params {
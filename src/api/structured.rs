@@ -1,12 +1,28 @@
 //! Structured output API module for handling JSON schema-based responses
 
+use crate::api::chat::ChatApi;
+use crate::api::request::ResponseFormatConfig;
 use crate::client::ClientConfig;
 use crate::error::{Error, Result};
 use crate::models::structured::JsonSchemaConfig;
 use crate::types::chat::{ChatCompletionRequest, ChatCompletionResponse, Message};
+use crate::utils::{http, validation};
+use async_stream::try_stream;
+use futures::stream::Stream;
+use futures::StreamExt;
 use reqwest::Client;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
+use std::pin::Pin;
+
+/// An item of a streamed structured output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StructuredEvent<T> {
+    /// A fragment of the raw JSON text, in arrival order.
+    Delta(String),
+    /// The complete output, parsed and validated; always the last item.
+    Complete(T),
+}
 
 /// API endpoint for structured output generation.
 pub struct StructuredApi {
@@ -26,25 +42,13 @@ impl StructuredApi {
     /// Generates a structured output that conforms to the provided JSON schema.
     /// Returns the parsed response deserialized into the specified type T.
     pub async fn generate<T>(&self, 
-        model: &str, 
+        model: impl Into<String>,
         messages: Vec<Message>,
         schema_config: JsonSchemaConfig
     ) -> Result<T> 
     where
         T: DeserializeOwned,
     {
-        // Build the request with structured output configuration
-        let request = ChatCompletionRequest {
-            model: model.to_string(),
-            messages,
-            stream: Some(false),
-            response_format: Some("json_schema".to_string()),
-            tools: None,
-            provider: None,
-            models: None,
-            transforms: None,
-        };
-        
         // Build the complete URL for the chat completions endpoint.
         let url = self
             .config
@@ -56,69 +60,144 @@ impl StructuredApi {
                 metadata: None,
             })?;
 
-        // Build the request body with the structured output schema
-        let mut body = serde_json::to_value(&request).map_err(|e| Error::SerializationError(e))?;
-        body["response_format"] = serde_json::json!({
-            "type": "json_schema",
-            "schema": schema_config.schema,
-            "name": schema_config.name,
-            "strict": schema_config.strict
-        });
+        let model = model.into();
+        self.check_model_support(&model)?;
+        let body = Self::request_body(model.clone(), messages, &schema_config, false)?;
+
+        // Fail fast while the model's circuit is open.
+        let breaker = self.config.circuit_breaker.as_ref();
+        if let Some(breaker) = breaker {
+            breaker.check(&model)?;
+        }
 
         // Send the request
-        let response = self
-            .client
-            .post(url)
-            .headers(self.config.build_headers()?)
-            .json(&body)
-            .send()
-            .await?;
-
-        // Get the response status and body
-        let status = response.status();
-        let body = response.text().await?;
-
-        // Check if the HTTP response is successful.
-        if !status.is_success() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: body.clone(),
-                metadata: None,
-            });
+        let result = async {
+            let http_request = self
+                .client
+                .post(url)
+                .headers(self.config.build_headers()?)
+                .json(&body);
+            let response = http::send(&self.client, &self.config, http_request).await?;
+
+            // Get the response status and body
+            let status = response.status();
+            let body = http::read_body(response, &self.config).await?;
+            Ok((status, http::decode_json::<ChatCompletionResponse>(status, body)?))
+        }
+        .await;
+        if let Some(breaker) = breaker {
+            breaker.record(&model, &result);
         }
+        let (status, chat_response) = result?;
 
-        // Deserialize the JSON response
-        let chat_response: ChatCompletionResponse = serde_json::from_str(&body).map_err(|e| {
-            Error::ApiError {
-                code: status.as_u16(),
-                message: format!("Failed to decode JSON: {}. Body was: {}", e, body),
-                metadata: None,
-            }
+        // Extract the content from the response
+        let content = chat_response.content().ok_or_else(|| Error::ApiError {
+            code: status.as_u16(),
+            message: "No choices returned in response".into(),
+            metadata: None,
         })?;
+        
+        Self::parse_output(content, &schema_config)
+    }
 
-        // Extract the content from the response
-        if chat_response.choices.is_empty() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: "No choices returned in response".into(),
-                metadata: None,
-            });
+    /// Streams a structured output, yielding text deltas as they arrive and the parsed
+    /// value once the response is complete.
+    ///
+    /// The JSON is only parsed and validated after the stream ends, so partial output is
+    /// surfaced as text, e.g. for a progress indicator.
+    pub fn generate_stream<T>(
+        &self,
+        model: impl Into<String>,
+        messages: Vec<Message>,
+        schema_config: JsonSchemaConfig,
+    ) -> Pin<Box<dyn Stream<Item = Result<StructuredEvent<T>>> + Send>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let model = model.into();
+        let body = self
+            .check_model_support(&model)
+            .and_then(|_| Self::request_body(model, messages, &schema_config, true));
+        let client = self.client.clone();
+        let config = self.config.clone();
+
+        let stream = try_stream! {
+            let mut chunks = ChatApi::stream_body(client, config, body?);
+            let mut content = String::new();
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk?;
+                let delta = chunk
+                    .choices
+                    .into_iter()
+                    .next()
+                    .map(|choice| choice.delta.content)
+                    .unwrap_or_default();
+                if !delta.is_empty() {
+                    content.push_str(&delta);
+                    yield StructuredEvent::Delta(delta);
+                }
+            }
+
+            yield StructuredEvent::Complete(Self::parse_output(&content, &schema_config)?);
+        };
+
+        Box::pin(stream)
+    }
+
+    /// Fails with `Error::StructuredOutputNotSupported` when the cached model catalog lists
+    /// `model`'s supported parameters and a structured response format is not among them.
+    ///
+    /// Models that are not cached, or report no parameters, are given the benefit of the doubt.
+    fn check_model_support(&self, model: &str) -> Result<()> {
+        let supported = self
+            .config
+            .models_cache
+            .find_model(model)
+            .and_then(|info| info.supports_structured_output());
+        match supported {
+            Some(false) => Err(Error::StructuredOutputNotSupported),
+            _ => Ok(()),
         }
+    }
 
-        let content = &chat_response.choices[0].message.content;
-        
+    /// Serializes a chat request for `model` asking for output matching `schema_config`.
+    fn request_body(
+        model: String,
+        messages: Vec<Message>,
+        schema_config: &JsonSchemaConfig,
+        stream: bool,
+    ) -> Result<Value> {
+        // Build the request with structured output configuration
+        let mut request = ChatCompletionRequest::new(model, messages);
+        request.stream = Some(stream);
+        request.response_format = Some("json_schema".to_string());
+
+        // Build the request body with the schema nested under `json_schema`, the shape
+        // native structured-output models expect
+        let mut body = serde_json::to_value(&request).map_err(Error::SerializationError)?;
+        let response_format = ResponseFormatConfig {
+            format_type: "json_schema".to_string(),
+            json_schema: schema_config.clone(),
+        };
+        body["response_format"] =
+            serde_json::to_value(response_format).map_err(Error::SerializationError)?;
+        Ok(body)
+    }
+
+    /// Parses `content` as JSON, validates it in strict mode, and deserializes it into `T`.
+    fn parse_output<T: DeserializeOwned>(content: &str, schema_config: &JsonSchemaConfig) -> Result<T> {
         // Parse the content as JSON
         let json_result: Value = serde_json::from_str(content).map_err(|e| {
             Error::SchemaValidationError(format!("Failed to parse response as JSON: {}", e))
         })?;
         
-        // Basic validation of required fields if strict mode is enabled
+        // Validate the result against the schema if strict mode is enabled
         if schema_config.strict {
             // Convert schema_config.schema to a Value before validation
             let schema_value = serde_json::to_value(&schema_config.schema)
-                .map_err(|e| Error::SerializationError(e))?;
+                .map_err(Error::SerializationError)?;
                 
-            self.basic_schema_validation(&schema_value, &json_result)?;
+            validation::validate_against_schema(&schema_value, &json_result)?;
         }
         
         // Deserialize the result into the target type
@@ -126,81 +205,6 @@ impl StructuredApi {
             Error::SchemaValidationError(format!("Failed to deserialize response into target type: {}", e))
         })
     }
-    
-    /// Simple schema validation for required fields and top-level type checking
-    fn basic_schema_validation(&self, schema: &Value, data: &Value) -> Result<()> {
-        // Check if schema is an object
-        if !schema.is_object() {
-            return Err(Error::SchemaValidationError("Schema must be an object".into()));
-        }
-        
-        let schema_obj = schema.as_object().unwrap();
-        
-        // Check type
-        if let Some(type_val) = schema_obj.get("type") {
-            if let Some(type_str) = type_val.as_str() {
-                match type_str {
-                    "object" => {
-                        if !data.is_object() {
-                            return Err(Error::SchemaValidationError(
-                                "Expected an object but received a different type".into()
-                            ));
-                        }
-                    },
-                    "array" => {
-                        if !data.is_array() {
-                            return Err(Error::SchemaValidationError(
-                                "Expected an array but received a different type".into()
-                            ));
-                        }
-                    },
-                    "string" => {
-                        if !data.is_string() {
-                            return Err(Error::SchemaValidationError(
-                                "Expected a string but received a different type".into()
-                            ));
-                        }
-                    },
-                    "number" | "integer" => {
-                        if !data.is_number() {
-                            return Err(Error::SchemaValidationError(
-                                "Expected a number but received a different type".into()
-                            ));
-                        }
-                    },
-                    "boolean" => {
-                        if !data.is_boolean() {
-                            return Err(Error::SchemaValidationError(
-                                "Expected a boolean but received a different type".into()
-                            ));
-                        }
-                    },
-                    _ => {}
-                }
-            }
-        }
-        
-        // Check required fields
-        if let Some(required) = schema_obj.get("required") {
-            if let Some(required_arr) = required.as_array() {
-                let data_obj = match data.as_object() {
-                    Some(obj) => obj,
-                    None => return Ok(()), // Skip if not an object
-                };
-                
-                for field in required_arr {
-                    if let Some(field_str) = field.as_str() {
-                        if !data_obj.contains_key(field_str) {
-                            return Err(Error::SchemaValidationError(
-                                format!("Required field '{}' is missing", field_str)
-                            ));
-                        }
-                    }
-                }
-            }
-        }
-        
-        Ok(())
-    }
+
 }
 
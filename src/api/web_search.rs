@@ -1,8 +1,9 @@
-//// File: openrouter_api/src/api/web_search.rs
+// File: openrouter_api/src/api/web_search.rs
 use crate::{
     client::ClientConfig,
     error::{Error, Result},
     types::web_search::{WebSearchRequest, WebSearchResponse},
+    utils::{http, validation},
 };
 use reqwest::Client;
 use serde::de::DeserializeOwned;
@@ -23,6 +24,8 @@ impl WebSearchApi {
 
     /// Performs a web search with the given request and returns a structured response.
     pub async fn search(&self, request: WebSearchRequest) -> Result<WebSearchResponse> {
+        validation::validate_web_search_request(&request)?;
+
         // Join the base URL with the relative path "web/search".
         let url = self
             .config
@@ -34,21 +37,12 @@ impl WebSearchApi {
                 metadata: None,
             })?;
 
-        let response = self
+        let http_request = self
             .client
             .post(url)
             .headers(self.config.build_headers()?)
-            .json(&request)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(Error::ApiError {
-                code: response.status().as_u16(),
-                message: response.text().await?,
-                metadata: None,
-            });
-        }
+            .json(&request);
+        let response = http::send(&self.client, &self.config, http_request).await?;
 
         let search_response: WebSearchResponse = self.handle_response(response).await?;
         Ok(search_response)
@@ -60,25 +54,7 @@ impl WebSearchApi {
         T: DeserializeOwned,
     {
         let status = response.status();
-        let body = response.text().await?;
-        if !status.is_success() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: body.clone(),
-                metadata: None,
-            });
-        }
-        if body.trim().is_empty() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: "Empty response body".into(),
-                metadata: None,
-            });
-        }
-        serde_json::from_str::<T>(&body).map_err(|e| Error::ApiError {
-            code: status.as_u16(),
-            message: format!("Failed to decode JSON: {}. Body was: {}", e, body),
-            metadata: None,
-        })
+        let body = http::read_body(response, &self.config).await?;
+        http::decode_json(status, body)
     }
 }
@@ -0,0 +1,89 @@
+//! Synchronous facade over the async client, for scripts and CLI tools.
+//!
+//! Enabled by the `blocking` feature. [`BlockingClient`] owns a single-threaded Tokio
+//! runtime and blocks the calling thread on it for each request, much like
+//! `reqwest::blocking`. Calling it from inside an async runtime would panic in Tokio, so
+//! those calls fail with [`Error::ConfigError`] instead; use the async client there.
+
+use crate::client::{OpenRouterClient, Ready};
+use crate::error::{Error, Result};
+use crate::types::chat::{ChatCompletionRequest, ChatCompletionResponse};
+use crate::types::completion::{CompletionRequest, CompletionResponse};
+use crate::types::models::{ModelsRequest, ModelsResponse};
+use std::future::Future;
+use tokio::runtime::{Builder, Runtime};
+
+/// A client whose methods block until the response arrives.
+pub struct BlockingClient {
+    client: OpenRouterClient<Ready>,
+    /// Always `Some` until dropped
+    runtime: Option<Runtime>,
+}
+
+impl BlockingClient {
+    /// Wraps `client`, starting the runtime requests are driven on.
+    pub fn new(client: OpenRouterClient<Ready>) -> Result<Self> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| Error::ConfigError(format!("Failed to start blocking runtime: {}", e)))?;
+        Ok(Self {
+            client,
+            runtime: Some(runtime),
+        })
+    }
+
+    /// The wrapped async client.
+    pub fn inner(&self) -> &OpenRouterClient<Ready> {
+        &self.client
+    }
+
+    /// Sends a chat completion request and waits for the response.
+    pub fn chat_completion(&self, request: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+        let chat = self.client.chat()?;
+        self.block_on(chat.chat_completion(request))
+    }
+
+    /// Sends a text completion request and waits for the response.
+    pub fn text_completion(&self, request: CompletionRequest) -> Result<CompletionResponse> {
+        let completions = self.client.completions()?;
+        self.block_on(completions.text_completion(request))
+    }
+
+    /// Lists the available models, optionally filtered by `request`.
+    pub fn list_models(&self, request: Option<ModelsRequest>) -> Result<ModelsResponse> {
+        let models = self.client.models()?;
+        self.block_on(models.list_models(request))
+    }
+
+    /// Runs `future` to completion on the internal runtime.
+    ///
+    /// Fails instead of panicking when called from within an async runtime.
+    pub fn block_on<F: Future<Output = Result<T>>, T>(&self, future: F) -> Result<T> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err(Error::ConfigError(
+                "The blocking client cannot be used from within an async runtime; use the async client instead".into(),
+            ));
+        }
+        match &self.runtime {
+            Some(runtime) => runtime.block_on(future),
+            None => Err(Error::ConfigError("Blocking runtime has shut down".into())),
+        }
+    }
+}
+
+impl Drop for BlockingClient {
+    fn drop(&mut self) {
+        // Dropping a runtime inside an async context panics; shutting it down doesn't.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl OpenRouterClient<Ready> {
+    /// Wraps the client in a synchronous facade; see [`crate::blocking`].
+    pub fn blocking(self) -> Result<BlockingClient> {
+        BlockingClient::new(self)
+    }
+}
@@ -3,25 +3,80 @@
 #![allow(unused)]
 // Fix for unused imports in src/client.rs
 use crate::error::{Error, Result};
+use crate::mcp::client::ContextStrategy;
 use crate::types;
 use crate::types::routing::{PredefinedModelCoverageProfile, RouterConfig};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use std::fmt;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use std::time::Duration;
 use url::Url;
 
 // [rest of client.rs remains the same]
 
+/// Environment variable supplying the HTTP referer when none is set explicitly.
+pub const HTTP_REFERER_ENV: &str = "OPENROUTER_HTTP_REFERER";
+/// Environment variable supplying the site title when none is set explicitly.
+pub const SITE_TITLE_ENV: &str = "OPENROUTER_SITE_TITLE";
+
 /// Client configuration containing API key, base URL, and additional settings.
 #[derive(Debug, Clone)]
 pub struct ClientConfig {
     pub api_key: Option<String>,
+    pub api_keys: Option<crate::utils::auth::ApiKeyRing>,
     pub base_url: Url,
     pub http_referer: Option<String>,
     pub site_title: Option<String>,
     pub user_id: Option<String>,
     pub timeout: Duration,
+    pub request_timeout: Option<Duration>,
     pub retry_config: RetryConfig,
+    pub context_config: Option<ContextConfig>,
+    pub models_cache: crate::api::models::ModelsCache,
+    pub interceptors: crate::utils::http::Interceptors,
+    pub compress_requests: bool,
+    pub proxy: Option<ProxyConfig>,
+    pub stream_deadline: Option<Duration>,
+    pub circuit_breaker: Option<crate::utils::circuit_breaker::CircuitBreaker>,
+    pub default_max_tokens: Option<u32>,
+    pub idempotency: bool,
+    pub max_response_bytes: Option<usize>,
+    pub reserve_completion_tokens: Option<u32>,
+    pub default_system_prompt: Option<String>,
+    pub context_overflow_handler: Option<crate::utils::validation::ContextOverflowHandler>,
+}
+
+/// An explicit HTTP(S) proxy for all requests.
+///
+/// Hosts listed in the `NO_PROXY` environment variable bypass it.
+#[derive(Clone)]
+pub struct ProxyConfig {
+    pub url: Url,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("url", &self.url.as_str())
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl ProxyConfig {
+    /// Builds the `reqwest::Proxy` for this configuration.
+    fn to_reqwest(&self) -> Result<reqwest::Proxy> {
+        let mut proxy = reqwest::Proxy::all(self.url.as_str())
+            .map_err(|e| Error::ConfigError(format!("Invalid proxy URL: {}", e)))?;
+        if let Some(username) = &self.username {
+            proxy = proxy.basic_auth(username, self.password.as_deref().unwrap_or_default());
+        }
+        Ok(proxy.no_proxy(reqwest::NoProxy::from_env()))
+    }
 }
 
 /// Configuration for automatic retry behavior
@@ -44,12 +99,36 @@ impl Default for RetryConfig {
     }
 }
 
+/// Context fitting applied to chat requests before they are sent.
+#[derive(Clone)]
+pub struct ContextConfig {
+    pub strategy: Arc<dyn ContextStrategy>,
+    pub max_tokens: usize,
+}
+
+impl fmt::Debug for ContextConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContextConfig")
+            .field("max_tokens", &self.max_tokens)
+            .finish_non_exhaustive()
+    }
+}
+
 impl ClientConfig {
+    /// Fills an unset referer or site title from `lookup`, which maps a variable name such
+    /// as [`HTTP_REFERER_ENV`] to its value. Blank values are ignored.
+    pub(crate) fn fill_app_attribution_from(&mut self, lookup: impl Fn(&str) -> Option<String>) {
+        let value = |name: &str| lookup(name).filter(|value| !value.trim().is_empty());
+        self.http_referer = self.http_referer.take().or_else(|| value(HTTP_REFERER_ENV));
+        self.site_title = self.site_title.take().or_else(|| value(SITE_TITLE_ENV));
+    }
+
     /// Build HTTP headers required for making API calls.
     /// Returns an error if any header value cannot be constructed.
     pub fn build_headers(&self) -> Result<HeaderMap> {
         let mut headers = HeaderMap::new();
-        if let Some(ref key) = self.api_key {
+        let active_key = self.api_keys.as_ref().map(|ring| ring.active());
+        if let Some(key) = active_key.or(self.api_key.as_deref()) {
             let auth_header = HeaderValue::from_str(&format!("Bearer {}", key))
                 .map_err(|e| Error::ConfigError(format!("Invalid API key header format: {}", e)))?;
             headers.insert(AUTHORIZATION, auth_header);
@@ -88,19 +167,40 @@ pub struct OpenRouterClient<State = Unconfigured> {
     pub router_config: Option<RouterConfig>,
 }
 
+impl Default for OpenRouterClient<Unconfigured> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl OpenRouterClient<Unconfigured> {
     /// Creates a new unconfigured client.
     pub fn new() -> Self {
         Self {
             config: ClientConfig {
                 api_key: None,
+                api_keys: None,
                 // Default base URL; can be overridden with with_base_url().
                 base_url: "https://openrouter.ai/api/v1/".parse().unwrap(),
                 http_referer: None,
                 site_title: None,
                 user_id: None,
                 timeout: Duration::from_secs(30),
+                request_timeout: None,
                 retry_config: RetryConfig::default(),
+                context_config: None,
+                models_cache: crate::api::models::ModelsCache::default(),
+                interceptors: crate::utils::http::Interceptors::default(),
+                compress_requests: false,
+                proxy: None,
+                stream_deadline: None,
+                circuit_breaker: None,
+                default_max_tokens: None,
+                idempotency: true,
+                max_response_bytes: None,
+                reserve_completion_tokens: None,
+                default_system_prompt: None,
+                context_overflow_handler: None,
             },
             http_client: None,
             _state: PhantomData,
@@ -109,16 +209,30 @@ impl OpenRouterClient<Unconfigured> {
     }
 
     /// Sets the base URL and transitions to the NoAuth state.
-    /// The base URL must include a trailing slash.
+    /// The base URL must include a trailing slash, use `http` or `https`, and carry no
+    /// query or fragment.
     pub fn with_base_url(
         mut self,
         base_url: impl Into<String>,
     ) -> Result<OpenRouterClient<NoAuth>> {
-        self.config.base_url = Url::parse(&base_url.into()).map_err(|e| Error::ApiError {
+        let base_url = Url::parse(&base_url.into()).map_err(|e| Error::ApiError {
             code: 400,
             message: format!("Invalid base URL: {}", e),
             metadata: None,
         })?;
+        if !matches!(base_url.scheme(), "http" | "https") {
+            return Err(Error::ConfigError(format!(
+                "Invalid base URL scheme '{}': expected http or https",
+                base_url.scheme()
+            )));
+        }
+        if base_url.query().is_some() || base_url.fragment().is_some() {
+            return Err(Error::ConfigError(format!(
+                "Base URL must not contain a query or fragment: {}",
+                base_url
+            )));
+        }
+        self.config.base_url = base_url;
         Ok(self.transition_to_no_auth())
     }
 
@@ -134,17 +248,48 @@ impl OpenRouterClient<Unconfigured> {
 
 impl OpenRouterClient<NoAuth> {
     /// Supplies the API key and transitions to the Ready state.
-    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Result<OpenRouterClient<Ready>> {
+    ///
+    /// Empty or implausibly short keys are rejected with a `ConfigError`; use
+    /// `with_api_key_unchecked` for keys in an unusual format.
+    pub fn with_api_key(self, api_key: impl Into<String>) -> Result<OpenRouterClient<Ready>> {
+        let api_key = api_key.into();
+        crate::utils::auth::validate_api_key(&api_key)?;
+        self.with_api_key_unchecked(api_key)
+    }
+
+    /// Supplies the API key without validating its format and transitions to the Ready state.
+    pub fn with_api_key_unchecked(mut self, api_key: impl Into<String>) -> Result<OpenRouterClient<Ready>> {
         self.config.api_key = Some(api_key.into());
         self.transition_to_ready()
     }
 
+    /// Supplies several API keys and transitions to the Ready state.
+    ///
+    /// Requests use the first key until it is rejected. When any request fails with 401,
+    /// 402 or 403 it is retried once with each remaining key, and the key that worked stays
+    /// active for later requests. Every key is validated as in `with_api_key`.
+    pub fn with_api_keys(mut self, api_keys: Vec<String>) -> Result<OpenRouterClient<Ready>> {
+        let ring = crate::utils::auth::ApiKeyRing::new(api_keys)?;
+        self.config.api_key = Some(ring.active().to_string());
+        self.config.api_keys = Some(ring);
+        self.transition_to_ready()
+    }
+
     /// Optionally sets the request timeout.
     pub fn with_timeout(mut self, timeout: Duration) -> Self {
         self.config.timeout = timeout;
         self
     }
 
+    /// Optionally sets the referer and `X-Title` headers OpenRouter uses to attribute
+    /// requests to an app.
+    ///
+    /// Either header left unset is taken from the `OPENROUTER_HTTP_REFERER` or
+    /// `OPENROUTER_SITE_TITLE` environment variable when the client becomes ready.
+    pub fn with_app_attribution(self, referer: impl Into<String>, title: impl Into<String>) -> Self {
+        self.with_http_referer(referer).with_site_title(title)
+    }
+
     /// Optionally sets the HTTP referer header.
     pub fn with_http_referer(mut self, referer: impl Into<String>) -> Self {
         self.config.http_referer = Some(referer.into());
@@ -163,12 +308,176 @@ impl OpenRouterClient<NoAuth> {
         self
     }
 
+    /// Optionally supplies the `reqwest::Client` used for requests, e.g. one with a proxy,
+    /// custom TLS settings or a test double.
+    ///
+    /// Authentication and attribution headers are still added to every request; the
+    /// timeout set with `with_timeout` is not applied to a supplied client.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Optionally gzips large request bodies (such as long documents in the prompt) and
+    /// marks them with `Content-Encoding: gzip`. Compressed responses are always decoded
+    /// transparently.
+    pub fn with_compression(mut self, enabled: bool) -> Self {
+        self.config.compress_requests = enabled;
+        self
+    }
+
+    /// Optionally bounds the total duration of streaming requests.
+    ///
+    /// A stream that has not finished within `deadline` of being started ends with
+    /// `Error::TimeoutError`. For streams the deadline replaces the request timeout set with
+    /// `with_timeout`, which would otherwise cut off long answers that are still arriving.
+    pub fn with_stream_deadline(mut self, deadline: Duration) -> Self {
+        self.config.stream_deadline = Some(deadline);
+        self
+    }
+
+    /// Optionally caps the size of response bodies at `limit` bytes.
+    ///
+    /// Reading stops with `Error::ResponseTooLarge` once a body exceeds the limit, instead
+    /// of buffering whatever the endpoint sends. For streams the limit applies to each line.
+    pub fn with_max_response_bytes(mut self, limit: usize) -> Self {
+        self.config.max_response_bytes = Some(limit);
+        self
+    }
+
+    /// Optionally routes all requests through the proxy at `url`.
+    ///
+    /// Without an explicit proxy, the `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`
+    /// environment variables are honored. Like the timeout, this only applies to the
+    /// client built by this crate, not one supplied with `with_http_client`.
+    pub fn with_proxy(mut self, url: impl AsRef<str>) -> Result<Self> {
+        let url = Url::parse(url.as_ref())
+            .map_err(|e| Error::ConfigError(format!("Invalid proxy URL: {}", e)))?;
+        self.config.proxy = Some(ProxyConfig {
+            url,
+            username: None,
+            password: None,
+        });
+        Ok(self)
+    }
+
+    /// Optionally sets basic-auth credentials for the proxy configured with `with_proxy`.
+    pub fn with_proxy_auth(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Result<Self> {
+        let proxy = self.config.proxy.as_mut().ok_or_else(|| {
+            Error::ConfigError("with_proxy_auth requires a proxy set with with_proxy".into())
+        })?;
+        proxy.username = Some(username.into());
+        proxy.password = Some(password.into());
+        Ok(self)
+    }
+
+    /// Optionally stops sending chat requests to a model for `cooldown` after it fails
+    /// `failure_threshold` times in a row, failing fast with `Error::ModelNotAvailable`.
+    /// Streaming chat and structured output requests, streamed or not, are covered too.
+    ///
+    /// Only transient failures (network errors, timeouts, rate limits and server errors that
+    /// outlast the retry budget) count; the breaker is shared by all APIs of this client.
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, cooldown: Duration) -> Self {
+        self.config.circuit_breaker = Some(crate::utils::circuit_breaker::CircuitBreaker::new(
+            failure_threshold,
+            cooldown,
+        ));
+        self
+    }
+
+    /// Optionally sets the `max_tokens` sent with chat requests that don't set their own,
+    /// since some providers default to very short outputs.
+    pub fn with_default_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.config.default_max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Optionally requires chat requests to leave room for `tokens` completion tokens.
+    ///
+    /// Requests to a model whose context length is cached fail with
+    /// `Error::ContextLengthExceeded` when the estimated prompt plus the larger of their
+    /// `max_tokens` and `tokens` does not fit, instead of being truncated by the provider.
+    pub fn with_reserved_completion_tokens(mut self, tokens: u32) -> Self {
+        self.config.reserve_completion_tokens = Some(tokens);
+        self
+    }
+
+    /// Optionally calls `handler` for chat requests whose estimated prompt plus `max_tokens`
+    /// exceeds the context length of a cached model, e.g. to log a warning.
+    ///
+    /// Not called when completion tokens are reserved, since such requests fail instead.
+    pub fn with_context_overflow_handler(
+        mut self,
+        handler: impl Fn(&crate::utils::validation::ContextOverflow) + Send + Sync + 'static,
+    ) -> Self {
+        self.config.context_overflow_handler =
+            Some(crate::utils::validation::ContextOverflowHandler::new(handler));
+        self
+    }
+
+    /// Optionally starts every chat request with a system message containing `prompt`.
+    ///
+    /// Requests whose first message is already a system message are sent unchanged, so a
+    /// call site can override the default by supplying its own.
+    pub fn with_default_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.config.default_system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Controls whether chat requests carry an `Idempotency-Key` header, on by default.
+    ///
+    /// The key is generated once per request and reused by its retries, so retrying after
+    /// an error status cannot act twice. With idempotency off, only rate-limited requests and
+    /// connection failures (which never reached the server) are retried.
+    pub fn with_idempotency(mut self, enabled: bool) -> Self {
+        self.config.idempotency = enabled;
+        self
+    }
+
     /// Optionally configures retry behavior.
     pub fn with_retry_config(mut self, retry_config: RetryConfig) -> Self {
         self.config.retry_config = retry_config;
         self
     }
 
+    /// Optionally fits the messages of every chat request to `max_tokens` using `strategy`.
+    pub fn with_context_strategy(
+        mut self,
+        strategy: impl ContextStrategy + 'static,
+        max_tokens: usize,
+    ) -> Self {
+        self.config.context_config = Some(ContextConfig {
+            strategy: Arc::new(strategy),
+            max_tokens,
+        });
+        self
+    }
+
+    /// Optionally sets how long model listings are cached by
+    /// `ModelsApi::list_models_cached`. Defaults to five minutes.
+    pub fn with_models_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.config.models_cache = crate::api::models::ModelsCache::new(ttl);
+        self
+    }
+
+    /// Optionally registers an interceptor run around every API request.
+    pub fn with_interceptor(mut self, interceptor: impl crate::utils::http::Interceptor + 'static) -> Self {
+        self.config.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Optionally logs every outgoing request, including its JSON body, to stderr.
+    ///
+    /// The API key is redacted from the logged headers. Meant for debugging serialization
+    /// and provider behavior; prompts are logged in full.
+    pub fn with_request_logging(self) -> Self {
+        self.with_interceptor(crate::utils::http::RequestLogger)
+    }
+
     /// Configures Model Coverage Profile for model selection and routing.
     pub fn with_model_coverage_profile(mut self, profile: PredefinedModelCoverageProfile) -> Self {
         self.router_config = Some(RouterConfig {
@@ -178,17 +487,28 @@ impl OpenRouterClient<NoAuth> {
         self
     }
 
-    fn transition_to_ready(self) -> Result<OpenRouterClient<Ready>> {
-        let headers = self.config.build_headers()?;
-        
-        // Build a client with retry capabilities
-        let client_builder = reqwest::Client::builder()
-            .timeout(self.config.timeout)
-            .default_headers(headers);
-        
-        let http_client = client_builder
-            .build()
-            .map_err(|e| Error::ConfigError(format!("Failed to create HTTP client: {}", e)))?;
+    fn transition_to_ready(mut self) -> Result<OpenRouterClient<Ready>> {
+        // Explicit attribution wins over the environment.
+        self.config.fill_app_attribution_from(|name| std::env::var(name).ok());
+
+        let http_client = match self.http_client {
+            Some(http_client) => http_client,
+            None => {
+                let headers = self.config.build_headers()?;
+                
+                // Build a client with retry capabilities
+                let mut client_builder = reqwest::Client::builder()
+                    .timeout(self.config.timeout)
+                    .default_headers(headers);
+                if let Some(proxy) = &self.config.proxy {
+                    client_builder = client_builder.proxy(proxy.to_reqwest()?);
+                }
+                
+                client_builder
+                    .build()
+                    .map_err(|e| Error::ConfigError(format!("Failed to create HTTP client: {}", e)))?
+            }
+        };
         
         Ok(OpenRouterClient {
             config: self.config,
@@ -199,14 +519,48 @@ impl OpenRouterClient<NoAuth> {
     }
 }
 
+/// Ready clients share their connection pool when cloned.
+impl Clone for OpenRouterClient<Ready> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            http_client: self.http_client.clone(),
+            _state: PhantomData,
+            router_config: self.router_config.clone(),
+        }
+    }
+}
+
 impl OpenRouterClient<Ready> {
+    /// Index of the API key in use when several were supplied with `with_api_keys`.
+    pub fn active_api_key_index(&self) -> Option<usize> {
+        self.config.api_keys.as_ref().map(|ring| ring.active_index())
+    }
+
+    /// Returns the client with a different request timeout.
+    ///
+    /// Clone the client first to keep the original timeout for other requests. Unlike the
+    /// timeout set before authentication, this one also applies to a supplied HTTP client.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self.config.request_timeout = Some(timeout);
+        self
+    }
+
     /// Provides access to the chat endpoint.
+    ///
+    /// Provider preferences from the router configuration are applied to every request
+    /// that doesn't specify its own.
     pub fn chat(&self) -> Result<crate::api::chat::ChatApi> {
         let client = self
             .http_client
             .clone()
             .ok_or_else(|| Error::ConfigError("HTTP client is missing".into()))?;
-        Ok(crate::api::chat::ChatApi::new(client, &self.config))
+        let preferences = self
+            .router_config
+            .as_ref()
+            .and_then(|router_config| router_config.provider_preferences.clone());
+        Ok(crate::api::chat::ChatApi::new(client, &self.config).with_provider_preferences(preferences))
     }
 
     /// Provides access to the completions endpoint.
@@ -227,6 +581,15 @@ impl OpenRouterClient<Ready> {
         Ok(crate::api::models::ModelsApi::new(client, &self.config))
     }
 
+    /// Provides access to the moderation endpoint.
+    pub fn moderation(&self) -> Result<crate::api::moderation::ModerationApi> {
+        let client = self
+            .http_client
+            .clone()
+            .ok_or_else(|| Error::ConfigError("HTTP client is missing".into()))?;
+        Ok(crate::api::moderation::ModerationApi::new(client, &self.config))
+    }
+
     /// Provides access to the structured output endpoint.
     pub fn structured(&self) -> Result<crate::api::structured::StructuredApi> {
         let client = self
@@ -308,26 +671,8 @@ pub fn chat_request_builder(
         T: serde::de::DeserializeOwned,
     {
         let status = response.status();
-        let body = response.text().await?;
-        if !status.is_success() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: body.clone(),
-                metadata: None,
-            });
-        }
-        if body.trim().is_empty() {
-            return Err(Error::ApiError {
-                code: status.as_u16(),
-                message: "Empty response body".into(),
-                metadata: None,
-            });
-        }
-        serde_json::from_str::<T>(&body).map_err(|e| Error::ApiError {
-            code: status.as_u16(),
-            message: format!("Failed to decode JSON: {}. Body was: {}", e, body),
-            metadata: None,
-        })
+        let body = crate::utils::http::read_body(response, &self.config).await?;
+        crate::utils::http::decode_json(status, body)
     }
 
     /// Validates tool calls in a chat completion response.
@@ -6,9 +6,14 @@ use thiserror::Error;
 /// OpenRouter API error details
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiErrorDetails {
-    /// Error code (e.g., "insufficient_quota")
+    /// Error code (e.g., "insufficient_quota"); numeric codes are kept as strings
+    #[serde(default, deserialize_with = "deserialize_code")]
     pub code: Option<String>,
     
+    /// Human-readable error message
+    #[serde(default)]
+    pub message: Option<String>,
+    
     /// HTTP status code
     pub status: Option<u16>,
     
@@ -19,6 +24,37 @@ pub struct ApiErrorDetails {
     pub metadata: Option<serde_json::Value>,
 }
 
+impl ApiErrorDetails {
+    /// Parses the details from an error response body, either wrapped in an `error`
+    /// object (as OpenRouter sends them) or at the top level.
+    ///
+    /// Returns `None` for bodies that aren't JSON objects or carry no details.
+    pub fn from_body(body: &str) -> Option<Self> {
+        let value: Value = serde_json::from_str(body).ok()?;
+        let object = value.get("error").filter(|error| error.is_object()).unwrap_or(&value);
+        let details: Self = serde_json::from_value(object.clone()).ok()?;
+
+        let empty = details.code.is_none()
+            && details.message.is_none()
+            && details.status.is_none()
+            && details.provider.is_none()
+            && details.metadata.is_none();
+        (!empty).then_some(details)
+    }
+}
+
+/// Accepts error codes sent either as strings or as numbers.
+fn deserialize_code<'de, D>(deserializer: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::String(code)) => Some(code),
+        Some(Value::Null) | None => None,
+        Some(other) => Some(other.to_string()),
+    })
+}
+
 /// Centralized error type for the OpenRouter client library.
 #[derive(Error, Debug)]
 pub enum Error {
@@ -32,8 +68,23 @@ pub enum Error {
         metadata: Option<Value>,
     },
 
-    #[error("Rate limit exceeded: {0}")]
-    RateLimitExceeded(String),
+    #[error("Rate limit exceeded: {message}")]
+    RateLimitExceeded {
+        message: String,
+        metadata: Option<Value>,
+    },
+
+    #[error("Authentication failed: {message}")]
+    AuthenticationError {
+        message: String,
+        metadata: Option<Value>,
+    },
+
+    #[error("Insufficient credits: {message}")]
+    InsufficientCredits {
+        message: String,
+        metadata: Option<Value>,
+    },
 
     #[error("Invalid configuration: {0}")]
     ConfigError(String),
@@ -47,6 +98,14 @@ pub enum Error {
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
 
+    #[error("Failed to decode response (status {status}): {source}. Body was: {body}")]
+    DeserializationError {
+        status: u16,
+        body: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
     #[error("Model not available: {0}")]
     ModelNotAvailable(String),
 
@@ -56,6 +115,9 @@ pub enum Error {
     #[error("Streaming error: {0}")]
     StreamingError(String),
 
+    #[error("Transport error: {0}")]
+    TransportError(String),
+
     #[error("Context length exceeded for model {model}: {message}")]
     ContextLengthExceeded {
         model: String,
@@ -65,6 +127,18 @@ pub enum Error {
     #[error("Timeout error: {0}")]
     TimeoutError(String),
 
+    /// An error object returned by a JSON-RPC (MCP) server, with its code as sent.
+    #[error("JSON-RPC error {code}: {message}")]
+    JsonRpcError {
+        code: i32,
+        message: String,
+        data: Option<Value>,
+    },
+
+    /// A response body larger than the configured `max_response_bytes`.
+    #[error("Response body exceeds the limit of {limit} bytes")]
+    ResponseTooLarge { limit: usize },
+
     #[error("Unknown error")]
     Unknown,
 }
@@ -72,30 +146,66 @@ pub enum Error {
 pub type Result<T> = std::result::Result<T, Error>;
 
 impl Error {
+    /// Creates the error for a non-success HTTP status and its response body.
+    ///
+    /// 401 and 403 become [`Error::AuthenticationError`], 402 becomes
+    /// [`Error::InsufficientCredits`], 429 becomes [`Error::RateLimitExceeded`], and every
+    /// other status an [`Error::ApiError`]. The `metadata` of each holds the body's
+    /// [`ApiErrorDetails`], if it has any.
+    pub fn from_status(code: u16, message: String) -> Self {
+        let metadata = || {
+            ApiErrorDetails::from_body(&message).and_then(|details| serde_json::to_value(details).ok())
+        };
+        match code {
+            401 | 403 => Error::AuthenticationError {
+                metadata: metadata(),
+                message,
+            },
+            402 => Error::InsufficientCredits {
+                metadata: metadata(),
+                message,
+            },
+            429 => Error::RateLimitExceeded {
+                metadata: metadata(),
+                message,
+            },
+            _ => Error::ApiError {
+                code,
+                metadata: metadata(),
+                message,
+            },
+        }
+    }
+
     /// Creates an API error from a given HTTP response.
     pub async fn from_response(response: Response) -> Result<Self> {
         let status = response.status().as_u16();
         let text = response.text().await.unwrap_or_default();
-        
-        // Try to parse structured API error response
-        if let Ok(api_error) = serde_json::from_str::<ApiErrorDetails>(&text) {
-            return Ok(Error::ApiError {
-                code: status,
-                message: text,
-                metadata: Some(serde_json::to_value(api_error).unwrap_or_default()),
-            });
-        }
-        
-        // Handle rate limiting specifically
-        if status == 429 {
-            return Ok(Error::RateLimitExceeded(text));
+        Ok(Error::from_status(status, text))
+    }
+
+    /// The structured details of an error created from an HTTP response, if the response
+    /// carried any.
+    pub fn api_error_details(&self) -> Option<ApiErrorDetails> {
+        match self {
+            Error::ApiError {
+                metadata: Some(metadata),
+                ..
+            }
+            | Error::AuthenticationError {
+                metadata: Some(metadata),
+                ..
+            }
+            | Error::InsufficientCredits {
+                metadata: Some(metadata),
+                ..
+            }
+            | Error::RateLimitExceeded {
+                metadata: Some(metadata),
+                ..
+            } => serde_json::from_value(metadata.clone()).ok(),
+            _ => None,
         }
-        
-        Ok(Error::ApiError {
-            code: status,
-            message: text,
-            metadata: None,
-        })
     }
 }
 
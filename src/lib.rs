@@ -3,10 +3,14 @@
 //! A Rust client for interfacing with the OpenRouter API.
 
 pub mod api;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod client;
 pub mod error;
 pub mod mcp;  // Add the MCP module
 pub mod models;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 pub mod tests;
 pub mod types;
 pub mod utils;
@@ -15,6 +19,6 @@ pub use error::{Error, Result};
 pub use types::*;
 
 pub use client::{NoAuth, OpenRouterClient, Ready, Unconfigured};
-pub use mcp::client::MCPClient;  // Re-export MCPClient
+pub use mcp::client::{ConversationResult, MCPClient};  // Re-export MCPClient
 pub use mcp::types as mcp_types;  // Re-export MCP types
 
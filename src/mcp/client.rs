@@ -1,58 +1,322 @@
 //! MCP client implementation for connecting to MCP servers.
 
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value;
 use tokio::sync::Mutex;
-use url::Url;
 
+use crate::client::RetryConfig;
 use crate::error::{Error, Result};
+use crate::mcp::transport::{HttpTransport, StdioTransport, Transport, WebSocketTransport};
 use crate::mcp::types::*;
+use crate::client::{OpenRouterClient, Ready};
+use crate::types::chat::{ChatCompletionRequest, ChatCompletionResponse, Message, Usage};
+use crate::types::conversation::Conversation;
+use crate::utils::validation::TokenCounter;
+
+/// Handler invoked for server-initiated sampling requests.
+pub type SamplingHandler = dyn Fn(SamplingParams) -> Result<SamplingResponse> + Send + Sync;
+
+/// JSON-RPC error code for an unknown method.
+pub const METHOD_NOT_FOUND: i32 = -32601;
+/// JSON-RPC error code for malformed parameters.
+pub const INVALID_PARAMS: i32 = -32602;
+/// JSON-RPC error code for a failure inside the handler.
+pub const INTERNAL_ERROR: i32 = -32603;
+
+/// Default time to wait for a response to a single request.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Strategy for fitting a conversation into a model's context window.
+#[async_trait]
+pub trait ContextStrategy: Send + Sync {
+    /// Reduce `messages` so the conversation fits within `max_tokens`.
+    async fn fit_to_context(&self, messages: Vec<Message>, max_tokens: usize) -> Result<Vec<Message>>;
+
+    /// Reduce `messages` to fit within `max_tokens` as measured by `counter`.
+    ///
+    /// Strategies that budget by tokens should override this; the default ignores
+    /// `counter` and calls [`fit_to_context`](Self::fit_to_context).
+    async fn fit_to_context_with_counter(
+        &self,
+        messages: Vec<Message>,
+        max_tokens: usize,
+        counter: &dyn TokenCounter,
+    ) -> Result<Vec<Message>> {
+        let _ = counter;
+        self.fit_to_context(messages, max_tokens).await
+    }
+
+    /// Compress `messages` without a specific token budget.
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>>;
+
+    /// Fit a conversation's messages within `max_tokens`, keeping its metadata.
+    async fn fit_conversation(&self, conversation: Conversation, max_tokens: usize) -> Result<Conversation> {
+        let Conversation { messages, metadata } = conversation;
+        Ok(Conversation {
+            messages: self.fit_to_context(messages, max_tokens).await?,
+            metadata,
+        })
+    }
+
+    /// Estimate how many tokens `messages` occupy.
+    ///
+    /// Defaults to the character-based heuristic in [`crate::utils::validation`]; override
+    /// it to budget with a model's real tokenizer. Token budgets are measured with it
+    /// unless a [`TokenCounter`] is supplied, e.g. through [`MCPClient::with_token_counter`].
+    fn estimate_token_count(&self, messages: &[Message]) -> usize {
+        messages
+            .iter()
+            .map(|msg| crate::utils::validation::estimate_message_tokens(msg) as usize)
+            .sum()
+    }
+}
+
+/// [`TokenCounter`] that measures with a strategy's
+/// [`estimate_token_count`](ContextStrategy::estimate_token_count).
+pub(crate) struct EstimateCounter<'a, S: ?Sized>(pub(crate) &'a S);
+
+#[async_trait]
+impl<S: ContextStrategy + ?Sized> TokenCounter for EstimateCounter<'_, S> {
+    async fn count_message_tokens(&self, message: &Message) -> Result<usize> {
+        Ok(self.0.estimate_token_count(std::slice::from_ref(message)))
+    }
+
+    async fn count_tokens(&self, messages: &[Message]) -> Result<usize> {
+        Ok(self.0.estimate_token_count(messages))
+    }
+}
+
+/// Processor that condenses conversation history, typically with the help of a model.
+#[async_trait]
+pub trait ContextProcessor: Send + Sync {
+    /// Replace older history with a condensed equivalent.
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>>;
+
+    /// Produce a single message summarizing `messages`.
+    async fn summarize(&self, messages: Vec<Message>) -> Result<Message>;
+
+    /// Extract the key points from `messages`, one per entry.
+    async fn extract_key_info(&self, messages: Vec<Message>) -> Result<Vec<String>>;
+}
+
+/// Outcome of a conversation turn run through [`MCPClient::run_conversation`].
+#[derive(Debug)]
+pub struct ConversationResult {
+    /// The conversation as sent after context fitting, followed by the model's reply
+    pub messages: Vec<Message>,
+    /// The raw chat completion response
+    pub response: ChatCompletionResponse,
+    /// Token usage reported for the turn
+    pub usage: Option<Usage>,
+}
 
 /// MCP client for connecting to and interacting with MCP servers.
+///
+/// Clones are cheap and share the connection, capabilities and sampling handler, so one
+/// client can be handed to several tasks.
+#[derive(Clone)]
 pub struct MCPClient {
-    /// The HTTP client for making requests
-    client: reqwest::Client,
-    /// The base URL of the MCP server
-    server_url: Url,
+    /// The transport carrying JSON-RPC messages to and from the server
+    transport: Arc<dyn Transport>,
     /// Server capabilities once initialized
-    capabilities: Mutex<Option<ServerCapabilities>>,
+    capabilities: Arc<Mutex<Option<ServerCapabilities>>>,
+    /// Capabilities this client last initialized with, reused by `reinitialize`
+    client_capabilities: Arc<Mutex<Option<ClientCapabilities>>>,
+    /// Responses received while another request was waiting, keyed by request id
+    pending: Arc<Mutex<HashMap<String, JsonRpcResponse>>>,
+    /// Serializes reads from the transport
+    reader: Arc<Mutex<()>>,
+    /// Handler for server-initiated sampling requests
+    sampling_handler: Arc<RwLock<Option<Arc<SamplingHandler>>>>,
+    /// Time to wait for a response before an attempt is abandoned
+    timeout: Duration,
+    /// Retry policy for failed or timed-out requests
+    retry_config: RetryConfig,
+    /// Strategy used to fit conversations into the context window
+    context_strategy: Option<Arc<dyn ContextStrategy>>,
+    /// Processor for condensing conversation history
+    processor: Option<Arc<dyn ContextProcessor>>,
+    /// Token budget for conversations
+    max_context_size: usize,
+    /// Counter the context strategy budgets with; the validator's heuristic when unset
+    token_counter: Option<Arc<dyn TokenCounter>>,
 }
 
 impl MCPClient {
-    /// Create a new MCP client for the given server URL.
-    pub fn new(server_url: impl AsRef<str>) -> Result<Self> {
-        let server_url = Url::parse(server_url.as_ref())
-            .map_err(|e| Error::ConfigError(format!("Invalid server URL: {}", e)))?;
-            
-        Ok(Self {
-            client: reqwest::Client::new(),
-            server_url,
-            capabilities: Mutex::new(None),
+    /// Create a new MCP client on top of the given transport.
+    pub fn new(transport: impl Transport + 'static) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            capabilities: Arc::new(Mutex::new(None)),
+            client_capabilities: Arc::new(Mutex::new(None)),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            reader: Arc::new(Mutex::new(())),
+            sampling_handler: Arc::new(RwLock::new(None)),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            retry_config: RetryConfig::default(),
+            context_strategy: None,
+            processor: None,
+            max_context_size: usize::MAX,
+            token_counter: None,
+        }
+    }
+
+    /// Create a new MCP client that talks to the server over HTTP POST.
+    pub fn with_http(server_url: impl AsRef<str>) -> Result<Self> {
+        Ok(Self::new(HttpTransport::new(server_url)?))
+    }
+
+    /// Create a new MCP client over HTTP POST using a preconfigured `reqwest::Client`.
+    pub fn with_http_client(client: reqwest::Client, server_url: impl AsRef<str>) -> Result<Self> {
+        Ok(Self::new(HttpTransport::with_client(client, server_url)?))
+    }
+
+    /// Create a new MCP client over a persistent WebSocket connection.
+    pub async fn with_websocket(server_url: impl AsRef<str>) -> Result<Self> {
+        Ok(Self::new(WebSocketTransport::connect(server_url).await?))
+    }
+
+    /// Create a new MCP client for a local server spawned as a child process.
+    ///
+    /// The server is expected to speak newline-delimited JSON-RPC on stdin/stdout.
+    pub fn with_stdio<I, S>(command: impl AsRef<std::ffi::OsStr>, args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        Ok(Self::new(StdioTransport::spawn(command, args)?))
+    }
+
+    /// Sets how long each request attempt waits for its response (default 30 seconds).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Configures retry behavior for requests that fail or time out.
+    ///
+    /// Transport failures and timeouts are always retried; API errors only when their
+    /// status code is listed in `retry_on_status_codes`.
+    pub fn with_retry_config(mut self, retry_config: RetryConfig) -> Self {
+        self.retry_config = retry_config;
+        self
+    }
+
+    /// Fits conversations to `max_context_size` tokens using `strategy`.
+    pub fn with_context_strategy(
+        mut self,
+        strategy: impl ContextStrategy + 'static,
+        max_context_size: usize,
+    ) -> Self {
+        self.context_strategy = Some(Arc::new(strategy));
+        self.max_context_size = max_context_size;
+        self
+    }
+
+    /// Counts tokens with `counter` when fitting conversations to the context window.
+    ///
+    /// Without one, strategies measure with their own
+    /// [`estimate_token_count`](ContextStrategy::estimate_token_count).
+    pub fn with_token_counter(mut self, counter: impl TokenCounter + 'static) -> Self {
+        self.token_counter = Some(Arc::new(counter));
+        self
+    }
+
+    /// Attaches a processor for condensing conversation history.
+    pub fn with_processor(mut self, processor: Arc<dyn ContextProcessor>) -> Self {
+        self.processor = Some(processor);
+        self
+    }
+
+    /// The configured context processor, if any.
+    pub fn processor(&self) -> Option<&Arc<dyn ContextProcessor>> {
+        self.processor.as_ref()
+    }
+
+    /// Fit `messages` to the context window using the configured strategy.
+    ///
+    /// Messages are returned unchanged when no strategy is configured.
+    pub async fn fit_to_context(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
+        match &self.context_strategy {
+            Some(strategy) => {
+                let estimate = EstimateCounter(strategy.as_ref());
+                let counter: &dyn TokenCounter = match &self.token_counter {
+                    Some(counter) => counter.as_ref(),
+                    None => &estimate,
+                };
+                strategy
+                    .fit_to_context_with_counter(messages, self.max_context_size, counter)
+                    .await
+            }
+            None => Ok(messages),
+        }
+    }
+
+    /// Run one conversation turn: fit `request.messages` to the context window with the
+    /// configured strategy, send the request through `client`, and collect the result.
+    pub async fn run_conversation(
+        &self,
+        client: &OpenRouterClient<Ready>,
+        mut request: ChatCompletionRequest,
+    ) -> Result<ConversationResult> {
+        request.messages = self.fit_to_context(request.messages).await?;
+        let mut messages = request.messages.clone();
+
+        let response = client.chat()?.chat_completion(request).await?;
+        if let Some(choice) = response.first_choice() {
+            messages.push(choice.message.clone());
+        }
+
+        Ok(ConversationResult {
+            messages,
+            usage: response.usage.clone(),
+            response,
         })
     }
     
     /// Generate a simple request ID
     fn generate_id() -> String {
-        // Use a simple timestamp-based ID instead of UUID
+        // Use a simple timestamp-based ID instead of UUID; the counter keeps IDs unique
+        // when several are generated within the clock's resolution (e.g. for a batch).
+        use std::sync::atomic::{AtomicU64, Ordering};
         use std::time::{SystemTime, UNIX_EPOCH};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_nanos();
-        format!("req-{}", timestamp)
+        format!("req-{}-{}", timestamp, COUNTER.fetch_add(1, Ordering::Relaxed))
     }
     
     /// Initialize the connection to the MCP server.
+    ///
+    /// Fails with `Error::ConfigError` if the server answers with a protocol version
+    /// other than the one requested, leaving the client uninitialized.
     pub async fn initialize(&self, client_capabilities: ClientCapabilities) -> Result<ServerCapabilities> {
+        let requested_version = client_capabilities.protocol_version.clone();
+        *self.client_capabilities.lock().await = Some(client_capabilities.clone());
         let request = JsonRpcRequest {
             jsonrpc: "2.0".to_string(),
             id: Self::generate_id(),
             method: "initialize".to_string(),
             params: Some(serde_json::to_value(InitializeParams {
                 capabilities: client_capabilities,
-            }).map_err(|e| Error::SerializationError(e))?),
+            }).map_err(Error::SerializationError)?),
         };
         
         let response = self.send_request(request).await?;
         let capabilities = self.parse_response::<ServerCapabilities>(response)?;
+
+        if capabilities.protocol_version != requested_version {
+            return Err(Error::ConfigError(format!(
+                "MCP server speaks protocol version {}, but this client requested {}",
+                capabilities.protocol_version, requested_version
+            )));
+        }
         
         // Store the server capabilities
         let mut caps = self.capabilities.lock().await;
@@ -61,6 +325,21 @@ impl MCPClient {
         Ok(capabilities)
     }
     
+    /// Re-runs the initialization handshake, e.g. after the server restarted or changed
+    /// the tools it advertises, and returns the fresh capabilities.
+    ///
+    /// Uses the client capabilities from the last call to `initialize`. The cached server
+    /// capabilities and any unclaimed responses are dropped first, so the client reports
+    /// itself uninitialized if the handshake fails.
+    pub async fn reinitialize(&self) -> Result<ServerCapabilities> {
+        let client_capabilities = self.client_capabilities.lock().await.clone().ok_or_else(|| {
+            Error::ConfigError("MCP client cannot reinitialize before initialize".into())
+        })?;
+        *self.capabilities.lock().await = None;
+        self.pending.lock().await.clear();
+        self.initialize(client_capabilities).await
+    }
+
     /// Get a resource from the server.
     pub async fn get_resource(&self, params: GetResourceParams) -> Result<ResourceResponse> {
         // Check if initialized
@@ -70,13 +349,56 @@ impl MCPClient {
             jsonrpc: "2.0".to_string(),
             id: Self::generate_id(),
             method: "getResource".to_string(),
-            params: Some(serde_json::to_value(params).map_err(|e| Error::SerializationError(e))?),
+            params: Some(serde_json::to_value(params).map_err(Error::SerializationError)?),
         };
         
         let response = self.send_request(request).await?;
         self.parse_response::<ResourceResponse>(response)
     }
     
+    /// List the resources available on the server.
+    pub async fn list_resources(&self) -> Result<Vec<Resource>> {
+        self.ensure_initialized().await?;
+        
+        let request = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Self::generate_id(),
+            method: "resources/list".to_string(),
+            params: None,
+        };
+        
+        let response = self.send_request(request).await?;
+        Ok(self.parse_response::<ListResourcesResponse>(response)?.resources)
+    }
+    
+    /// Fetch several resources in a single JSON-RPC batch round trip.
+    ///
+    /// Results are returned in the same order as `ids`; the first failed fetch fails the call.
+    pub async fn get_resources(&self, ids: Vec<String>) -> Result<Vec<ResourceResponse>> {
+        self.ensure_initialized().await?;
+        
+        let requests = ids
+            .into_iter()
+            .map(|id| {
+                Ok(JsonRpcRequest {
+                    jsonrpc: "2.0".to_string(),
+                    id: Self::generate_id(),
+                    method: "getResource".to_string(),
+                    params: Some(
+                        serde_json::to_value(GetResourceParams { id, parameters: None })
+                            .map_err(Error::SerializationError)?,
+                    ),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        
+        self.send_batch(requests)
+            .await?
+            .into_iter()
+            .map(|response| self.parse_response::<ResourceResponse>(response))
+            .collect()
+    }
+    
     /// Call a tool on the server.
     pub async fn tool_call(&self, params: ToolCallParams) -> Result<ToolCallResponse> {
         // Check if initialized
@@ -86,7 +408,7 @@ impl MCPClient {
             jsonrpc: "2.0".to_string(),
             id: Self::generate_id(),
             method: "toolCall".to_string(),
-            params: Some(serde_json::to_value(params).map_err(|e| Error::SerializationError(e))?),
+            params: Some(serde_json::to_value(params).map_err(Error::SerializationError)?),
         };
         
         let response = self.send_request(request).await?;
@@ -102,7 +424,7 @@ impl MCPClient {
             jsonrpc: "2.0".to_string(),
             id: Self::generate_id(),
             method: "executePrompt".to_string(),
-            params: Some(serde_json::to_value(params).map_err(|e| Error::SerializationError(e))?),
+            params: Some(serde_json::to_value(params).map_err(Error::SerializationError)?),
         };
         
         let response = self.send_request(request).await?;
@@ -117,7 +439,7 @@ impl MCPClient {
         let response = JsonRpcResponse {
             jsonrpc: "2.0".to_string(),
             id,
-            result: Some(serde_json::to_value(result).map_err(|e| Error::SerializationError(e))?),
+            result: Some(serde_json::to_value(result).map_err(Error::SerializationError)?),
             error: None,
         };
         
@@ -128,58 +450,240 @@ impl MCPClient {
     pub async fn capabilities(&self) -> Option<ServerCapabilities> {
         self.capabilities.lock().await.clone()
     }
+
+    /// The server's tools as chat completion tool definitions, ready to pass to the model.
+    ///
+    /// Returns an empty list before initialization or when the server exposes no tools.
+    pub async fn as_chat_tools(&self) -> Vec<crate::models::tool::Tool> {
+        self.capabilities
+            .lock()
+            .await
+            .as_ref()
+            .and_then(|caps| caps.tools.as_ref())
+            .map(|tools| tools.tools.iter().cloned().map(Into::into).collect())
+            .unwrap_or_default()
+    }
     
-    /// Send a JSON-RPC request to the server.
+    /// Register the handler used to answer server-initiated `sampling/...` requests.
+    ///
+    /// Inbound requests are dispatched while waiting for responses and by [`MCPClient::run`].
+    pub fn on_sampling<F>(&self, handler: F)
+    where
+        F: Fn(SamplingParams) -> Result<SamplingResponse> + Send + Sync + 'static,
+    {
+        let mut slot = self
+            .sampling_handler
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *slot = Some(Arc::new(handler));
+    }
+    
+    /// Process server-initiated messages until the transport fails or closes.
+    ///
+    /// This requires a persistent transport such as WebSocket or stdio; over HTTP there
+    /// is nothing to receive outside of request/response pairs.
+    pub async fn run(&self) -> Result<()> {
+        loop {
+            let message = {
+                let _reader = self.reader.lock().await;
+                self.transport.receive().await?
+            };
+            self.dispatch(message).await?;
+        }
+    }
+    
+    /// Send a JSON-RPC request to the server and wait for its response.
     async fn send_request(&self, request: JsonRpcRequest) -> Result<JsonRpcResponse> {
-        let response = self.client
-            .post(self.server_url.clone())
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| Error::HttpError(e))?;
-        
-        if !response.status().is_success() {
-            return Err(Error::ApiError {
-                code: response.status().as_u16(),
-                message: response.text().await.unwrap_or_default(),
-                metadata: None,
-            });
+        let message = serde_json::to_value(&request).map_err(Error::SerializationError)?;
+        let mut responses = self.exchange(message, std::slice::from_ref(&request)).await?;
+        Ok(responses.remove(0))
+    }
+
+    /// Send several JSON-RPC requests as one batch and wait for all of their responses.
+    ///
+    /// Responses are correlated by id and returned in the order of `requests`.
+    async fn send_batch(&self, requests: Vec<JsonRpcRequest>) -> Result<Vec<JsonRpcResponse>> {
+        if requests.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let message = serde_json::to_value(&requests).map_err(Error::SerializationError)?;
+        self.exchange(message, &requests).await
+    }
+
+    /// Send `message` and collect the responses to `requests`.
+    ///
+    /// Each attempt is bounded by the configured timeout and retried with exponential
+    /// backoff according to the retry configuration.
+    async fn exchange(&self, message: Value, requests: &[JsonRpcRequest]) -> Result<Vec<JsonRpcResponse>> {
+        let mut retry_count = 0;
+        let mut backoff_ms = self.retry_config.initial_backoff_ms;
+
+        loop {
+            let attempt = async {
+                self.transport.send(message.clone()).await?;
+
+                let mut responses = Vec::with_capacity(requests.len());
+                for request in requests {
+                    responses.push(self.receive_response(&request.id).await?);
+                }
+                Ok(responses)
+            };
+
+            let error = match tokio::time::timeout(self.timeout, attempt).await {
+                Ok(Ok(responses)) => return Ok(responses),
+                Ok(Err(e)) => e,
+                Err(_) => {
+                    let what = match requests {
+                        [request] => format!("'{}'", request.method),
+                        _ => format!("a batch of {} requests", requests.len()),
+                    };
+                    Error::TimeoutError(format!(
+                        "No response to {} within {} ms",
+                        what,
+                        self.timeout.as_millis()
+                    ))
+                }
+            };
+
+            if retry_count >= self.retry_config.max_retries || !self.is_retryable(&error) {
+                return Err(error);
+            }
+
+            retry_count += 1;
+            tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+            backoff_ms = std::cmp::min(backoff_ms * 2, self.retry_config.max_backoff_ms);
+        }
+    }
+
+    /// Whether a failed request attempt is worth retrying.
+    fn is_retryable(&self, error: &Error) -> bool {
+        match error {
+            Error::TimeoutError(_) | Error::TransportError(_) | Error::HttpError(_) => true,
+            Error::ApiError { code, .. } => self.retry_config.retry_on_status_codes.contains(code),
+            Error::RateLimitExceeded { .. } => self.retry_config.retry_on_status_codes.contains(&429),
+            _ => false,
         }
-        
-        let response_body = response.text().await.map_err(|e| Error::HttpError(e))?;
-        let response: JsonRpcResponse = serde_json::from_str(&response_body)
-            .map_err(|e| Error::SerializationError(e))?;
-        
-        Ok(response)
     }
     
     /// Send a JSON-RPC response to the server.
     async fn send_response(&self, response: JsonRpcResponse) -> Result<()> {
-        let _response = self.client
-            .post(self.server_url.clone())
-            .json(&response)
-            .send()
-            .await
-            .map_err(|e| Error::HttpError(e))?;
-        
+        let message = serde_json::to_value(&response).map_err(Error::SerializationError)?;
+        self.transport.send(message).await
+    }
+    
+    /// Read from the transport until the response with the given id arrives.
+    ///
+    /// Responses to other in-flight requests are stashed for their owners.
+    async fn receive_response(&self, id: &str) -> Result<JsonRpcResponse> {
+        loop {
+            if let Some(response) = self.pending.lock().await.remove(id) {
+                return Ok(response);
+            }
+
+            let _reader = self.reader.lock().await;
+
+            // Another task may have received our response while we waited for the reader.
+            if let Some(response) = self.pending.lock().await.remove(id) {
+                return Ok(response);
+            }
+
+            let message = self.transport.receive().await?;
+            self.dispatch(message).await?;
+        }
+    }
+    
+    /// Route an incoming message: responses are stashed for their waiters and
+    /// server-initiated requests are answered.
+    async fn dispatch(&self, message: Value) -> Result<()> {
+        let messages = match message {
+            Value::Array(messages) => messages,
+            message => vec![message],
+        };
+
+        for message in messages {
+            if message.get("method").is_none() {
+                let response: JsonRpcResponse =
+                    serde_json::from_value(message).map_err(Error::SerializationError)?;
+                self.pending.lock().await.insert(response.id.clone(), response);
+            } else if message.get("id").is_some() {
+                let request: JsonRpcRequest =
+                    serde_json::from_value(message).map_err(Error::SerializationError)?;
+                self.handle_server_request(request).await?;
+            }
+            // Notifications carry no id and need no reply.
+        }
+
         Ok(())
     }
     
+    /// Answer a server-initiated request.
+    async fn handle_server_request(&self, request: JsonRpcRequest) -> Result<()> {
+        if !request.method.starts_with("sampling/") {
+            return self
+                .send_error(request.id, METHOD_NOT_FOUND, format!("Method not found: {}", request.method))
+                .await;
+        }
+
+        let handler = self
+            .sampling_handler
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        let handler = match handler {
+            Some(handler) => handler,
+            None => {
+                return self
+                    .send_error(request.id, METHOD_NOT_FOUND, "No sampling handler registered".into())
+                    .await
+            }
+        };
+
+        let params = match serde_json::from_value::<SamplingParams>(request.params.unwrap_or(Value::Null)) {
+            Ok(params) => params,
+            Err(e) => {
+                return self
+                    .send_error(request.id, INVALID_PARAMS, format!("Invalid sampling params: {}", e))
+                    .await
+            }
+        };
+
+        match handler(params) {
+            Ok(result) => self.respond_to_sampling(request.id, result).await,
+            Err(e) => self.send_error(request.id, INTERNAL_ERROR, e.to_string()).await,
+        }
+    }
+    
+    /// Send a JSON-RPC error response to the server.
+    async fn send_error(&self, id: String, code: i32, message: String) -> Result<()> {
+        self.send_response(JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(JsonRpcError {
+                code,
+                message,
+                data: None,
+            }),
+        })
+        .await
+    }
+    
     /// Parse a JSON-RPC response into the expected type.
     fn parse_response<T: serde::de::DeserializeOwned>(&self, response: JsonRpcResponse) -> Result<T> {
         // Check for errors
         if let Some(error) = response.error {
-            return Err(Error::ApiError {
-                code: error.code as u16,
+            return Err(Error::JsonRpcError {
+                code: error.code,
                 message: error.message,
-                metadata: error.data,
+                data: error.data,
             });
         }
         
         // Parse the result
         match response.result {
             Some(result) => serde_json::from_value(result)
-                .map_err(|e| Error::SerializationError(e)),
+                .map_err(Error::SerializationError),
             None => Err(Error::ConfigError("Response contains no result".into())),
         }
     }
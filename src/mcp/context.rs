@@ -1,258 +1,213 @@
 // src/mcp/context.rs
+//! Model-backed context processing and preconfigured MCP clients.
+
 use crate::error::{Error, Result};
 use crate::types::chat::{ChatCompletionRequest, Message};
 use crate::mcp::client::{ContextProcessor, MCPClient};
+use crate::mcp::transport::Transport;
+use crate::utils::validation::{HeuristicTokenCounter, TokenCounter};
+use async_trait::async_trait;
 use std::sync::Arc;
 
+/// Instruction given to the model when condensing conversation history
+const SUMMARIZE_INSTRUCTION: &str = "Summarize the following conversation history concisely, capturing all important points and context needed for continuing the conversation.";
+
+/// Instruction given to the model when combining the summaries of consecutive chunks
+const COMBINE_INSTRUCTION: &str = "Combine the following partial summaries of consecutive parts of one conversation into a single concise summary, keeping all important points and context needed for continuing the conversation.";
+
+/// Instruction given to the model when extracting key points
+const EXTRACT_INSTRUCTION: &str = "Extract the key pieces of information from the following text. Return each key point as a separate line.";
+
 /// Advanced implementation of context processor using summarization
+///
+/// Histories too long for one summarization prompt are split into chunks that fit, each
+/// chunk is summarized, and the summaries are combined until one remains.
 pub struct SummarizingProcessor {
     /// OpenRouter client for calling summarization model
     client: Arc<crate::client::OpenRouterClient<crate::client::Ready>>,
     /// Model to use for summarization
     summarization_model: String,
-    /// Tokio runtime for blocking operations
-    runtime: tokio::runtime::Runtime,
+    /// Token budget for the history sent in one summarization prompt
+    chunk_budget: Option<usize>,
+    /// Counter used to size the chunks
+    token_counter: Arc<dyn TokenCounter>,
 }
 
 impl SummarizingProcessor {
     pub fn new(
         client: Arc<crate::client::OpenRouterClient<crate::client::Ready>>,
         summarization_model: impl Into<String>,
-    ) -> Result<Self> {
-        // Create a runtime for async operations within sync contexts
-        let runtime = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .map_err(|e| Error::ConfigError(format!("Failed to create Tokio runtime: {}", e)))?;
-            
-        Ok(Self {
+    ) -> Self {
+        Self {
             client,
             summarization_model: summarization_model.into(),
-            runtime,
+            chunk_budget: None,
+            token_counter: Arc::new(HeuristicTokenCounter),
+        }
+    }
+
+    /// Limits the history sent in one summarization prompt to `tokens`.
+    ///
+    /// Without a limit, three quarters of the summarization model's context length are
+    /// used when the client has it cached; otherwise the history is sent in one prompt.
+    pub fn with_chunk_budget(mut self, tokens: usize) -> Self {
+        self.chunk_budget = Some(tokens);
+        self
+    }
+
+    /// Sizes chunks with `counter` instead of the character-based estimate.
+    pub fn with_token_counter(mut self, counter: impl TokenCounter + 'static) -> Self {
+        self.token_counter = Arc::new(counter);
+        self
+    }
+
+    /// The token budget for one chunk of history, if chunking applies.
+    fn chunk_budget(&self) -> Option<usize> {
+        self.chunk_budget.or_else(|| {
+            let model = self.client.config.models_cache.find_model(&self.summarization_model)?;
+            model.context_length.map(|length| length as usize / 4 * 3)
         })
     }
-}
 
-impl ContextProcessor for SummarizingProcessor {
-    fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
-        // Clone the necessary data to avoid lifetime issues
-        let client = self.client.clone();
-        let model = self.summarization_model.clone();
-        
-        // Use the runtime to execute the async block
-        self.runtime.block_on(async move {
-            // Combine user/assistant exchanges into a single history
-            let mut history = String::new();
-            
-            for msg in &messages {
-                let role_prefix = match msg.role.as_str() {
-                    "user" => "User",
-                    "assistant" => "Assistant",
-                    "system" => "System",
-                    _ => &msg.role,
-                };
-                
-                history.push_str(&format!("{}: {}\n\n", role_prefix, msg.content));
-            }
-            
-            // Prepare to call the summarization model
-            let chat_api = client.chat()?;
-            
-            // Create a system message asking for summarization
-            let system_msg = Message {
-                role: "system".to_string(),
-                content: "Summarize the following conversation history concisely, capturing all important points and context needed for continuing the conversation.".to_string(),
-                name: None,
-                tool_calls: None,
-            };
-            
-            // Create a user message with the conversation history
-            let user_msg = Message {
-                role: "user".to_string(),
-                content: history,
-                name: None,
-                tool_calls: None,
-            };
-            
-            // Call the model to summarize
-            let request = ChatCompletionRequest {
-                model: model.clone(),
-                messages: vec![system_msg, user_msg],
-                stream: None,
-                response_format: None,
-                tools: None,
-                provider: None,
-                models: None,
-                transforms: None,
-            };
-            
-            // Get the summary
-            let response = chat_api.chat_completion(request)
-                .await
-                .map_err(|e| Error::ConfigError(format!("Failed to summarize context: {}", e)))?;
-                
-            if response.choices.is_empty() {
-                return Err(Error::ConfigError("No summary generated".to_string()));
-            }
-            
-            // Create a summarized context
-            let summary = Message {
-                role: "system".to_string(),
-                content: format!("Previous conversation summary: {}", 
-                    response.choices[0].message.content),
-                name: None,
-                tool_calls: None,
-            };
-            
-            // Return the summary as the first message
-            let mut result = Vec::new();
-            
-            // If there was a system message, preserve it first
-            if !messages.is_empty() && messages[0].role == "system" {
-                result.push(messages[0].clone());
-            }
-            
-            // Add the summary
-            result.push(summary);
-            
-            // Add the most recent messages (one exchange)
-            if messages.len() >= 2 {
-                result.push(messages[messages.len() - 2].clone());
-                result.push(messages[messages.len() - 1].clone());
+    /// Split `messages` into consecutive chunks of at most `budget` tokens.
+    ///
+    /// A message larger than the budget forms a chunk on its own. Chunks always hold at
+    /// least two messages when there are several, so combining summaries makes progress.
+    async fn chunk(&self, messages: Vec<Message>, budget: usize) -> Result<Vec<Vec<Message>>> {
+        let mut chunks: Vec<Vec<Message>> = Vec::new();
+        let mut current = Vec::new();
+        let mut current_tokens = 0;
+
+        for msg in messages {
+            let tokens = self.token_counter.count_message_tokens(&msg).await?;
+            if current.len() >= 2 && current_tokens + tokens > budget {
+                chunks.push(std::mem::take(&mut current));
+                current_tokens = 0;
             }
-            
-            Ok(result)
-        })
+            current_tokens += tokens;
+            current.push(msg);
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+
+        Ok(chunks)
     }
-    
-    fn summarize(&self, messages: Vec<Message>) -> Result<Message> {
-        // Clone data for 'static lifetime
-        let client = self.client.clone();
-        let model = self.summarization_model.clone();
-        
-        self.runtime.block_on(async move {
-            // First compress the messages
-            let mut history = String::new();
-            
-            for msg in &messages {
-                let role_prefix = match msg.role.as_str() {
-                    "user" => "User",
-                    "assistant" => "Assistant",
-                    "system" => "System",
-                    _ => &msg.role,
-                };
-                
-                history.push_str(&format!("{}: {}\n\n", role_prefix, msg.content));
+
+    /// Summarize `messages`, chunking them to fit the budget and combining the partial
+    /// summaries until a single one remains.
+    async fn summarize_text(&self, messages: Vec<Message>) -> Result<String> {
+        let Some(budget) = self.chunk_budget() else {
+            return self.ask(SUMMARIZE_INSTRUCTION, Self::transcript(&messages)).await;
+        };
+
+        let mut instruction = SUMMARIZE_INSTRUCTION;
+        let mut pending = messages;
+        loop {
+            let chunks = self.chunk(pending, budget).await?;
+            let mut summaries = Vec::with_capacity(chunks.len());
+            for chunk in &chunks {
+                summaries.push(self.ask(instruction, Self::transcript(chunk)).await?);
             }
-            
-            // Prepare to call the summarization model
-            let chat_api = client.chat()?;
-            
-            // Create a system message asking for summarization
-            let system_msg = Message {
-                role: "system".to_string(),
-                content: "Summarize the following conversation history concisely, capturing all important points and context needed for continuing the conversation.".to_string(),
-                name: None,
-                tool_calls: None,
-            };
-            
-            // Create a user message with the conversation history
-            let user_msg = Message {
-                role: "user".to_string(),
-                content: history,
-                name: None,
-                tool_calls: None,
-            };
-            
-            // Call the model to summarize
-            let request = ChatCompletionRequest {
-                model,
-                messages: vec![system_msg, user_msg],
-                stream: None,
-                response_format: None,
-                tools: None,
-                provider: None,
-                models: None,
-                transforms: None,
-            };
-            
-            // Get the summary
-            let response = chat_api.chat_completion(request)
-                .await
-                .map_err(|e| Error::ConfigError(format!("Failed to summarize context: {}", e)))?;
-                
-            if response.choices.is_empty() {
-                return Err(Error::ConfigError("No summary generated".to_string()));
+
+            if summaries.len() == 1 {
+                return Ok(summaries.remove(0));
             }
-            
-            // Create and return the summary message
-            Ok(Message {
-                role: "system".to_string(),
-                content: format!("Previous conversation summary: {}", 
-                    response.choices[0].message.content),
-                name: None,
-                tool_calls: None,
-            })
-        })
+            instruction = COMBINE_INSTRUCTION;
+            pending = summaries
+                .into_iter()
+                .map(Message::system)
+                .collect();
+        }
     }
-    
-    fn extract_key_info(&self, messages: Vec<Message>) -> Result<Vec<String>> {
-        // Clone data for 'static lifetime
-        let client = self.client.clone();
-        let model = self.summarization_model.clone();
-        
-        self.runtime.block_on(async move {
-            // Combine all messages
-            let combined = messages.iter()
-                .map(|msg| msg.content.clone())
-                .collect::<Vec<String>>()
-                .join("\n\n");
-                
-            // Create a prompt to extract key information
-            let system_msg = Message {
-                role: "system".to_string(),
-                content: "Extract the key pieces of information from the following text. Return each key point as a separate line.".to_string(),
-                name: None,
-                tool_calls: None,
-            };
-            
-            let user_msg = Message {
-                role: "user".to_string(),
-                content: combined,
-                name: None,
-                tool_calls: None,
-            };
-            
-            // Call the model
-            let chat_api = client.chat()?;
-            let request = ChatCompletionRequest {
-                model,
-                messages: vec![system_msg, user_msg],
-                stream: None,
-                response_format: None,
-                tools: None,
-                provider: None,
-                models: None,
-                transforms: None,
+
+    /// Render messages as a plain-text transcript for the summarization model.
+    fn transcript(messages: &[Message]) -> String {
+        let mut history = String::new();
+
+        for msg in messages {
+            let role_prefix = match msg.role.as_str() {
+                "user" => "User",
+                "assistant" => "Assistant",
+                "system" => "System",
+                _ => &msg.role,
             };
-            
-            let response = chat_api.chat_completion(request)
-                .await
-                .map_err(|e| Error::ConfigError(format!("Failed to extract key info: {}", e)))?;
-                
-            if response.choices.is_empty() {
-                return Err(Error::ConfigError("No key information extracted".to_string()));
-            }
-            
-            // Split the response into lines
-            let key_points = response.choices[0].message.content
-                .lines()
-                .map(|line| line.trim().to_string())
-                .filter(|line| !line.is_empty())
-                .collect();
-                
-            Ok(key_points)
-        })
+
+            history.push_str(&format!("{}: {}\n\n", role_prefix, msg.content));
+        }
+
+        history
+    }
+
+    /// Send `content` to the summarization model under `instruction` and return its reply.
+    async fn ask(&self, instruction: &str, content: String) -> Result<String> {
+        let chat_api = self.client.chat()?;
+
+        let request = ChatCompletionRequest::new(
+            self.summarization_model.clone(),
+            vec![Message::system(instruction), Message::user(content)],
+        );
+
+        let response = chat_api.chat_completion(request).await?;
+
+        response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message.content.into_text())
+            .ok_or_else(|| Error::ConfigError("No response from summarization model".to_string()))
+    }
+}
+
+#[async_trait]
+impl ContextProcessor for SummarizingProcessor {
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
+        let summary = self.summarize(messages.clone()).await?;
+
+        let mut result = Vec::new();
+
+        // If there was a system message, preserve it first
+        if !messages.is_empty() && messages[0].role == "system" {
+            result.push(messages[0].clone());
+        }
+
+        // Add the summary
+        result.push(summary);
+
+        // Add the most recent messages (one exchange)
+        if messages.len() >= 2 {
+            result.push(messages[messages.len() - 2].clone());
+            result.push(messages[messages.len() - 1].clone());
+        }
+
+        Ok(result)
+    }
+
+    async fn summarize(&self, messages: Vec<Message>) -> Result<Message> {
+        let summary = self
+            .summarize_text(messages)
+            .await
+            .map_err(|e| Error::ConfigError(format!("Failed to summarize context: {}", e)))?;
+
+        Ok(Message::system(format!("Previous conversation summary: {}", summary)))
+    }
+
+    async fn extract_key_info(&self, messages: Vec<Message>) -> Result<Vec<String>> {
+        let combined = messages.iter()
+            .map(|msg| msg.content.to_string())
+            .collect::<Vec<String>>()
+            .join("\n\n");
+
+        let reply = self
+            .ask(EXTRACT_INSTRUCTION, combined)
+            .await
+            .map_err(|e| Error::ConfigError(format!("Failed to extract key info: {}", e)))?;
+
+        Ok(reply
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
     }
 }
 
@@ -261,73 +216,85 @@ pub struct MCPClientFactory;
 
 impl MCPClientFactory {
     /// Create a client with truncation strategy
-    pub fn with_truncation(max_context_size: usize) -> MCPClient {
-        MCPClient::new(
+    pub fn with_truncation(transport: impl Transport + 'static, max_context_size: usize) -> MCPClient {
+        MCPClient::new(transport).with_context_strategy(
+            crate::mcp::strategy::TruncationStrategy,
             max_context_size,
-            Box::new(crate::mcp::strategy::TruncationStrategy)
         )
     }
-    
+
     /// Create a client with sliding window strategy
     pub fn with_sliding_window(
+        transport: impl Transport + 'static,
         max_context_size: usize,
         window_size: usize,
         always_include_first: bool
     ) -> MCPClient {
-        MCPClient::new(
+        MCPClient::new(transport).with_context_strategy(
+            crate::mcp::strategy::SlidingWindowStrategy::new(window_size, always_include_first),
             max_context_size,
-            Box::new(crate::mcp::strategy::SlidingWindowStrategy::new(
-                window_size, 
-                always_include_first
-            ))
         )
     }
-    
+
     /// Create a client with summary-based strategy
-    pub fn with_summary<F>(
+    pub fn with_summary<F, Fut>(
+        transport: impl Transport + 'static,
         max_context_size: usize,
         summarizer: F,
         recent_count: usize
-    ) -> MCPClient 
+    ) -> MCPClient
     where
-        F: Fn(Vec<Message>) -> Result<Message> + Send + Sync + 'static
+        F: Fn(Vec<Message>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Message>> + Send + 'static,
     {
-        MCPClient::new(
+        MCPClient::new(transport).with_context_strategy(
+            crate::mcp::strategy::SummaryStrategy::new(summarizer, recent_count),
             max_context_size,
-            Box::new(crate::mcp::strategy::SummaryStrategy::new(
-                summarizer,
-                recent_count
-            ))
         )
     }
-    
+
+    /// Create a client with a hybrid summarize-then-window strategy
+    pub fn with_hybrid<F, Fut>(
+        transport: impl Transport + 'static,
+        max_context_size: usize,
+        summarizer: F,
+        window_size: usize,
+        summarize_threshold: usize
+    ) -> MCPClient
+    where
+        F: Fn(Vec<Message>) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<Message>> + Send + 'static,
+    {
+        MCPClient::new(transport).with_context_strategy(
+            crate::mcp::strategy::HybridStrategy::new(summarizer, window_size, summarize_threshold),
+            max_context_size,
+        )
+    }
+
     /// Create a client with advanced summarization processor
     pub fn with_advanced_summarization(
+        transport: impl Transport + 'static,
         max_context_size: usize,
         client: Arc<crate::client::OpenRouterClient<crate::client::Ready>>,
         summarization_model: impl Into<String>,
         recent_count: usize
-    ) -> Result<MCPClient> {
+    ) -> MCPClient {
         // Create the processor
-        let processor = Arc::new(SummarizingProcessor::new(
-            client.clone(),
-            summarization_model,
-        )?);
-        
+        let processor = Arc::new(SummarizingProcessor::new(client, summarization_model));
+
         // Create a summary strategy that uses the processor
         let processor_clone = Arc::clone(&processor);
-        let summarizer = move |messages: Vec<Message>| -> Result<Message> {
-            processor_clone.summarize(messages)
+        let summarizer = move |messages: Vec<Message>| {
+            let processor = Arc::clone(&processor_clone);
+            async move { processor.summarize(messages).await }
         };
-        
+
         // Create the client with the summary strategy and processor
-        Ok(MCPClient::new(
-            max_context_size,
-            Box::new(crate::mcp::strategy::SummaryStrategy::new(
-                summarizer,
-                recent_count
-            ))
-        ).with_processor(processor))
+        MCPClient::new(transport)
+            .with_context_strategy(
+                crate::mcp::strategy::SummaryStrategy::new(summarizer, recent_count),
+                max_context_size,
+            )
+            .with_processor(processor)
     }
 }
-
@@ -3,8 +3,11 @@
 //! 
 //! The Model Context Protocol is an open protocol that standardizes how
 //! applications provide context to LLMs. For more information, see:
-//! https://modelcontextprotocol.io/
+//! <https://modelcontextprotocol.io/>
 
 pub mod client;
+pub mod context;
+pub mod strategy;
+pub mod transport;
 pub mod types;
 
@@ -1,49 +1,139 @@
 // src/mcp/strategy.rs
-use crate::error::Result;
-use crate::types::chat::Message;
-use crate::mcp::client::ContextStrategy;
+//! Built-in [`ContextStrategy`] implementations.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use tokio::sync::Mutex;
+
+use crate::error::{Error, Result};
+use crate::types::chat::{Message, MessageContent};
+use crate::mcp::client::{ContextStrategy, EstimateCounter};
+use crate::utils::validation::TokenCounter;
+
+/// Group `messages` into units that must be kept or dropped together.
+///
+/// An assistant message carrying `tool_calls` is grouped with the `tool` responses that
+/// follow it, since the API rejects a tool call without its response and vice versa.
+/// Every other message forms a unit on its own.
+fn group_tool_exchanges(messages: Vec<Message>) -> Vec<Vec<Message>> {
+    let mut units: Vec<Vec<Message>> = Vec::new();
+    let mut open_calls: HashSet<String> = HashSet::new();
+
+    for msg in messages {
+        let answers_open_call = msg.role == "tool"
+            && !open_calls.is_empty()
+            && msg.tool_call_id.as_ref().is_none_or(|id| open_calls.contains(id));
+
+        if answers_open_call {
+            if let Some(unit) = units.last_mut() {
+                unit.push(msg);
+                continue;
+            }
+        }
+
+        open_calls = msg
+            .tool_calls
+            .iter()
+            .flatten()
+            .map(|call| call.id.clone())
+            .collect();
+        units.push(vec![msg]);
+    }
+
+    units
+}
+
+/// Remove the most recent units that fit into `slots` messages from `units`.
+///
+/// Returns their messages in conversation order; a unit that does not fit whole stops
+/// the window, so fewer than `slots` messages may be returned.
+fn split_recent(units: &mut Vec<Vec<Message>>, mut slots: usize) -> Vec<Message> {
+    let mut split = units.len();
+    while split > 0 && units[split - 1].len() <= slots {
+        split -= 1;
+        slots -= units[split].len();
+    }
+    units.split_off(split).concat()
+}
+
+/// Tokens by which `messages` exceed `budget` according to `counter`.
+async fn excess(counter: &dyn TokenCounter, messages: &[Message], budget: usize) -> Result<usize> {
+    Ok(counter.count_tokens(messages).await?.saturating_sub(budget))
+}
 
 /// Simple context truncation strategy
 pub struct TruncationStrategy;
 
+#[async_trait]
 impl ContextStrategy for TruncationStrategy {
-    fn fit_to_context(&self, mut messages: Vec<Message>, max_tokens: usize) -> Result<Vec<Message>> {
-        // Keep system messages and recent messages, removing older ones in the middle
-        let mut system_messages = Vec::new();
-        let mut user_messages = Vec::new();
-        
-        // Separate system and user messages
-        for msg in messages.drain(..) {
-            if msg.role == "system" {
-                system_messages.push(msg);
-            } else {
-                user_messages.push(msg);
-            }
+    async fn fit_to_context(&self, messages: Vec<Message>, max_tokens: usize) -> Result<Vec<Message>> {
+        self.fit_to_context_with_counter(messages, max_tokens, &EstimateCounter(self)).await
+    }
+    
+    async fn fit_to_context_with_counter(
+        &self,
+        messages: Vec<Message>,
+        max_tokens: usize,
+        counter: &dyn TokenCounter,
+    ) -> Result<Vec<Message>> {
+        // Keep system messages and recent messages, removing older ones
+        let (system_messages, user_messages): (Vec<_>, Vec<_>) =
+            messages.into_iter().partition(|msg| msg.role == "system");
+        
+        let system_tokens = counter.count_tokens(&system_messages).await?;
+        if system_tokens > max_tokens {
+            return Err(Error::ConfigError(format!(
+                "System messages need about {} tokens, more than the context budget of {}",
+                system_tokens, max_tokens
+            )));
         }
+        let budget = max_tokens - system_tokens;
         
-        // If we have too many messages, remove older ones
-        while self.estimate_token_count_split(&system_messages, &user_messages) > max_tokens && !user_messages.is_empty() {
-            // Remove the oldest non-system message
-            // Skip the most recent user message to maintain conversation flow
-            if user_messages.len() > 2 {
-                user_messages.remove(1); // Remove the second message (keeping the latest)
-            } else {
-                // If we only have a few messages left, truncate content instead
-                if let Some(msg) = user_messages.get_mut(0) {
-                    msg.content = format!("... [truncated] {}", 
-                        msg.content.chars().skip(msg.content.len() / 2).collect::<String>());
+        // Tool calls and their responses are removed together
+        let mut user_units = group_tool_exchanges(user_messages);
+        
+        // Remove the oldest exchanges while over budget, always keeping the latest one
+        while user_units.len() > 1 && excess(counter, &user_units.concat(), budget).await? > 0 {
+            user_units.remove(0);
+        }
+        
+        // If the latest exchange alone is still too large, truncate its content, assuming
+        // about four bytes per token and re-counting after each cut
+        if let Some(unit) = user_units.last_mut() {
+            for idx in 0..unit.len() {
+                loop {
+                    let over = excess(counter, unit, budget).await?;
+                    // Only plain text is cut; multipart content is kept whole.
+                    let MessageContent::Text(content) = &mut unit[idx].content else {
+                        break;
+                    };
+                    if over == 0 || content.is_empty() {
+                        break;
+                    }
+                    truncate_front(content, over * 4);
                 }
-                break;
+            }
+            
+            if excess(counter, unit, budget).await? > 0 {
+                return Err(Error::ConfigError(format!(
+                    "The latest messages do not fit the context budget of {} tokens even when truncated",
+                    max_tokens
+                )));
             }
         }
         
         // Recombine messages in the correct order
         let mut result = system_messages;
-        result.extend(user_messages);
+        result.extend(user_units.into_iter().flatten());
         Ok(result)
     }
     
-    fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
         // Simple compression: combine consecutive messages from the same role
         let mut compressed = Vec::new();
         let mut current_role = String::new();
@@ -53,46 +143,49 @@ impl ContextStrategy for TruncationStrategy {
             if msg.role == current_role && !current_content.is_empty() {
                 // Combine with previous message of same role
                 current_content.push_str("\n\n");
-                current_content.push_str(&msg.content);
+                current_content.push_str(&msg.content.text());
             } else {
                 // Add the previous combined message if it exists
                 if !current_role.is_empty() && !current_content.is_empty() {
-                    compressed.push(Message {
-                        role: current_role,
-                        content: current_content,
-                        name: None,
-                        tool_calls: None,
-                    });
+                    compressed.push(Message::new(current_role, current_content));
                 }
                 
                 // Start a new combined message
                 current_role = msg.role;
-                current_content = msg.content;
+                current_content = msg.content.into_text();
             }
         }
         
         // Add the last combined message if it exists
         if !current_role.is_empty() && !current_content.is_empty() {
-            compressed.push(Message {
-                role: current_role,
-                content: current_content,
-                name: None,
-                tool_calls: None,
-            });
+            compressed.push(Message::new(current_role, current_content));
         }
         
         Ok(compressed)
     }
 }
 
-impl TruncationStrategy {
-    // Helper method to estimate token count for split messages
-    fn estimate_token_count_split(&self, system_msgs: &[Message], user_msgs: &[Message]) -> usize {
-        // Basic estimation - in a real implementation, this would be more sophisticated
-        let system_tokens = self.estimate_token_count(system_msgs);
-        let user_tokens = self.estimate_token_count(user_msgs);
-        system_tokens + user_tokens
-    }
+/// Marker prefixed to content that had its beginning cut off
+const TRUNCATION_MARKER: &str = "... [truncated] ";
+
+/// Drop at least `bytes` bytes from the start of `content`, keeping the most recent text.
+///
+/// The remainder is prefixed with [`TRUNCATION_MARKER`]; once nothing is left the content
+/// becomes empty.
+fn truncate_front(content: &mut String, bytes: usize) {
+    let text = content.strip_prefix(TRUNCATION_MARKER).unwrap_or(content);
+    let cut = text
+        .char_indices()
+        .map(|(idx, _)| idx)
+        .find(|&idx| idx >= bytes.max(1))
+        .unwrap_or(text.len());
+    let rest = &text[cut..];
+
+    *content = if rest.is_empty() {
+        String::new()
+    } else {
+        format!("{}{}", TRUNCATION_MARKER, rest)
+    };
 }
 
 /// Sliding window context strategy
@@ -112,77 +205,95 @@ impl SlidingWindowStrategy {
     }
 }
 
+#[async_trait]
 impl ContextStrategy for SlidingWindowStrategy {
-    fn fit_to_context(&self, messages: Vec<Message>, _max_tokens: usize) -> Result<Vec<Message>> {
+    async fn fit_to_context(&self, messages: Vec<Message>, _max_tokens: usize) -> Result<Vec<Message>> {
         if messages.len() <= self.window_size {
             return Ok(messages);
         }
         
-        let mut result = Vec::new();
-        
-        // Always include the first message if flag is set
-        if self.always_include_first && !messages.is_empty() {
-            result.push(messages[0].clone());
+        // An empty window keeps nothing, not even the first message
+        if self.window_size == 0 {
+            return Ok(Vec::new());
         }
         
-        // Add the most recent messages up to window_size
-        let start_idx = if self.always_include_first { 1 } else { 0 };
-        let window_start = messages.len().saturating_sub(self.window_size - start_idx);
+        let mut units = group_tool_exchanges(messages);
+        let mut result = Vec::with_capacity(self.window_size);
         
-        // Add messages from the sliding window
-        for idx in window_start..messages.len() {
-            result.push(messages[idx].clone());
+        // Always include the first message if flag is set; it takes its slots of the window.
+        // When it opens a tool exchange too long for the window, the whole exchange is dropped.
+        let mut slots = self.window_size;
+        if self.always_include_first {
+            let first = units.remove(0);
+            if first.len() <= slots {
+                slots -= first.len();
+                result.extend(first);
+            }
         }
         
+        // Add the most recent messages. A tool exchange that does not fit whole is dropped
+        // along with everything before it, so the window may come up a few messages short.
+        result.extend(split_recent(&mut units, slots));
+        
         Ok(result)
     }
     
-    fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
         // For sliding window, we simply ensure we're within the window size
-        self.fit_to_context(messages, usize::MAX)
+        self.fit_to_context(messages, usize::MAX).await
     }
 }
 
+/// Asynchronous function that summarizes a set of messages
+type Summarizer = dyn Fn(Vec<Message>) -> BoxFuture<'static, Result<Message>> + Send + Sync;
+
 /// Summary-based context strategy
 pub struct SummaryStrategy {
     /// Function to summarize a set of messages
-    summarizer: Box<dyn Fn(Vec<Message>) -> Result<Message> + Send + Sync>,
+    summarizer: Box<Summarizer>,
     /// Number of recent messages to always include without summarization
     recent_count: usize,
 }
 
 impl SummaryStrategy {
-    pub fn new<F>(summarizer: F, recent_count: usize) -> Self 
+    pub fn new<F, Fut>(summarizer: F, recent_count: usize) -> Self 
     where
-        F: Fn(Vec<Message>) -> Result<Message> + Send + Sync + 'static
+        F: Fn(Vec<Message>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Message>> + Send + 'static,
     {
         Self {
-            summarizer: Box::new(summarizer),
+            summarizer: Box::new(move |messages| Box::pin(summarizer(messages))),
             recent_count,
         }
     }
 }
 
+#[async_trait]
 impl ContextStrategy for SummaryStrategy {
-    fn fit_to_context(&self, messages: Vec<Message>, _max_tokens: usize) -> Result<Vec<Message>> {
+    async fn fit_to_context(&self, messages: Vec<Message>, _max_tokens: usize) -> Result<Vec<Message>> {
         if messages.len() <= self.recent_count + 1 {
             return Ok(messages);
         }
         
-        let mut result = Vec::new();
+        let mut units = group_tool_exchanges(messages);
         
         // Always include the first message (system prompt)
-        if !messages.is_empty() {
-            result.push(messages[0].clone());
-        }
+        let mut result = units.remove(0);
         
-        // Split into history to summarize and recent messages to keep
-        let history = messages[1..messages.len() - self.recent_count].to_vec();
-        let recent = messages[messages.len() - self.recent_count..].to_vec();
+        // Split into history to summarize and recent messages to keep. The split never
+        // falls inside a tool exchange, so slightly more than `recent_count` may be kept.
+        let mut split = units.len();
+        let mut kept = 0;
+        while split > 0 && kept < self.recent_count {
+            split -= 1;
+            kept += units[split].len();
+        }
+        let recent = units.split_off(split).concat();
+        let history = units.concat();
         
         // Generate a summary of the history
         if !history.is_empty() {
-            let summary = (self.summarizer)(history)?;
+            let summary = (self.summarizer)(history).await?;
             result.push(summary);
         }
         
@@ -192,9 +303,111 @@ impl ContextStrategy for SummaryStrategy {
         Ok(result)
     }
     
-    fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
         // For summary strategy, compression is effectively the same as fitting
-        self.fit_to_context(messages, usize::MAX)
+        self.fit_to_context(messages, usize::MAX).await
     }
 }
 
+/// Summary of the oldest part of a conversation, kept between calls
+struct RunningSummary {
+    /// The summary message
+    summary: Message,
+    /// Number of messages after the system prompt folded into the summary
+    covered: usize,
+    /// Fingerprint of the covered messages, to detect a different conversation
+    fingerprint: u64,
+}
+
+/// Hybrid strategy: keeps the system prompt, a running summary of older turns and a
+/// sliding window of recent turns.
+///
+/// Nothing is summarized until more than `summarize_threshold` messages have left the
+/// window. From then on, each call only summarizes the messages that aged out since the
+/// previous call, together with the previous summary, instead of the whole history.
+pub struct HybridStrategy {
+    /// Function to summarize a set of messages
+    summarizer: Box<Summarizer>,
+    /// Number of recent messages kept verbatim
+    window_size: usize,
+    /// Number of aged-out messages tolerated before summarizing
+    summarize_threshold: usize,
+    /// Summary carried over from the previous call
+    running: Mutex<Option<RunningSummary>>,
+}
+
+impl HybridStrategy {
+    pub fn new<F, Fut>(summarizer: F, window_size: usize, summarize_threshold: usize) -> Self
+    where
+        F: Fn(Vec<Message>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Message>> + Send + 'static,
+    {
+        Self {
+            summarizer: Box::new(move |messages| Box::pin(summarizer(messages))),
+            window_size,
+            summarize_threshold,
+            running: Mutex::new(None),
+        }
+    }
+
+    /// Fingerprint the role and content of `messages`.
+    fn fingerprint(messages: &[Message]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for msg in messages {
+            msg.role.hash(&mut hasher);
+            msg.content.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+#[async_trait]
+impl ContextStrategy for HybridStrategy {
+    async fn fit_to_context(&self, messages: Vec<Message>, _max_tokens: usize) -> Result<Vec<Message>> {
+        let has_system_prompt = messages.first().is_some_and(|msg| msg.role == "system");
+        let mut units = group_tool_exchanges(messages);
+        
+        // Always include the system prompt
+        let mut result = if has_system_prompt { units.remove(0) } else { Vec::new() };
+        
+        let recent = split_recent(&mut units, self.window_size);
+        let older = units.concat();
+        
+        if older.len() <= self.summarize_threshold {
+            result.extend(older);
+            result.extend(recent);
+            return Ok(result);
+        }
+        
+        let mut running = self.running.lock().await;
+        
+        // Reuse the previous summary if it covers a prefix of this conversation
+        let previous = running.take().filter(|prev| {
+            prev.covered <= older.len() && prev.fingerprint == Self::fingerprint(&older[..prev.covered])
+        });
+        
+        let summary = match previous {
+            Some(prev) if prev.covered == older.len() => prev.summary,
+            Some(prev) => {
+                let mut input = vec![prev.summary];
+                input.extend_from_slice(&older[prev.covered..]);
+                (self.summarizer)(input).await?
+            }
+            None => (self.summarizer)(older.clone()).await?,
+        };
+        
+        *running = Some(RunningSummary {
+            summary: summary.clone(),
+            covered: older.len(),
+            fingerprint: Self::fingerprint(&older),
+        });
+        
+        result.push(summary);
+        result.extend(recent);
+        Ok(result)
+    }
+    
+    async fn compress(&self, messages: Vec<Message>) -> Result<Vec<Message>> {
+        self.fit_to_context(messages, usize::MAX).await
+    }
+}
@@ -0,0 +1,305 @@
+//! Transports that carry JSON-RPC messages between the MCP client and a server.
+//!
+//! A transport moves raw JSON-RPC values; correlating requests with responses is the
+//! job of [`MCPClient`](crate::mcp::client::MCPClient). Persistent transports such as
+//! [`WebSocketTransport`] and [`StdioTransport`] also deliver server-initiated requests
+//! and notifications.
+
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::process::Stdio;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::TcpStream;
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use url::Url;
+
+use crate::error::{Error, Result};
+
+/// A bidirectional channel for JSON-RPC messages.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Sends a single JSON-RPC message (or a batch array) to the server.
+    async fn send(&self, message: Value) -> Result<()>;
+
+    /// Receives the next JSON-RPC message (or batch array) from the server.
+    async fn receive(&self) -> Result<Value>;
+
+    /// Closes the transport.
+    async fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    async fn send(&self, message: Value) -> Result<()> {
+        (**self).send(message).await
+    }
+
+    async fn receive(&self) -> Result<Value> {
+        (**self).receive().await
+    }
+
+    async fn close(&self) -> Result<()> {
+        (**self).close().await
+    }
+}
+
+/// Transport that issues one HTTP POST per outgoing message.
+///
+/// Response bodies are queued and handed out by [`Transport::receive`]. HTTP cannot carry
+/// server-initiated messages, so use a persistent transport for servers that push them.
+pub struct HttpTransport {
+    /// The HTTP client for making requests
+    client: reqwest::Client,
+    /// The URL of the MCP server endpoint
+    server_url: Url,
+    /// Response bodies waiting to be received
+    inbox: Mutex<VecDeque<Value>>,
+}
+
+impl HttpTransport {
+    /// Create a new HTTP transport for the given server URL.
+    pub fn new(server_url: impl AsRef<str>) -> Result<Self> {
+        Self::with_client(reqwest::Client::new(), server_url)
+    }
+
+    /// Create a new HTTP transport that sends through a preconfigured `reqwest::Client`,
+    /// e.g. one with its own timeouts, proxy or TLS settings.
+    pub fn with_client(client: reqwest::Client, server_url: impl AsRef<str>) -> Result<Self> {
+        let server_url = Url::parse(server_url.as_ref())
+            .map_err(|e| Error::ConfigError(format!("Invalid server URL: {}", e)))?;
+
+        Ok(Self {
+            client,
+            server_url,
+            inbox: Mutex::new(VecDeque::new()),
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn send(&self, message: Value) -> Result<()> {
+        let response = self
+            .client
+            .post(self.server_url.clone())
+            .json(&message)
+            .send()
+            .await
+            .map_err(Error::HttpError)?;
+
+        if !response.status().is_success() {
+            return Err(Error::ApiError {
+                code: response.status().as_u16(),
+                message: response.text().await.unwrap_or_default(),
+                metadata: None,
+            });
+        }
+
+        let response_body = response.text().await.map_err(Error::HttpError)?;
+
+        // Notifications and responses we send are acknowledged without a body.
+        if response_body.trim().is_empty() {
+            return Ok(());
+        }
+
+        let value: Value =
+            serde_json::from_str(&response_body).map_err(Error::SerializationError)?;
+        self.inbox.lock().await.push_back(value);
+
+        Ok(())
+    }
+
+    async fn receive(&self) -> Result<Value> {
+        self.inbox.lock().await.pop_front().ok_or_else(|| {
+            Error::TransportError(
+                "No pending message: HTTP transport only yields responses to sent messages".into(),
+            )
+        })
+    }
+}
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Transport that keeps a single WebSocket connection open for all traffic.
+pub struct WebSocketTransport {
+    /// Outgoing half of the connection
+    sink: Mutex<SplitSink<WsStream, WsMessage>>,
+    /// Incoming half of the connection
+    stream: Mutex<SplitStream<WsStream>>,
+}
+
+impl WebSocketTransport {
+    /// Connect to an MCP server over `ws://` or `wss://`.
+    pub async fn connect(server_url: impl AsRef<str>) -> Result<Self> {
+        let server_url = Url::parse(server_url.as_ref())
+            .map_err(|e| Error::ConfigError(format!("Invalid server URL: {}", e)))?;
+
+        let (socket, _response) = tokio_tungstenite::connect_async(server_url.as_str())
+            .await
+            .map_err(|e| Error::TransportError(format!("WebSocket connection failed: {}", e)))?;
+        let (sink, stream) = socket.split();
+
+        Ok(Self {
+            sink: Mutex::new(sink),
+            stream: Mutex::new(stream),
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    async fn send(&self, message: Value) -> Result<()> {
+        self.sink
+            .lock()
+            .await
+            .send(WsMessage::Text(message.to_string()))
+            .await
+            .map_err(|e| Error::TransportError(format!("WebSocket send failed: {}", e)))
+    }
+
+    async fn receive(&self) -> Result<Value> {
+        let mut stream = self.stream.lock().await;
+
+        loop {
+            let frame = stream
+                .next()
+                .await
+                .ok_or_else(|| Error::TransportError("WebSocket connection closed".into()))?
+                .map_err(|e| Error::TransportError(format!("WebSocket receive failed: {}", e)))?;
+
+            match frame {
+                WsMessage::Text(text) => {
+                    return serde_json::from_str(&text).map_err(Error::SerializationError)
+                }
+                WsMessage::Binary(bytes) => {
+                    return serde_json::from_slice(&bytes).map_err(Error::SerializationError)
+                }
+                WsMessage::Close(_) => {
+                    return Err(Error::TransportError("WebSocket connection closed".into()))
+                }
+                // Ping/pong frames are answered by tungstenite itself.
+                _ => continue,
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<()> {
+        self.sink
+            .lock()
+            .await
+            .close()
+            .await
+            .map_err(|e| Error::TransportError(format!("WebSocket close failed: {}", e)))
+    }
+}
+
+/// Transport that runs a local MCP server as a child process.
+///
+/// Messages are exchanged as newline-delimited JSON over the child's stdin and stdout;
+/// the child's stderr is inherited so server logs remain visible.
+pub struct StdioTransport {
+    /// The server process, killed when the transport is dropped
+    child: Mutex<Child>,
+    /// The server's standard input, dropped on close
+    stdin: Mutex<Option<ChildStdin>>,
+    /// Lines read from the server's standard output
+    stdout: Mutex<Lines<BufReader<ChildStdout>>>,
+}
+
+impl StdioTransport {
+    /// Spawn `command` with `args` and connect to its standard streams.
+    pub fn spawn<I, S>(command: impl AsRef<OsStr>, args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let program = command.as_ref().to_string_lossy().into_owned();
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                Error::TransportError(format!("Failed to spawn MCP server '{}': {}", program, e))
+            })?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| Error::TransportError("MCP server stdin is unavailable".into()))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| Error::TransportError("MCP server stdout is unavailable".into()))?;
+
+        Ok(Self {
+            child: Mutex::new(child),
+            stdin: Mutex::new(Some(stdin)),
+            stdout: Mutex::new(BufReader::new(stdout).lines()),
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for StdioTransport {
+    async fn send(&self, message: Value) -> Result<()> {
+        let mut line = message.to_string();
+        line.push('\n');
+
+        let mut guard = self.stdin.lock().await;
+        let stdin = guard
+            .as_mut()
+            .ok_or_else(|| Error::TransportError("MCP server stdin is closed".into()))?;
+        stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| Error::TransportError(format!("Failed to write to MCP server: {}", e)))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| Error::TransportError(format!("Failed to write to MCP server: {}", e)))
+    }
+
+    async fn receive(&self) -> Result<Value> {
+        let mut stdout = self.stdout.lock().await;
+
+        loop {
+            let line = stdout
+                .next_line()
+                .await
+                .map_err(|e| Error::TransportError(format!("Failed to read from MCP server: {}", e)))?
+                .ok_or_else(|| Error::TransportError("MCP server closed its output".into()))?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            return serde_json::from_str(&line).map_err(Error::SerializationError);
+        }
+    }
+
+    async fn close(&self) -> Result<()> {
+        // Closing stdin asks the server to exit; then wait for it to do so.
+        drop(self.stdin.lock().await.take());
+        self.child
+            .lock()
+            .await
+            .wait()
+            .await
+            .map_err(|e| Error::TransportError(format!("Failed to wait for MCP server: {}", e)))?;
+        Ok(())
+    }
+}
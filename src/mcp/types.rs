@@ -134,6 +134,23 @@ pub struct Tool {
     pub return_schema: serde_json::Value,
 }
 
+/// Converts an MCP tool into a chat completion function tool.
+///
+/// The function is named after the tool `id`, so a model's tool call can be routed back
+/// with `ToolCallParams { id: call.function_call.name, .. }`. The human-readable name is
+/// used as the description when the tool has none.
+impl From<Tool> for crate::models::tool::Tool {
+    fn from(tool: Tool) -> Self {
+        crate::models::tool::Tool::Function {
+            function: crate::models::tool::FunctionDescription {
+                name: tool.id,
+                description: tool.description.or(Some(tool.name)),
+                parameters: tool.parameter_schema,
+            },
+        }
+    }
+}
+
 /// Prompt capabilities offered by the server
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PromptCapabilities {
@@ -184,6 +201,13 @@ pub struct ResourceResponse {
     pub metadata: Option<serde_json::Value>,
 }
 
+/// Response to a `resources/list` request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListResourcesResponse {
+    /// Resources available on the server
+    pub resources: Vec<Resource>,
+}
+
 /// Tool call parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCallParams {
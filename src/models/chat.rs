@@ -4,15 +4,35 @@
    This module defines the chat models used to construct and parse chat completion requests.
 */
 
+use crate::error::Error;
+use crate::models::tool::ToolCall;
+use crate::types::chat::MessageContent;
 use serde::{Deserialize, Serialize};
 
-/// Defines the role of a chat message (user, assistant, or system).
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Defines the role of a chat message (user, assistant, system, tool, or developer).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ChatRole {
     User,
     Assistant,
     System,
+    /// A tool result, answering the assistant tool call named by `tool_call_id`.
+    Tool,
+    /// Instructions from the application developer, used by some models in place of `system`.
+    Developer,
+}
+
+impl ChatRole {
+    /// The role as it appears on the wire.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+            ChatRole::System => "system",
+            ChatRole::Tool => "tool",
+            ChatRole::Developer => "developer",
+        }
+    }
 }
 
 /// Represents a chat message with a role and content.
@@ -21,23 +41,55 @@ pub enum ChatRole {
 pub struct ChatMessage {
     pub role: ChatRole,
     pub content: String,
+    /// For tool messages, the ID of the tool call being answered.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// For assistant messages, the tool calls the model requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
 }
 
 /// Conversion from the model’s ChatMessage to the types::chat::Message used in API requests.
 impl From<ChatMessage> for crate::types::chat::Message {
     fn from(chat_msg: ChatMessage) -> Self {
-        // Convert the role enum to the expected lowercase string.
-        let role_str = match chat_msg.role {
-            ChatRole::User => "user".to_string(),
-            ChatRole::Assistant => "assistant".to_string(),
-            ChatRole::System => "system".to_string(),
-        };
         Self {
-            role: role_str,
-            content: chat_msg.content,
-            name: None,
-            tool_calls: None,
-            tool_call_id: None,
+            tool_calls: chat_msg.tool_calls,
+            tool_call_id: chat_msg.tool_call_id,
+            ..Self::new(chat_msg.role.as_str(), chat_msg.content)
         }
     }
 }
+
+/// Conversion from an API message back to the model’s ChatMessage.
+///
+/// Fails with `Error::ConfigError` for roles `ChatRole` doesn't know and for multipart
+/// content. The message's `name` and `reasoning` have no model-side counterpart and are
+/// dropped.
+impl TryFrom<crate::types::chat::Message> for ChatMessage {
+    type Error = Error;
+
+    fn try_from(msg: crate::types::chat::Message) -> Result<Self, Self::Error> {
+        let role = match msg.role.as_str() {
+            "user" => ChatRole::User,
+            "assistant" => ChatRole::Assistant,
+            "system" => ChatRole::System,
+            "tool" => ChatRole::Tool,
+            "developer" => ChatRole::Developer,
+            other => return Err(Error::ConfigError(format!("Unknown chat role: {}", other))),
+        };
+        let content = match msg.content {
+            MessageContent::Text(text) => text,
+            MessageContent::Parts(_) => {
+                return Err(Error::ConfigError(
+                    "Multipart message content cannot be converted to a ChatMessage".into(),
+                ))
+            }
+        };
+        Ok(Self {
+            role,
+            content,
+            tool_call_id: msg.tool_call_id,
+            tool_calls: msg.tool_calls,
+        })
+    }
+}
@@ -7,6 +7,138 @@
 use crate::error::Error;
 use serde::{Deserialize, Serialize};
 
+/// A provider name as used in `order` and `ignore` lists.
+///
+/// Known providers serialize to their canonical OpenRouter names; anything else is kept
+/// verbatim as [`Provider::Custom`]. Strings convert with [`From`], matching known names
+/// case-insensitively, so builders accept both enum values and raw strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Provider {
+    OpenAI,
+    Anthropic,
+    Google,
+    GoogleAIStudio,
+    Mistral,
+    Cohere,
+    DeepSeek,
+    XAI,
+    Groq,
+    Together,
+    Fireworks,
+    DeepInfra,
+    Azure,
+    AmazonBedrock,
+    Perplexity,
+    Cerebras,
+    SambaNova,
+    Lambda,
+    Novita,
+    Hyperbolic,
+    /// A provider not in the known list.
+    Custom(String),
+}
+
+impl Provider {
+    /// Every known provider, in declaration order.
+    pub const KNOWN: &'static [Provider] = &[
+        Provider::OpenAI,
+        Provider::Anthropic,
+        Provider::Google,
+        Provider::GoogleAIStudio,
+        Provider::Mistral,
+        Provider::Cohere,
+        Provider::DeepSeek,
+        Provider::XAI,
+        Provider::Groq,
+        Provider::Together,
+        Provider::Fireworks,
+        Provider::DeepInfra,
+        Provider::Azure,
+        Provider::AmazonBedrock,
+        Provider::Perplexity,
+        Provider::Cerebras,
+        Provider::SambaNova,
+        Provider::Lambda,
+        Provider::Novita,
+        Provider::Hyperbolic,
+    ];
+
+    /// The provider's name as OpenRouter expects it.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Provider::OpenAI => "OpenAI",
+            Provider::Anthropic => "Anthropic",
+            Provider::Google => "Google",
+            Provider::GoogleAIStudio => "Google AI Studio",
+            Provider::Mistral => "Mistral",
+            Provider::Cohere => "Cohere",
+            Provider::DeepSeek => "DeepSeek",
+            Provider::XAI => "xAI",
+            Provider::Groq => "Groq",
+            Provider::Together => "Together",
+            Provider::Fireworks => "Fireworks",
+            Provider::DeepInfra => "DeepInfra",
+            Provider::Azure => "Azure",
+            Provider::AmazonBedrock => "Amazon Bedrock",
+            Provider::Perplexity => "Perplexity",
+            Provider::Cerebras => "Cerebras",
+            Provider::SambaNova => "SambaNova",
+            Provider::Lambda => "Lambda",
+            Provider::Novita => "Novita",
+            Provider::Hyperbolic => "Hyperbolic",
+            Provider::Custom(name) => name,
+        }
+    }
+
+    /// Whether this is one of the known providers.
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Provider::Custom(_))
+    }
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for Provider {
+    fn from(name: &str) -> Self {
+        Provider::KNOWN
+            .iter()
+            .find(|known| known.as_str().eq_ignore_ascii_case(name.trim()))
+            .cloned()
+            .unwrap_or_else(|| Provider::Custom(name.to_string()))
+    }
+}
+
+impl From<String> for Provider {
+    fn from(name: String) -> Self {
+        Provider::from(name.as_str())
+    }
+}
+
+impl From<&String> for Provider {
+    fn from(name: &String) -> Self {
+        Provider::from(name.as_str())
+    }
+}
+
+impl From<Provider> for String {
+    fn from(provider: Provider) -> Self {
+        provider.as_str().to_string()
+    }
+}
+
+/// Converts provider names to their canonical form.
+pub(crate) fn provider_names<P: Into<Provider>>(providers: impl IntoIterator<Item = P>) -> Vec<String> {
+    providers
+        .into_iter()
+        .map(|provider| String::from(provider.into()))
+        .collect()
+}
+
 /// Defines the data collection policy when selecting providers.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -34,6 +166,8 @@ pub enum Quantization {
     Fp16,
     Bf16,
     Fp32,
+    /// Any level not listed above, for future compatibility
+    #[serde(other)]
     Unknown,
 }
 
@@ -64,6 +198,12 @@ pub struct ProviderPreferences {
     pub sort: Option<ProviderSort>,
 }
 
+impl Default for ProviderPreferences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ProviderPreferences {
     /// Creates a new empty ProviderPreferences.
     ///
@@ -105,13 +245,43 @@ impl ProviderPreferences {
             }
         }
 
+        // Validate ignore if present
+        if let Some(ref ignore) = self.ignore {
+            if ignore.is_empty() {
+                return Err(Error::ConfigError(
+                    "Provider ignore list cannot be empty".to_string(),
+                ));
+            }
+
+            // A provider cannot be both preferred and excluded
+            if let Some(provider) = self.order.iter().flatten().find(|p| ignore.contains(p)) {
+                return Err(Error::ConfigError(format!(
+                    "Provider {} is in both the order and ignore lists",
+                    provider
+                )));
+            }
+        }
+
         // Validation passed
         Ok(())
     }
 
+    /// Returns the names in `order` and `ignore` that are not known providers.
+    ///
+    /// Unknown names are still sent as-is, but they usually mean a typo.
+    pub fn unknown_providers(&self) -> Vec<&str> {
+        self.order
+            .iter()
+            .chain(self.ignore.iter())
+            .flatten()
+            .map(String::as_str)
+            .filter(|name| !Provider::from(*name).is_known())
+            .collect()
+    }
+
     /// Sets the order of providers.
-    pub fn with_order(mut self, order: Vec<String>) -> Self {
-        self.order = Some(order);
+    pub fn with_order<P: Into<Provider>>(mut self, order: impl IntoIterator<Item = P>) -> Self {
+        self.order = Some(provider_names(order));
         self
     }
 
@@ -134,8 +304,8 @@ impl ProviderPreferences {
     }
 
     /// Sets the providers to ignore.
-    pub fn with_ignore(mut self, ignore: Vec<String>) -> Self {
-        self.ignore = Some(ignore);
+    pub fn with_ignore<P: Into<Provider>>(mut self, ignore: impl IntoIterator<Item = P>) -> Self {
+        self.ignore = Some(provider_names(ignore));
         self
     }
 
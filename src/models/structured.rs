@@ -1,8 +1,23 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
+/// The `additionalProperties` keyword: a flag, or a schema undeclared properties must match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AdditionalProperties {
+    Bool(bool),
+    Schema(Box<Value>),
+}
+
+impl From<bool> for AdditionalProperties {
+    fn from(allowed: bool) -> Self {
+        AdditionalProperties::Bool(allowed)
+    }
+}
+
 /// A strongly‑typed representation of a JSON Schema definition.
-/// This captures common validation properties.
+/// This captures common validation properties; the nested schemas in `properties` may use
+/// the same keywords. Any other keyword is kept in `extra`, so the schema is sent as written.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JsonSchemaDefinition {
@@ -14,9 +29,36 @@ pub struct JsonSchemaDefinition {
     /// List of required property names.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub required: Option<Vec<String>>,
-    /// Indicates whether additional properties are allowed.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub additional_properties: Option<bool>,
+    /// Whether, or how, properties not listed in `properties` are allowed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub additional_properties: Option<AdditionalProperties>,
+    /// The only values the instance may take.
+    #[serde(rename = "enum", default, skip_serializing_if = "Option::is_none")]
+    pub enum_values: Option<Vec<Value>>,
+    /// The single value the instance must equal.
+    #[serde(rename = "const", default, skip_serializing_if = "Option::is_none")]
+    pub const_value: Option<Value>,
+    /// Inclusive lower bound for numbers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<f64>,
+    /// Inclusive upper bound for numbers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<f64>,
+    /// Minimum length of strings, in characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<u64>,
+    /// Maximum length of strings, in characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<u64>,
+    /// Regular expression strings must match somewhere, checked in strict mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Reusable subschemas, referenced as `{"$ref": "#/$defs/Name"}`.
+    #[serde(rename = "$defs", default, skip_serializing_if = "Option::is_none")]
+    pub defs: Option<Map<String, Value>>,
+    /// Keywords without a field of their own, such as `anyOf` or `description`.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 /// JSON Schema configuration for requesting structured outputs.
@@ -30,3 +72,27 @@ pub struct JsonSchemaConfig {
     /// The JSON Schema definition.
     pub schema: JsonSchemaDefinition,
 }
+
+impl JsonSchemaConfig {
+    /// Builds a strict schema config for `T` from its `schemars::JsonSchema` implementation,
+    /// keeping the schema in sync with the type the response is deserialized into.
+    ///
+    /// Fails if `T` does not describe a JSON object, such as an enum with data variants.
+    #[cfg(feature = "schemars")]
+    pub fn from_type<T: schemars::JsonSchema>(name: impl Into<String>) -> crate::error::Result<Self> {
+        let schema = schemars::SchemaGenerator::default().into_root_schema_for::<T>();
+        let schema = serde_json::from_value(schema.to_value()).map_err(|e| {
+            crate::error::Error::SchemaValidationError(format!(
+                "Schema for {} is not an object schema: {}",
+                std::any::type_name::<T>(),
+                e
+            ))
+        })?;
+
+        Ok(Self {
+            name: name.into(),
+            strict: true,
+            schema,
+        })
+    }
+}
@@ -0,0 +1,144 @@
+//! Test support: a mock OpenRouter server and a client wired to it.
+//!
+//! Enabled for this crate's own tests and, for downstream crates, through the `test-util`
+//! feature. [`MockClient`] starts a local HTTP server that answers with canned responses,
+//! so retry, streaming and error-mapping behavior can be tested without network access.
+
+use crate::client::{OpenRouterClient, Ready, RetryConfig};
+use crate::error::Result;
+use serde_json::{json, Value};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// API key used by clients created with [`MockClient::client`].
+pub const MOCK_API_KEY: &str = "sk-or-mock-key";
+
+/// A local mock of the OpenRouter API.
+pub struct MockClient {
+    server: MockServer,
+}
+
+impl MockClient {
+    /// Starts a mock server on a random local port.
+    pub async fn start() -> Self {
+        Self {
+            server: MockServer::start().await,
+        }
+    }
+
+    /// The underlying server, for mounting custom mocks.
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Base URL of the mock API, with a trailing slash.
+    pub fn base_url(&self) -> String {
+        format!("{}/", self.server.uri())
+    }
+
+    /// A ready client pointed at the mock server.
+    ///
+    /// Retries use millisecond backoffs so retry behavior can be tested quickly.
+    pub fn client(&self) -> Result<OpenRouterClient<Ready>> {
+        OpenRouterClient::new()
+            .with_base_url(self.base_url())?
+            .with_retry_config(RetryConfig {
+                initial_backoff_ms: 1,
+                max_backoff_ms: 10,
+                ..RetryConfig::default()
+            })
+            .with_api_key(MOCK_API_KEY)
+    }
+
+    /// Answers every chat completion request with `response`.
+    pub async fn mock_chat_completion(&self, response: Value) {
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers every chat completion request with an assistant message containing `content`.
+    pub async fn mock_chat_content(&self, content: &str) {
+        self.mock_chat_completion(chat_response(content)).await;
+    }
+
+    /// Answers the next `times` chat completion requests with an error status and body.
+    ///
+    /// Mocks mounted earlier take over once these are used up, which makes it easy to
+    /// simulate transient failures followed by a success.
+    pub async fn mock_chat_error(&self, status: u16, body: Value, times: u64) {
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(status).set_body_json(body))
+            .up_to_n_times(times)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers chat completion requests with a server-sent event stream of `chunks`,
+    /// terminated by `[DONE]`.
+    pub async fn mock_chat_stream(&self, chunks: &[Value]) {
+        let mut body = String::new();
+        for chunk in chunks {
+            body.push_str(&format!("data: {}\n\n", chunk));
+        }
+        body.push_str("data: [DONE]\n\n");
+
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers every model listing request with `response`.
+    pub async fn mock_models(&self, response: Value) {
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// JSON bodies of the requests received so far, in order.
+    pub async fn received_bodies(&self) -> Vec<Value> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(|request| serde_json::from_slice(&request.body).unwrap_or(Value::Null))
+            .collect()
+    }
+}
+
+/// A canned chat completion response with a single assistant message.
+pub fn chat_response(content: &str) -> Value {
+    json!({
+        "id": "gen-mock",
+        "choices": [{
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop",
+            "native_finish_reason": "stop"
+        }],
+        "created": 0,
+        "model": "mock/model",
+        "usage": {"prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2}
+    })
+}
+
+/// A canned streaming chunk carrying `content`.
+pub fn chat_chunk(content: &str) -> Value {
+    json!({
+        "id": "gen-mock",
+        "choices": [{
+            "index": 0,
+            "delta": {"role": "assistant", "content": content},
+            "finish_reason": null,
+            "native_finish_reason": null
+        }]
+    })
+}
@@ -1,4 +1,4 @@
-//// File: openrouter_api/src/tests/integration_tests.rs
+// File: openrouter_api/src/tests/integration_tests.rs
 /*
    src/tests/integration_tests.rs
 
@@ -6,6 +6,7 @@
 */
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod integration_tests {
     use crate::client::{OpenRouterClient, RetryConfig, Unconfigured};
     #[allow(unused_imports)]
@@ -42,22 +43,7 @@ mod integration_tests {
             .with_api_key(api_key);
 
         // Create a basic chat completion request.
-        let _request = ChatCompletionRequest {
-            model: "openai/gpt-4o".to_string(),
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: "What is a phantom type in Rust?".to_string(),
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-            }],
-            stream: None,
-            response_format: None,
-            tools: None,
-            provider: None,
-            models: None,
-            transforms: None,
-        };
+        let _request = ChatCompletionRequest::new("openai/gpt-4o", vec![Message::user("What is a phantom type in Rust?")]);
 
         // For this integration test we are simulating a response.
         let simulated_response_json = r#"
@@ -126,6 +112,21 @@ mod integration_tests {
                 user_id: None, // Add this field
                 timeout: std::time::Duration::from_secs(30),
                 retry_config: RetryConfig::default(), // Add this field
+                context_config: None, // Add this field
+                models_cache: Default::default(), // Add this field
+                interceptors: Default::default(), // Add this field
+                compress_requests: false, // Add this field
+                proxy: None, // Add this field
+                stream_deadline: None, // Add this field
+                circuit_breaker: None, // Add this field
+                default_max_tokens: None, // Add this field
+                idempotency: true, // Add this field
+                api_keys: None, // Add this field
+                default_system_prompt: None, // Add this field
+                request_timeout: None, // Add this field
+                max_response_bytes: None, // Add this field
+                context_overflow_handler: None, // Add this field
+                reserve_completion_tokens: None, // Add this field
             },
             http_client: None,
             _state: std::marker::PhantomData,
@@ -151,7 +152,10 @@ mod integration_tests {
                     "index": 0,
                     "finish_reason": "stop"
                 }
-            ]
+            ],
+            "model": "openai/gpt-3.5-turbo-instruct",
+            "created": 1234567890,
+            "usage": {"prompt_tokens": 5, "completion_tokens": 12, "total_tokens": 17}
         }
         "#;
 
@@ -161,9 +165,18 @@ mod integration_tests {
         )?;
 
         // Verify that the deserialization worked correctly.
-        assert!(response.choices.len() > 0);
+        assert!(!response.choices.is_empty());
         assert_eq!(response.choices[0].finish_reason.as_deref(), Some("stop"));
         assert!(response.choices[0].text.contains("Once upon a time"));
+        assert_eq!(response.model.as_deref(), Some("openai/gpt-3.5-turbo-instruct"));
+        assert_eq!(response.created, Some(1234567890));
+        assert_eq!(response.usage.map(|usage| usage.total_tokens), Some(17));
+
+        // Providers that omit the metadata still deserialize.
+        let minimal = serde_json::from_str::<crate::types::completion::CompletionResponse>(
+            r#"{"choices": [{"text": "Hi"}]}"#,
+        )?;
+        assert!(minimal.usage.is_none() && minimal.model.is_none());
 
         Ok(())
     }
@@ -206,6 +219,21 @@ mod integration_tests {
                 user_id: None, // Add this field
                 timeout: std::time::Duration::from_secs(30),
                 retry_config: RetryConfig::default(), // Add this field
+                context_config: None, // Add this field
+                models_cache: Default::default(), // Add this field
+                interceptors: Default::default(), // Add this field
+                compress_requests: false, // Add this field
+                proxy: None, // Add this field
+                stream_deadline: None, // Add this field
+                circuit_breaker: None, // Add this field
+                default_max_tokens: None, // Add this field
+                idempotency: true, // Add this field
+                api_keys: None, // Add this field
+                default_system_prompt: None, // Add this field
+                request_timeout: None, // Add this field
+                max_response_bytes: None, // Add this field
+                context_overflow_handler: None, // Add this field
+                reserve_completion_tokens: None, // Add this field
             },
             http_client: None,
             _state: std::marker::PhantomData,
@@ -294,4 +322,3927 @@ mod integration_tests {
         assert_eq!(response.results.len(), 2);
         Ok(())
     }
+
+    #[test]
+    fn test_sse_parser_multiline_and_error_events() {
+        use crate::api::chat::SseParser;
+
+        let mut parser = SseParser::default();
+        let stream = [
+            ": OPENROUTER PROCESSING",
+            "data: {\"id\": \"gen-1\",",
+            "data: \"choices\": []}",
+            "",
+            "event: error",
+            "data: {\"message\": \"provider overloaded\"}",
+            "",
+        ];
+
+        let events: Vec<_> = stream
+            .iter()
+            .filter_map(|line| parser.push_line(line))
+            .collect();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, None);
+        assert_eq!(events[0].data, "{\"id\": \"gen-1\",\n\"choices\": []}");
+        let value: Value = serde_json::from_str(&events[0].data).expect("joined data is valid JSON");
+        assert_eq!(value["id"], "gen-1");
+        assert_eq!(events[1].event.as_deref(), Some("error"));
+        assert!(parser.flush().is_none());
+    }
+
+    #[test]
+    fn test_stream_error_payload_is_surfaced() {
+        use crate::api::chat::stream_error_from_payload;
+
+        let payload = r#"{"error": {"code": 502, "message": "Upstream provider failed"}}"#;
+        match stream_error_from_payload(payload) {
+            Some(crate::error::Error::ApiError { code, message, .. }) => {
+                assert_eq!(code, 502);
+                assert_eq!(message, "Upstream provider failed");
+            }
+            other => panic!("Expected an ApiError, got {:?}", other),
+        }
+
+        assert!(stream_error_from_payload(r#"{"id": "gen-1", "choices": []}"#).is_none());
+        assert!(stream_error_from_payload("not json").is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_transport_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::transport::{StdioTransport, Transport};
+
+        // `cat` echoes each newline-delimited message straight back.
+        let transport = StdioTransport::spawn("cat", Vec::<&str>::new())?;
+        let message = json!({"jsonrpc": "2.0", "id": "req-1", "result": {"ok": true}});
+
+        transport.send(message.clone()).await?;
+        assert_eq!(transport.receive().await?, message);
+
+        transport.close().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mcp_client_answers_sampling_requests() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::MCPClient;
+        use crate::mcp::transport::Transport;
+        use crate::mcp::types::{ClientCapabilities, SamplingResponse, MCP_PROTOCOL_VERSION};
+        use async_trait::async_trait;
+        use std::collections::VecDeque;
+        use std::sync::{Arc, Mutex};
+
+        // Answers `initialize`, replays queued inbound messages and records everything
+        // else the client sends.
+        #[derive(Default)]
+        struct ScriptedTransport {
+            inbound: Mutex<VecDeque<Value>>,
+            sent: Mutex<Vec<Value>>,
+        }
+
+        #[async_trait]
+        impl Transport for ScriptedTransport {
+            async fn send(&self, message: Value) -> crate::error::Result<()> {
+                if message["method"] == "initialize" {
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": message["id"],
+                        "result": {"protocol_version": "2025-03-26"}
+                    });
+                    self.inbound.lock().unwrap().push_front(response);
+                } else {
+                    self.sent.lock().unwrap().push(message);
+                }
+                Ok(())
+            }
+
+            async fn receive(&self) -> crate::error::Result<Value> {
+                self.inbound.lock().unwrap().pop_front().ok_or_else(|| {
+                    crate::error::Error::TransportError("script exhausted".into())
+                })
+            }
+        }
+
+        let transport = Arc::new(ScriptedTransport::default());
+        let client = MCPClient::new(transport.clone());
+        client
+            .initialize(ClientCapabilities {
+                protocol_version: MCP_PROTOCOL_VERSION.to_string(),
+                supports_sampling: Some(true),
+            })
+            .await?;
+
+        transport.inbound.lock().unwrap().extend([
+            json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": {}}),
+            json!({
+                "jsonrpc": "2.0",
+                "id": "srv-1",
+                "method": "sampling/createMessage",
+                "params": {"task": "Say hello"}
+            }),
+            json!({"jsonrpc": "2.0", "id": "srv-2", "method": "roots/list"}),
+        ]);
+
+        client.on_sampling(|params| {
+            Ok(SamplingResponse {
+                result: format!("done: {}", params.task),
+            })
+        });
+
+        // The loop ends once the scripted transport runs dry.
+        assert!(client.run().await.is_err());
+
+        let sent = transport.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0]["id"], "srv-1");
+        assert_eq!(sent[0]["result"]["result"], "done: Say hello");
+        assert_eq!(sent[1]["id"], "srv-2");
+        assert_eq!(sent[1]["error"]["code"], -32601);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mcp_client_times_out_and_retries() {
+        use crate::mcp::client::MCPClient;
+        use crate::mcp::transport::Transport;
+        use crate::mcp::types::{ClientCapabilities, MCP_PROTOCOL_VERSION};
+        use async_trait::async_trait;
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        // A server that accepts every message and never answers.
+        #[derive(Default)]
+        struct SilentTransport {
+            sends: AtomicU32,
+        }
+
+        #[async_trait]
+        impl Transport for SilentTransport {
+            async fn send(&self, _message: Value) -> crate::error::Result<()> {
+                self.sends.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+
+            async fn receive(&self) -> crate::error::Result<Value> {
+                std::future::pending().await
+            }
+        }
+
+        let transport = Arc::new(SilentTransport::default());
+        let client = MCPClient::new(transport.clone())
+            .with_timeout(Duration::from_millis(20))
+            .with_retry_config(RetryConfig {
+                max_retries: 2,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 5,
+                retry_on_status_codes: vec![],
+            });
+
+        let result = client
+            .initialize(ClientCapabilities {
+                protocol_version: MCP_PROTOCOL_VERSION.to_string(),
+                supports_sampling: None,
+            })
+            .await;
+
+        assert!(matches!(result, Err(crate::error::Error::TimeoutError(_))));
+        assert_eq!(transport.sends.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_mcp_tool_converts_to_chat_tool() {
+        let mcp_tool = crate::mcp::types::Tool {
+            id: "get_weather".to_string(),
+            name: "Get weather".to_string(),
+            description: None,
+            parameter_schema: json!({
+                "type": "object",
+                "properties": {"city": {"type": "string"}},
+                "required": ["city"]
+            }),
+            return_schema: json!({"type": "string"}),
+        };
+
+        let Tool::Function { function } = Tool::from(mcp_tool.clone());
+        assert_eq!(function.name, "get_weather");
+        assert_eq!(function.description.as_deref(), Some("Get weather"));
+        assert_eq!(function.parameters, mcp_tool.parameter_schema);
+
+        let serialized = serde_json::to_value(Tool::from(mcp_tool)).unwrap();
+        assert_eq!(serialized["type"], "function");
+        assert_eq!(serialized["function"]["parameters"]["required"][0], "city");
+    }
+
+    #[tokio::test]
+    async fn test_mcp_batch_responses_are_correlated_by_id() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::MCPClient;
+        use crate::mcp::transport::Transport;
+        use crate::mcp::types::{ClientCapabilities, MCP_PROTOCOL_VERSION};
+        use async_trait::async_trait;
+        use std::sync::Mutex;
+
+        // Echoes each requested resource id back as its content, answering batches
+        // in reverse order.
+        #[derive(Default)]
+        struct ReversingTransport {
+            inbound: Mutex<Vec<Value>>,
+        }
+
+        fn answer(request: &Value) -> Value {
+            let result = match request["method"].as_str() {
+                Some("initialize") => json!({"protocol_version": MCP_PROTOCOL_VERSION}),
+                _ => json!({"content": request["params"]["id"], "mime_type": "text/plain"}),
+            };
+            json!({"jsonrpc": "2.0", "id": request["id"], "result": result})
+        }
+
+        #[async_trait]
+        impl Transport for ReversingTransport {
+            async fn send(&self, message: Value) -> crate::error::Result<()> {
+                let reply = match message {
+                    Value::Array(batch) => Value::Array(batch.iter().rev().map(answer).collect()),
+                    request => answer(&request),
+                };
+                self.inbound.lock().unwrap().push(reply);
+                Ok(())
+            }
+
+            async fn receive(&self) -> crate::error::Result<Value> {
+                self.inbound.lock().unwrap().pop().ok_or_else(|| {
+                    crate::error::Error::TransportError("nothing to receive".into())
+                })
+            }
+        }
+
+        let client = MCPClient::new(ReversingTransport::default());
+        client
+            .initialize(ClientCapabilities {
+                protocol_version: MCP_PROTOCOL_VERSION.to_string(),
+                supports_sampling: None,
+            })
+            .await?;
+
+        let ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let resources = client.get_resources(ids).await?;
+        let contents: Vec<_> = resources.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, ["a", "b", "c"]);
+
+        assert!(client.get_resources(Vec::new()).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_summary_strategy_runs_inside_async_runtime() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::ContextStrategy;
+        use crate::mcp::strategy::SummaryStrategy;
+
+        fn message(role: &str, content: &str) -> Message {
+            Message::new(role, content)
+        }
+
+        let strategy = SummaryStrategy::new(
+            |history: Vec<Message>| async move {
+                tokio::task::yield_now().await;
+                Ok(message("system", &format!("{} earlier messages", history.len())))
+            },
+            2,
+        );
+
+        let conversation = vec![
+            message("system", "You are helpful."),
+            message("user", "one"),
+            message("assistant", "two"),
+            message("user", "three"),
+            message("assistant", "four"),
+        ];
+
+        let fitted = strategy.fit_to_context(conversation, 1_000).await?;
+        let contents: Vec<_> = fitted.iter().map(|m| m.content.text()).collect();
+        assert_eq!(contents, ["You are helpful.", "2 earlier messages", "three", "four"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_strategy_token_estimate_default_and_override() {
+        use crate::mcp::client::ContextStrategy;
+        use crate::mcp::strategy::TruncationStrategy;
+        use crate::utils::validation::estimate_message_tokens;
+        use async_trait::async_trait;
+
+        let messages = vec![
+            Message::user("How many tokens is this sentence?");
+            3
+        ];
+
+        let expected: usize = messages.iter().map(|m| estimate_message_tokens(m) as usize).sum();
+        assert_eq!(TruncationStrategy.estimate_token_count(&messages), expected);
+
+        // A strategy backed by its own tokenizer can replace the heuristic.
+        struct OneTokenPerMessage;
+
+        #[async_trait]
+        impl ContextStrategy for OneTokenPerMessage {
+            async fn fit_to_context(&self, messages: Vec<Message>, _max_tokens: usize) -> crate::error::Result<Vec<Message>> {
+                Ok(messages)
+            }
+
+            async fn compress(&self, messages: Vec<Message>) -> crate::error::Result<Vec<Message>> {
+                Ok(messages)
+            }
+
+            fn estimate_token_count(&self, messages: &[Message]) -> usize {
+                messages.len()
+            }
+        }
+
+        assert_eq!(OneTokenPerMessage.estimate_token_count(&messages), 3);
+    }
+
+    #[tokio::test]
+    async fn test_sliding_window_small_windows() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::ContextStrategy;
+        use crate::mcp::strategy::SlidingWindowStrategy;
+
+        let conversation: Vec<Message> = (0..5)
+            .map(|i| Message::new(if i == 0 { "system" } else { "user" }, i.to_string()))
+            .collect();
+
+        let cases: &[(usize, bool, &[&str])] = &[
+            (0, true, &[]),
+            (0, false, &[]),
+            (1, true, &["0"]),
+            (1, false, &["4"]),
+            (2, true, &["0", "4"]),
+            (3, true, &["0", "3", "4"]),
+            (3, false, &["2", "3", "4"]),
+            (5, true, &["0", "1", "2", "3", "4"]),
+        ];
+
+        for &(window_size, always_include_first, expected) in cases {
+            let strategy = SlidingWindowStrategy::new(window_size, always_include_first);
+            let fitted = strategy.fit_to_context(conversation.clone(), usize::MAX).await?;
+            let contents: Vec<_> = fitted.iter().map(|m| m.content.text()).collect();
+            assert_eq!(contents, expected, "window {} / first {}", window_size, always_include_first);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_context_strategies_keep_tool_exchanges_together() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::ContextStrategy;
+        use crate::mcp::strategy::{SlidingWindowStrategy, SummaryStrategy, TruncationStrategy};
+        use std::collections::HashSet;
+
+        fn message(role: &str, content: &str) -> Message {
+            Message::new(role, content)
+        }
+
+        fn tool_call(ids: &[&str]) -> Message {
+            Message {
+                tool_calls: Some(
+                    ids.iter()
+                        .map(|id| ToolCall {
+                            id: id.to_string(),
+                            kind: "function".to_string(),
+                            function_call: FunctionCall {
+                                name: "lookup".to_string(),
+                                arguments: "{}".to_string(),
+                            },
+                        })
+                        .collect(),
+                ),
+                ..message("assistant", "")
+            }
+        }
+
+        fn tool_result(id: &str) -> Message {
+            Message {
+                tool_call_id: Some(id.to_string()),
+                ..message("tool", &"result ".repeat(40))
+            }
+        }
+
+        // Every tool call must have its response and every response its call.
+        fn assert_paired(messages: &[Message]) {
+            let calls: HashSet<_> = messages
+                .iter()
+                .flat_map(|m| m.tool_calls.iter().flatten().map(|c| c.id.clone()))
+                .collect();
+            let results: HashSet<_> =
+                messages.iter().filter_map(|m| m.tool_call_id.clone()).collect();
+            assert_eq!(calls, results, "unpaired tool messages in {:?}", messages);
+        }
+
+        let conversation = vec![
+            message("system", "You are helpful."),
+            message("user", &"question ".repeat(40)),
+            tool_call(&["a", "b"]),
+            tool_result("a"),
+            tool_result("b"),
+            message("assistant", "answer"),
+            message("user", &"follow up ".repeat(40)),
+            tool_call(&["c"]),
+            tool_result("c"),
+            message("assistant", "done"),
+        ];
+
+        for window_size in 1..conversation.len() {
+            for always_include_first in [true, false] {
+                let fitted = SlidingWindowStrategy::new(window_size, always_include_first)
+                    .fit_to_context(conversation.clone(), usize::MAX)
+                    .await?;
+                assert!(fitted.len() <= window_size);
+                assert_paired(&fitted);
+            }
+        }
+
+        // A leading tool exchange longer than the window is dropped rather than kept whole.
+        let tool_first: Vec<Message> = conversation[2..].to_vec();
+        let fitted = SlidingWindowStrategy::new(2, true)
+            .fit_to_context(tool_first.clone(), usize::MAX)
+            .await?;
+        let contents: Vec<_> = fitted.iter().map(|m| m.content.text()).collect();
+        assert_eq!(contents, [tool_first[tool_first.len() - 1].content.text()]);
+        let fitted = SlidingWindowStrategy::new(3, true)
+            .fit_to_context(tool_first.clone(), usize::MAX)
+            .await?;
+        let ids: Vec<_> = fitted.iter().filter_map(|m| m.tool_call_id.as_deref()).collect();
+        assert_eq!((fitted.len(), ids), (3, vec!["a", "b"]));
+
+        for recent_count in 0..conversation.len() {
+            let strategy = SummaryStrategy::new(
+                |_history: Vec<Message>| async { Ok(message("system", "summary")) },
+                recent_count,
+            );
+            assert_paired(&strategy.fit_to_context(conversation.clone(), usize::MAX).await?);
+        }
+
+        for max_tokens in [20, 60, 120, 200] {
+            let fitted = TruncationStrategy.fit_to_context(conversation.clone(), max_tokens).await?;
+            assert_paired(&fitted);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_truncation_strategy_respects_budget() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::ContextStrategy;
+        use crate::mcp::strategy::TruncationStrategy;
+
+        fn message(role: &str, content: String) -> Message {
+            Message::new(role, content)
+        }
+
+        let conversation: Vec<Message> = std::iter::once(message("system", "Be brief.".into()))
+            .chain((0..20).map(|i| message("user", format!("turn {} {}", i, "word ".repeat(50)))))
+            .collect();
+
+        for max_tokens in [25, 40, 100, 500] {
+            let fitted = TruncationStrategy.fit_to_context(conversation.clone(), max_tokens).await?;
+            assert!(TruncationStrategy.estimate_token_count(&fitted) <= max_tokens);
+            assert_eq!(fitted[0].content, "Be brief.");
+            // The latest turn always survives, truncated if need be.
+            assert!(fitted.last().unwrap().content.text().ends_with("word "));
+        }
+
+        // A single oversized message is clamped rather than returned over budget.
+        let fitted = TruncationStrategy
+            .fit_to_context(vec![message("user", "x".repeat(4_000))], 50)
+            .await?;
+        assert!(fitted[0].content.text().starts_with("... [truncated] "));
+        assert!(TruncationStrategy.estimate_token_count(&fitted) <= 50);
+
+        // System messages are never cut, so a budget they exceed is an error.
+        assert!(TruncationStrategy.fit_to_context(conversation, 2).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_conversation_fits_context_before_sending() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::MCPClient;
+        use crate::mcp::strategy::SlidingWindowStrategy;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "gen-1",
+                "choices": [{
+                    "message": {"role": "assistant", "content": "Paris."},
+                    "finish_reason": "stop",
+                    "native_finish_reason": "stop"
+                }],
+                "created": 1,
+                "model": "openai/gpt-4o",
+                "usage": {"prompt_tokens": 12, "completion_tokens": 2, "total_tokens": 14}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = OpenRouterClient::<Unconfigured>::new()
+            .with_base_url(format!("{}/", server.uri()))?
+            .with_api_key("sk-or-test-key")?;
+        let mcp = MCPClient::with_http(server.uri())?
+            .with_context_strategy(SlidingWindowStrategy::new(2, true), 1_000);
+
+        let message = |role: &str, content: &str| Message::new(role, content);
+        let request = ChatCompletionRequest::new(
+            "openai/gpt-4o",
+            vec![
+                message("system", "Answer briefly."),
+                message("user", "Hi"),
+                message("assistant", "Hello!"),
+                message("user", "Capital of France?"),
+            ],
+        );
+
+        let result = mcp.run_conversation(&client, request).await?;
+
+        let contents: Vec<_> = result.messages.iter().map(|m| m.content.text()).collect();
+        assert_eq!(contents, ["Answer briefly.", "Capital of France?", "Paris."]);
+        assert_eq!(result.usage.map(|u| u.total_tokens), Some(14));
+
+        let sent: Value = serde_json::from_slice(&server.received_requests().await.unwrap()[0].body)?;
+        assert_eq!(sent["messages"].as_array().map(Vec::len), Some(2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_applies_context_strategy() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::strategy::SlidingWindowStrategy;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "gen-1",
+                "choices": [{
+                    "message": {"role": "assistant", "content": "ok"},
+                    "finish_reason": "stop",
+                    "native_finish_reason": "stop"
+                }],
+                "created": 1,
+                "model": "openai/gpt-4o"
+            })))
+            .mount(&server)
+            .await;
+
+        let client = OpenRouterClient::<Unconfigured>::new()
+            .with_base_url(format!("{}/", server.uri()))?
+            .with_context_strategy(SlidingWindowStrategy::new(2, true), 1_000)
+            .with_api_key("sk-or-test-key")?;
+
+        let request = ChatCompletionRequest::new(
+            "openai/gpt-4o",
+            ["system", "user", "assistant", "user"]
+                .iter()
+                .enumerate()
+                .map(|(i, role)| Message::new(*role, format!("message {}", i)))
+                .collect(),
+        );
+
+        let chat = client.chat()?;
+        chat.chat_completion(request.clone()).await?;
+        chat.chat_completion_with_strategy(request, &SlidingWindowStrategy::new(3, false), 1_000)
+            .await?;
+
+        let sent: Vec<Vec<String>> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| {
+                let body: Value = serde_json::from_slice(&r.body).unwrap();
+                body["messages"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|m| m["content"].as_str().unwrap().to_string())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(sent[0], ["message 0", "message 3"]);
+        assert_eq!(sent[1], ["message 1", "message 2", "message 3"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_strategy_summarizes_incrementally() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::ContextStrategy;
+        use crate::mcp::strategy::HybridStrategy;
+        use std::sync::{Arc, Mutex};
+
+        fn message(role: &str, content: &str) -> Message {
+            Message::new(role, content)
+        }
+
+        // Record what each summarizer call receives; the summary lists the inputs.
+        let calls: Arc<Mutex<Vec<Vec<String>>>> = Arc::default();
+        let recorder = calls.clone();
+        let strategy = HybridStrategy::new(
+            move |history: Vec<Message>| {
+                let contents: Vec<String> = history.into_iter().map(|m| m.content.into_text()).collect();
+                recorder.lock().unwrap().push(contents.clone());
+                async move { Ok(message("system", &contents.join("+"))) }
+            },
+            2,
+            2,
+        );
+
+        let mut conversation = vec![message("system", "sys")];
+        let mut fit = Vec::new();
+        for turn in 1..=7 {
+            conversation.push(message("user", &format!("m{}", turn)));
+            fit.push(strategy.fit_to_context(conversation.clone(), usize::MAX).await?);
+        }
+        let contents = |messages: &[Message]| messages.iter().map(|m| m.content.clone()).collect::<Vec<_>>();
+
+        // Up to the threshold, older messages are kept verbatim.
+        assert_eq!(contents(&fit[3]), ["sys", "m1", "m2", "m3", "m4"]);
+        // Past it they are summarized, then folded into the running summary turn by turn.
+        assert_eq!(contents(&fit[4]), ["sys", "m1+m2+m3", "m4", "m5"]);
+        assert_eq!(contents(&fit[6]), ["sys", "m1+m2+m3+m4+m5", "m6", "m7"]);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [vec!["m1", "m2", "m3"], vec!["m1+m2+m3", "m4"], vec!["m1+m2+m3+m4", "m5"]]
+        );
+
+        // Refitting the same conversation reuses the summary without another call.
+        strategy.fit_to_context(conversation, usize::MAX).await?;
+        assert_eq!(calls.lock().unwrap().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_conversation_round_trip_and_fitting() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::ContextStrategy;
+        use crate::mcp::strategy::SummaryStrategy;
+        use crate::types::conversation::Conversation;
+
+        let message = |role: &str, content: &str| Message::new(role, content);
+
+        let mut conversation = Conversation::new(vec![message("system", "sys")])
+            .with_metadata("title", "Trip planning");
+        for turn in ["a", "b", "c", "d"] {
+            conversation.push(message("user", turn));
+        }
+
+        let strategy = SummaryStrategy::new(
+            |history: Vec<Message>| async move {
+                Ok(Message::system(format!("summary of {}", history.len())))
+            },
+            1,
+        );
+        let fitted = strategy.fit_conversation(conversation, usize::MAX).await?;
+
+        let restored = Conversation::from_json(&fitted.to_json()?)?;
+        let contents: Vec<_> = restored.messages.iter().map(|m| m.content.text()).collect();
+        assert_eq!(contents, ["sys", "summary of 3", "d"]);
+        assert_eq!(restored.metadata["title"], "Trip planning");
+
+        assert!(Conversation::from_json("{\"messages\": []}")?.metadata.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pkce_challenge_and_code_exchange() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::utils::auth::pkce::{
+            authorization_url, exchange_code_for_key_with, generate_pkce_challenge, PkceChallenge,
+        };
+        use wiremock::matchers::{body_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        // Test vector from RFC 7636, appendix B.
+        let rfc = PkceChallenge::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk");
+        assert_eq!(rfc.challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+
+        let pkce = generate_pkce_challenge();
+        assert_eq!(pkce.verifier.len(), 64);
+        assert_eq!(pkce.challenge, PkceChallenge::from_verifier(pkce.verifier.clone()).challenge);
+
+        let url = authorization_url("http://localhost:3000/callback", &pkce)?;
+        let query: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["callback_url"], "http://localhost:3000/callback");
+        assert_eq!(query["code_challenge"], pkce.challenge);
+        assert_eq!(query["code_challenge_method"], "S256");
+        assert!(authorization_url("not a url", &pkce).is_err());
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/auth/keys"))
+            .and(body_json(json!({
+                "code": "auth-code",
+                "code_verifier": pkce.verifier,
+                "code_challenge_method": "S256"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"key": "sk-or-user-key"})))
+            .mount(&server)
+            .await;
+
+        let base_url = Url::parse(&format!("{}/", server.uri()))?;
+        let client = reqwest::Client::new();
+        let key = exchange_code_for_key_with(&client, &base_url, "auth-code", &pkce.verifier).await?;
+        assert_eq!(key, "sk-or-user-key");
+
+        // A rejected code surfaces as an error.
+        assert!(exchange_code_for_key_with(&client, &base_url, "wrong", &pkce.verifier).await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_api_key_from_dotenv_and_file() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::utils::auth::{load_api_key_from_dotenv, load_api_key_from_file};
+
+        let dir = env::temp_dir().join(format!("openrouter_api_keys_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let dotenv = dir.join(".env");
+        std::fs::write(
+            &dotenv,
+            "# local settings\nOTHER=1\nexport OR_API_KEY='sk-or-fallback'\nOPENROUTER_API_KEY=\"sk-or-primary\" # dev key\n",
+        )?;
+        assert_eq!(load_api_key_from_dotenv(&dotenv)?, "sk-or-primary");
+
+        std::fs::write(&dotenv, "OR_API_KEY=sk-or-fallback # comment\nOPENROUTER_API_KEY=\n")?;
+        assert_eq!(load_api_key_from_dotenv(&dotenv)?, "sk-or-fallback");
+
+        std::fs::write(&dotenv, "OTHER=1\n")?;
+        assert!(load_api_key_from_dotenv(&dotenv).is_err());
+
+        let secret = dir.join("openrouter_key");
+        std::fs::write(&secret, "  sk-or-secret\n")?;
+        assert_eq!(load_api_key_from_file(&secret)?, "sk-or-secret");
+
+        std::fs::write(&secret, "\n")?;
+        assert!(load_api_key_from_file(&secret).is_err());
+        assert!(load_api_key_from_file(dir.join("missing")).is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mock_client_retry_stream_and_errors() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_chunk, MockClient};
+        use futures::StreamExt;
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+
+        // Two transient failures are retried before the canned success.
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("Hi there").await;
+        mock.mock_chat_error(503, json!({"error": {"message": "busy"}}), 2).await;
+        let response = mock.client()?.chat()?.chat_completion(request.clone()).await?;
+        assert_eq!(response.choices[0].message.content, "Hi there");
+        assert_eq!(mock.received_bodies().await.len(), 3);
+
+        // Non-retryable statuses surface as API errors.
+        let mock = MockClient::start().await;
+        mock.mock_chat_error(400, json!({"error": {"message": "bad request"}}), 10).await;
+        match mock.client()?.chat()?.chat_completion(request.clone()).await {
+            Err(crate::error::Error::ApiError { code, .. }) => assert_eq!(code, 400),
+            other => panic!("Expected an ApiError, got {:?}", other),
+        }
+
+        // Streams yield each chunk until [DONE].
+        let mock = MockClient::start().await;
+        mock.mock_chat_stream(&[chat_chunk("Hel"), chat_chunk("lo")]).await;
+        let client = mock.client()?;
+        let chat = client.chat()?;
+        let mut stream = chat.chat_completion_stream(request);
+        let mut text = String::new();
+        while let Some(chunk) = stream.next().await {
+            text.push_str(&chunk?.choices[0].delta.content);
+        }
+        assert_eq!(text, "Hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_chat_response_accessors() {
+        let response = deserialize_chat_response(
+            r#"{
+                "id": "gen-1",
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "content": "",
+                        "tool_calls": [{
+                            "id": "call-1",
+                            "type": "function",
+                            "function": {"name": "get_weather", "arguments": "{}"}
+                        }]
+                    },
+                    "finish_reason": "tool_calls",
+                    "native_finish_reason": "tool_calls"
+                }],
+                "created": 1,
+                "model": "openai/gpt-4o"
+            }"#,
+        );
+        assert_eq!(response.first_choice().and_then(|c| c.finish_reason.as_deref()), Some("tool_calls"));
+        assert_eq!(response.content(), Some(""));
+        assert_eq!(response.tool_calls().map(|calls| calls[0].id.as_str()), Some("call-1"));
+
+        let empty = deserialize_chat_response(r#"{"id": "gen-2", "choices": [], "created": 1, "model": "m"}"#);
+        assert!(empty.first_choice().is_none());
+        assert!(empty.content().is_none());
+        assert!(empty.tool_calls().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_send_payload_posts_built_request() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::api::request::RequestBuilder;
+        use crate::models::structured::{JsonSchemaConfig, JsonSchemaDefinition};
+        use crate::test_util::{chat_chunk, MockClient};
+        use futures::StreamExt;
+
+        let messages = vec![Message::user("Hello")];
+        let schema = JsonSchemaConfig {
+            name: "greeting".to_string(),
+            strict: true,
+            schema: JsonSchemaDefinition {
+                schema_type: "object".to_string(),
+                properties: serde_json::Map::new(),
+                required: None,
+                additional_properties: Some(false.into()),
+                enum_values: None,
+                const_value: None,
+                minimum: None,
+                maximum: None,
+                min_length: None,
+                max_length: None,
+                pattern: None,
+                defs: None,
+                extra: serde_json::Map::new(),
+            },
+        };
+        let payload = RequestBuilder::new("mock/model", messages, json!({"temperature": 0.5}))
+            .with_structured_output(schema, false, false)
+            .build();
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("Hi there").await;
+        let response = mock.client()?.chat()?.send_payload(payload.clone()).await?;
+        assert_eq!(response.content(), Some("Hi there"));
+
+        let body = &mock.received_bodies().await[0];
+        assert_eq!(body["model"], "mock/model");
+        assert_eq!(body["temperature"], 0.5);
+        assert_eq!(body["response_format"]["type"], "json_schema");
+        assert_eq!(body["response_format"]["json_schema"]["name"], "greeting");
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_stream(&[chat_chunk("Hel"), chat_chunk("lo")]).await;
+        let client = mock.client()?;
+        let chat = client.chat()?;
+        let mut stream = chat.send_payload_stream(payload);
+        let mut text = String::new();
+        while let Some(chunk) = stream.next().await {
+            text.push_str(&chunk?.choices[0].delta.content);
+        }
+        assert_eq!(text, "Hello");
+        assert_eq!(mock.received_bodies().await[0]["stream"], true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_part_cache_control_serialization() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::chat::{CacheControl, ContentPart};
+
+        let part = ContentPart::text("A long system prompt").cacheable();
+        assert_eq!(
+            serde_json::to_value(&part)?,
+            json!({"type": "text", "text": "A long system prompt", "cache_control": {"type": "ephemeral"}})
+        );
+        assert_eq!(part.cache_control(), Some(&CacheControl::ephemeral()));
+
+        // Parts without a directive omit the field and round-trip unchanged.
+        let image = ContentPart::image_url("https://example.com/cat.png");
+        let value = serde_json::to_value(&image)?;
+        assert_eq!(value, json!({"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}));
+        assert_eq!(serde_json::from_value::<ContentPart>(value)?, image);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reasoning_request_and_response() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::chat::{ReasoningConfig, ReasoningEffort};
+
+        let request = ChatCompletionRequest {
+            reasoning: Some(ReasoningConfig::with_effort(ReasoningEffort::High).excluded()),
+            ..ChatCompletionRequest::new("deepseek/deepseek-r1", vec![])
+        };
+        let value = serde_json::to_value(&request)?;
+        assert_eq!(value["reasoning"], json!({"effort": "high", "exclude": true}));
+        assert_eq!(
+            serde_json::to_value(ReasoningConfig::with_max_tokens(2000))?,
+            json!({"max_tokens": 2000})
+        );
+
+        let response = deserialize_chat_response(
+            r#"{
+                "id": "gen-1",
+                "choices": [{
+                    "message": {"role": "assistant", "content": "42", "reasoning": "Six times seven."},
+                    "finish_reason": "stop",
+                    "native_finish_reason": "stop"
+                }],
+                "created": 1,
+                "model": "deepseek/deepseek-r1"
+            }"#,
+        );
+        assert_eq!(response.content(), Some("42"));
+        assert_eq!(response.reasoning(), Some("Six times seven."));
+        Ok(())
+    }
+
+    #[test]
+    fn test_chat_request_plugins_serialization() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::plugin::{PdfParserOptions, Plugin};
+
+        let request = ChatCompletionRequest::new("openai/gpt-4o", vec![]).with_web_search();
+        assert_eq!(serde_json::to_value(&request)?["plugins"], json!([{"id": "web"}]));
+
+        let request = ChatCompletionRequest::new("openai/gpt-4o", vec![])
+            .with_plugin(Plugin::Web {
+                max_results: Some(3),
+                search_prompt: Some("Recent results:".to_string()),
+            })
+            .with_plugin(Plugin::FileParser {
+                pdf: Some(PdfParserOptions { engine: "pdf-text".to_string() }),
+            });
+        assert_eq!(
+            serde_json::to_value(&request)?["plugins"],
+            json!([
+                {"id": "web", "max_results": 3, "search_prompt": "Recent results:"},
+                {"id": "file-parser", "pdf": {"engine": "pdf-text"}}
+            ])
+        );
+
+        // Requests without plugins leave the field out entirely.
+        let plain = serde_json::to_value(ChatCompletionRequest::new("openai/gpt-4o", vec![]))?;
+        assert!(plain.get("plugins").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_id_suffixes_and_parsing() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::models::ModelId;
+
+        let id: ModelId = "meta-llama/llama-3-8b-instruct:free".parse()?;
+        assert_eq!(id.provider(), Some("meta-llama"));
+        assert_eq!(id.model(), "llama-3-8b-instruct");
+        assert_eq!(id.suffix(), Some("free"));
+
+        // Suffix helpers replace rather than stack suffixes.
+        assert_eq!(id.clone().nitro().to_string(), "meta-llama/llama-3-8b-instruct:nitro");
+        assert_eq!(id.base().floor().to_string(), "meta-llama/llama-3-8b-instruct:floor");
+
+        let request = ChatCompletionRequest::new(ModelId::parse("openai/gpt-4o")?.online(), vec![]);
+        assert_eq!(request.model, "openai/gpt-4o:online");
+
+        assert!(ModelId::parse("").is_err());
+        assert!(ModelId::parse("openai/").is_err());
+        assert!(ModelId::parse("openai/gpt-4o:").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_pricing_cost_estimates() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::models::ModelInfo;
+
+        let model: ModelInfo = serde_json::from_value(json!({
+            "id": "openai/gpt-4o",
+            "provider": "openai",
+            "pricing": {"prompt": "0.000005", "completion": 0.000015}
+        }))?;
+        let pricing = model.pricing.as_ref().expect("pricing should be parsed");
+        assert_eq!(pricing.prompt, Some(0.000005));
+        assert_eq!(pricing.completion, Some(0.000015));
+        assert!((model.estimate_cost(1000, 200) - 0.008).abs() < 1e-12);
+
+        let client = OpenRouterClient::new()
+            .with_base_url("https://openrouter.ai/api/v1/")?
+            .with_api_key(crate::test_util::MOCK_API_KEY)?;
+        let request = ChatCompletionRequest::new(
+            "openai/gpt-4o",
+            vec![Message::user("x".repeat(400))],
+        );
+        let tokens = crate::utils::validation::estimate_request_tokens(&request);
+        let cost = client.chat()?.estimate_cost(&request, &model)?;
+        assert!((cost - f64::from(tokens) * 0.000005).abs() < 1e-12);
+        let bounded = client.chat()?.estimate_cost(&request.clone().with_max_tokens(200), &model)?;
+        assert!((bounded - (f64::from(tokens) * 0.000005 + 200.0 * 0.000015)).abs() < 1e-12);
+
+        let unpriced: ModelInfo = serde_json::from_value(json!({"id": "m", "provider": "p"}))?;
+        assert!(client.chat()?.estimate_cost(&request, &unpriced).is_err());
+        assert!(serde_json::from_value::<ModelInfo>(json!({
+            "id": "m", "provider": "p", "pricing": {"prompt": "cheap"}
+        }))
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_info_pricing_and_modalities() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::models::ModelInfo;
+
+        let model: ModelInfo = serde_json::from_value(json!({
+            "id": "openai/gpt-4o",
+            "provider": "openai",
+            "pricing": {"prompt": "0.000005", "completion": "0.000015", "image": "0.007225", "request": "0.001"},
+            "architecture": {
+                "modality": "text+image->text",
+                "input_modalities": ["text", "image"],
+                "output_modalities": ["text"]
+            }
+        }))?;
+        let pricing = model.pricing.as_ref().expect("pricing should be parsed");
+        assert_eq!(pricing.image, Some(0.007225));
+        assert_eq!(pricing.request, Some(0.001));
+        assert!((model.estimate_cost(0, 0) - 0.001).abs() < 1e-12);
+        assert!(model.accepts_input("image"));
+        assert!(!model.accepts_input("audio"));
+
+        // Older entries only carry the modality summary.
+        let text_only: ModelInfo = serde_json::from_value(json!({
+            "id": "m", "provider": "p", "architecture": {"modality": "text->text"}
+        }))?;
+        assert!(text_only.accepts_input("text"));
+        assert!(!text_only.accepts_input("image"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_models_list_is_cached_until_refresh() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{MockClient, MOCK_API_KEY};
+
+        let mock = MockClient::start().await;
+        mock.mock_models(json!({"models": [{"id": "openai/gpt-4o", "provider": "openai"}]})).await;
+
+        let client = mock.client()?;
+        let models = client.models()?;
+        assert_eq!(models.list_models_cached(None).await?.models[0].id, "openai/gpt-4o");
+        // A second API handle from the same client shares the cache.
+        client.models()?.list_models_cached(None).await?;
+        assert_eq!(mock.received_bodies().await.len(), 1);
+
+        models.refresh(None).await?;
+        assert_eq!(mock.received_bodies().await.len(), 2);
+
+        // With a zero TTL every lookup goes to the network.
+        let uncached = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_models_cache_ttl(std::time::Duration::ZERO)
+            .with_api_key(MOCK_API_KEY)?;
+        uncached.models()?.list_models_cached(None).await?;
+        uncached.models()?.list_models_cached(None).await?;
+        assert_eq!(mock.received_bodies().await.len(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_model_and_list_endpoints() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        mock.mock_models(json!({"models": [
+            {"id": "openai/gpt-4o", "provider": "openai", "context_length": 128000},
+            {"id": "anthropic/claude-3-opus", "provider": "anthropic"}
+        ]}))
+        .await;
+        Mock::given(method("GET"))
+            .and(path("/models/openai/gpt-4o/endpoints"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"data": {
+                "id": "openai/gpt-4o",
+                "endpoints": [{
+                    "name": "OpenAI | openai/gpt-4o",
+                    "provider_name": "OpenAI",
+                    "context_length": 128000,
+                    "pricing": {"prompt": "0.0000025", "completion": "0.00001"},
+                    "supported_parameters": ["tools", "temperature"]
+                }]
+            }})))
+            .mount(mock.server())
+            .await;
+
+        let models = mock.client()?.models()?;
+        assert_eq!(models.get_model("openai/gpt-4o:nitro").await?.context_length, Some(128000));
+        match models.get_model("openai/missing").await {
+            Err(crate::error::Error::ModelNotAvailable(id)) => assert_eq!(id, "openai/missing"),
+            other => panic!("Expected ModelNotAvailable, got {:?}", other),
+        }
+
+        let endpoints = models.list_model_endpoints("openai/gpt-4o").await?;
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].provider_name, "OpenAI");
+        assert_eq!(endpoints[0].pricing.as_ref().and_then(|p| p.prompt), Some(0.0000025));
+        assert_eq!(endpoints[0].supported_parameters, vec!["tools", "temperature"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_moderation_request_and_response() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use crate::types::moderation::ModerationRequest;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        Mock::given(method("POST"))
+            .and(path("/moderations"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "modr-1",
+                "model": "omni-moderation-latest",
+                "results": [{
+                    "flagged": true,
+                    "categories": {"harassment": true, "violence": true, "hate": false},
+                    "category_scores": {"harassment": 0.91, "violence": 0.72, "hate": 0.01}
+                }]
+            })))
+            .mount(mock.server())
+            .await;
+
+        let moderation = mock.client()?.moderation()?;
+        let response = moderation
+            .moderate(ModerationRequest::new("some text").with_model("omni-moderation-latest"))
+            .await?;
+        assert!(response.flagged());
+        assert_eq!(response.results[0].flagged_categories(), vec!["harassment", "violence"]);
+        assert_eq!(response.results[0].category_scores["hate"], 0.01);
+        assert!(moderation.is_flagged("more text").await?);
+
+        let bodies = mock.received_bodies().await;
+        assert_eq!(bodies[0], json!({"input": "some text", "model": "omni-moderation-latest"}));
+        assert_eq!(bodies[1], json!({"input": "more text"}));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_interceptors_wrap_every_request() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_response, MockClient, MOCK_API_KEY};
+        use crate::utils::http::{Interceptor, RequestParts, ResponseMeta};
+        use std::sync::{Arc, Mutex};
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        struct Correlate {
+            seen: Arc<Mutex<Vec<(String, u16)>>>,
+        }
+
+        impl Interceptor for Correlate {
+            fn before_request(&self, request: &mut RequestParts) {
+                request.headers.insert("X-Correlation-ID", "abc-123".parse().unwrap());
+            }
+
+            fn after_response(&self, response: &ResponseMeta) {
+                self.seen
+                    .lock()
+                    .unwrap()
+                    .push((response.url.path().to_string(), response.status.as_u16()));
+            }
+        }
+
+        // Only requests carrying the injected header are answered.
+        let mock = MockClient::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("X-Correlation-ID", "abc-123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(chat_response("Hi")))
+            .mount(mock.server())
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .and(header("X-Correlation-ID", "abc-123"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"models": []})))
+            .mount(mock.server())
+            .await;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_interceptor(Correlate { seen: Arc::clone(&seen) })
+            .with_api_key(MOCK_API_KEY)?;
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+        assert_eq!(client.chat()?.chat_completion(request).await?.content(), Some("Hi"));
+        client.models()?.list_models_cached(None).await?;
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![("/chat/completions".to_string(), 200), ("/models".to_string(), 200)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_n_parameter_serialization_and_validation() {
+        use crate::utils::validation::validate_chat_request;
+
+        let messages = vec![Message::user("Pick a number")];
+
+        let request = ChatCompletionRequest::new("openai/gpt-4o", messages.clone()).with_n(5);
+        assert_eq!(serde_json::to_value(&request).unwrap()["n"], 5);
+        assert!(validate_chat_request(&request).is_ok());
+
+        let request = ChatCompletionRequest::new("openai/gpt-4o", messages).with_n(0);
+        assert!(validate_chat_request(&request).is_err());
+    }
+
+    #[test]
+    fn test_user_field_in_request_bodies() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::completion::CompletionRequest;
+
+        let chat = ChatCompletionRequest::new("openai/gpt-4o", vec![]).with_user("tenant-42");
+        assert_eq!(serde_json::to_value(&chat)?["user"], "tenant-42");
+
+        let completion = CompletionRequest::new("openai/gpt-3.5-turbo-instruct", "Once upon a time")
+            .with_user("tenant-42");
+        assert_eq!(
+            serde_json::to_value(&completion)?,
+            json!({"model": "openai/gpt-3.5-turbo-instruct", "prompt": "Once upon a time", "user": "tenant-42"})
+        );
+
+        let anonymous = serde_json::to_value(ChatCompletionRequest::new("openai/gpt-4o", vec![]))?;
+        assert!(anonymous.get("user").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prediction_serialization() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::chat::Prediction;
+
+        let request = ChatCompletionRequest::new("openai/gpt-4o", vec![])
+            .with_prediction("fn main() {}");
+        assert_eq!(
+            serde_json::to_value(&request)?["prediction"],
+            json!({"type": "content", "content": "fn main() {}"})
+        );
+        assert_eq!(
+            serde_json::from_value::<Prediction>(json!({"type": "content", "content": "x"}))?,
+            Prediction::new("x")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_routing_provider_preferences_validation() {
+        use crate::types::provider::ProviderPreferences;
+
+        let valid = ProviderPreferences::new()
+            .with_data_collection("deny")
+            .with_sort("price")
+            .with_quantizations(vec!["fp8".to_string(), "bf16".to_string()])
+            .with_route_optimizations(vec!["latency".to_string()]);
+        assert!(valid.validate().is_ok());
+        assert!(ProviderPreferences::new().validate().is_ok());
+
+        let invalid = [
+            ProviderPreferences::new().with_data_collection("maybe"),
+            ProviderPreferences::new().with_sort("pricing"),
+            ProviderPreferences::new().with_quantizations(vec!["fp7".to_string()]),
+            ProviderPreferences::new().with_route_optimizations(vec!["pricing".to_string()]),
+        ];
+        for preferences in invalid {
+            match preferences.validate() {
+                Err(crate::error::Error::ConfigError(message)) => assert!(message.contains("expected one of")),
+                other => panic!("Expected a ConfigError, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_known_providers_in_preferences() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::models::provider_preferences::{Provider, ProviderPreferences};
+
+        assert_eq!(Provider::from("anthropic"), Provider::Anthropic);
+        assert_eq!(Provider::from("Amazon Bedrock"), Provider::AmazonBedrock);
+        assert_eq!(Provider::from("Anthopic"), Provider::Custom("Anthopic".to_string()));
+        assert!(!Provider::from("Anthopic").is_known());
+        assert_eq!(serde_json::to_value(Provider::XAI)?, json!("xAI"));
+        assert_eq!(serde_json::from_value::<Provider>(json!("groq"))?, Provider::Groq);
+
+        // Enum values and raw strings are both accepted, and known names are canonicalized.
+        let preferences = ProviderPreferences::new()
+            .with_order([Provider::OpenAI, Provider::Custom("MyHost".to_string())])
+            .with_ignore(vec!["azure".to_string()]);
+        assert_eq!(preferences.order, Some(vec!["OpenAI".to_string(), "MyHost".to_string()]));
+        assert_eq!(preferences.ignore, Some(vec!["Azure".to_string()]));
+        assert!(preferences.validate().is_ok());
+        assert_eq!(preferences.unknown_providers(), vec!["MyHost"]);
+
+        let routing = crate::types::provider::ProviderPreferences::new().with_order(["deepseek", "Together"]);
+        assert_eq!(routing.order, Some(vec!["DeepSeek".to_string(), "Together".to_string()]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fallback_models_validated_against_catalog() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use crate::types::models::ModelsResponse;
+        use crate::types::routing::ModelGroups;
+
+        let catalog: ModelsResponse = serde_json::from_value(json!({"models": [
+            {"id": "openai/gpt-4o", "provider": "openai"},
+            {"id": "anthropic/claude-3-opus-20240229", "provider": "anthropic"},
+            {"id": "google/gemini-1.5-pro", "provider": "google"}
+        ]}))?;
+
+        let mut profile = ModelGroups::code();
+        assert!(profile.validate_against(&catalog).is_ok());
+
+        profile.fallbacks = Some(vec!["openai/gpt-4o:nitro".to_string(), "openai/gtp-4o".to_string()]);
+        match profile.validate_against(&catalog) {
+            Err(crate::error::Error::ModelNotAvailable(ids)) => assert_eq!(ids, "openai/gtp-4o"),
+            other => panic!("Expected ModelNotAvailable, got {:?}", other),
+        }
+
+        let mut request = ChatCompletionRequest::new("openai/gpt-4o", vec![]);
+        request.models = Some(vec!["anthropic/claude-3-opus".to_string()]);
+        assert!(matches!(
+            request.validate_models_against(&catalog),
+            Err(crate::error::Error::ModelNotAvailable(ids)) if ids == "anthropic/claude-3-opus"
+        ));
+
+        let mock = MockClient::start().await;
+        mock.mock_models(json!({"models": [{"id": "openai/gpt-4o", "provider": "openai"}]})).await;
+        let models = mock.client()?.models()?;
+        assert!(models.validate_profile(&ModelGroups::general()).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auth_and_billing_errors_have_dedicated_variants() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::error::Error;
+        use crate::test_util::MockClient;
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+
+        for (status, expected) in [(401, "auth"), (403, "auth"), (402, "credits"), (404, "api")] {
+            let mock = MockClient::start().await;
+            mock.mock_chat_error(status, json!({"error": {"message": "nope"}}), 1).await;
+            let result = mock.client()?.chat()?.chat_completion(request.clone()).await;
+            match (expected, result) {
+                ("auth", Err(error @ Error::AuthenticationError { .. })) => {
+                    assert!(error.to_string().contains("nope"));
+                    let details = error.api_error_details().expect("auth errors keep their details");
+                    assert_eq!(details.message.as_deref(), Some("nope"));
+                }
+                ("credits", Err(error @ Error::InsufficientCredits { .. })) => {
+                    let details = error.api_error_details().expect("billing errors keep their details");
+                    assert_eq!(details.message.as_deref(), Some("nope"));
+                }
+                ("api", Err(Error::ApiError { code, .. })) => assert_eq!(code, status),
+                (_, other) => panic!("Unexpected result for status {}: {:?}", status, other),
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_compression_and_gzip_responses() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_response, MockClient, MOCK_API_KEY};
+        use flate2::read::GzDecoder;
+        use flate2::write::GzEncoder;
+        use std::io::{Read, Write};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        // The mock answers with a gzip-encoded body, which the client decodes.
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(chat_response("Compressed hello").to_string().as_bytes())?;
+        let mock = MockClient::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Encoding", "gzip")
+                    .insert_header("Content-Type", "application/json")
+                    .set_body_bytes(encoder.finish()?),
+            )
+            .mount(mock.server())
+            .await;
+
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_compression(true)
+            .with_api_key(MOCK_API_KEY)?;
+        let message = |content: String| Message::user(content);
+
+        let small = ChatCompletionRequest::new("mock/model", vec![message("Hello".to_string())]);
+        let large = ChatCompletionRequest::new("mock/model", vec![message("document ".repeat(4000))]);
+        for request in [small, large] {
+            let response = client.chat()?.chat_completion(request).await?;
+            assert_eq!(response.content(), Some("Compressed hello"));
+        }
+
+        // Only the large body was compressed.
+        let requests = mock.server().received_requests().await.unwrap_or_default();
+        let encoding = |request: &wiremock::Request| {
+            request
+                .headers
+                .iter()
+                .find(|(name, _)| name.as_str().eq_ignore_ascii_case("content-encoding"))
+                .map(|(_, values)| values.last().as_str().to_string())
+        };
+        assert_eq!(encoding(&requests[0]), None);
+        assert_eq!(encoding(&requests[1]).as_deref(), Some("gzip"));
+        let mut body = String::new();
+        GzDecoder::new(requests[1].body.as_slice()).read_to_string(&mut body)?;
+        let body: Value = serde_json::from_str(&body)?;
+        assert!(body["messages"][0]["content"].as_str().unwrap().starts_with("document "));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_configured_proxy() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{MockClient, MOCK_API_KEY};
+
+        // The mock server acts as the proxy for an unreachable upstream host.
+        let proxy = MockClient::start().await;
+        proxy.mock_chat_content("via proxy").await;
+
+        let client = OpenRouterClient::new()
+            .with_base_url("http://openrouter.invalid/")?
+            .with_proxy(proxy.base_url())?
+            .with_proxy_auth("alice", "secret")?
+            .with_api_key(MOCK_API_KEY)?;
+        assert!(!format!("{:?}", client.config.proxy).contains("secret"));
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+        let response = client.chat()?.chat_completion(request).await?;
+        assert_eq!(response.content(), Some("via proxy"));
+
+        let requests = proxy.server().received_requests().await.unwrap_or_default();
+        let authorization = requests[0]
+            .headers
+            .iter()
+            .find(|(name, _)| name.as_str().eq_ignore_ascii_case("proxy-authorization"))
+            .map(|(_, values)| values.last().as_str().to_string());
+        assert_eq!(authorization.as_deref(), Some("Basic YWxpY2U6c2VjcmV0"));
+
+        assert!(OpenRouterClient::new()
+            .with_base_url("https://openrouter.ai/api/v1/")?
+            .with_proxy_auth("alice", "secret")
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_provider_preferences_apply_to_chat_requests() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use crate::types::provider::ProviderPreferences;
+        use crate::types::routing::{PredefinedModelCoverageProfile, RouterConfig};
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("ok").await;
+
+        let mut client = mock.client()?;
+        client.router_config = Some(RouterConfig {
+            profile: PredefinedModelCoverageProfile::LowestCost,
+            provider_preferences: Some(ProviderPreferences::new().with_sort("price")),
+        });
+        let chat = client.chat()?;
+
+        let message = Message::user("Hello");
+        chat.chat_completion(ChatCompletionRequest::new("mock/model", vec![message.clone()]))
+            .await?;
+        chat.chat_completion(
+            ChatCompletionRequest::new("mock/model", vec![message])
+                .with_provider(ProviderPreferences::new().with_order(["Anthropic"])),
+        )
+        .await?;
+
+        let bodies = mock.received_bodies().await;
+        assert_eq!(bodies[0]["provider"], json!({"sort": "price"}));
+        assert_eq!(bodies[1]["provider"], json!({"order": ["Anthropic"], "sort": "price"}));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_deadline_ends_slow_streams() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_chunk, MockClient, MOCK_API_KEY};
+        use futures::StreamExt;
+        use std::time::Duration;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        let body = format!("data: {}\n\ndata: [DONE]\n\n", chat_chunk("late"));
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(body, "text/event-stream")
+                    .set_delay(Duration::from_millis(500)),
+            )
+            .mount(mock.server())
+            .await;
+
+        let message = Message::user("Hello");
+        let request = ChatCompletionRequest::new("mock/model", vec![message]);
+
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_stream_deadline(Duration::from_millis(50))
+            .with_api_key(MOCK_API_KEY)?;
+        let mut stream = client.chat()?.chat_completion_stream(request.clone());
+        assert!(matches!(stream.next().await, Some(Err(crate::error::Error::TimeoutError(_)))));
+
+        // Without a deadline the same stream completes.
+        let mut stream = mock.client()?.chat()?.chat_completion_stream(request);
+        assert!(stream.next().await.unwrap().is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_simple_completion_with_system_prompt() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("Bonjour").await;
+
+        let reply = mock
+            .client()?
+            .chat()?
+            .simple_completion_with_system("mock/model", "Answer in French.", "Hello")
+            .await?;
+        assert_eq!(reply, "Bonjour");
+
+        let body = &mock.received_bodies().await[0];
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][0]["content"], "Answer in French.");
+        assert_eq!(body["messages"][1]["role"], "user");
+        assert_eq!(body["messages"][1]["content"], "Hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_validation_enforces_constraints() {
+        use crate::models::structured::JsonSchemaDefinition;
+        use crate::utils::validation::validate_against_schema;
+
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "title": {"type": "string", "minLength": 1},
+                "year": {"type": "integer", "minimum": 1980, "maximum": 1989},
+                "genre": {"enum": ["sci-fi", "horror"]},
+                "tags": {"type": "array", "items": {"type": "string", "maxLength": 5}}
+            },
+            "required": ["title", "year"],
+            "additionalProperties": false
+        });
+
+        let valid = json!({"title": "Alien", "year": 1986, "genre": "sci-fi", "tags": ["space"]});
+        assert!(validate_against_schema(&schema, &valid).is_ok());
+
+        for (invalid, expected) in [
+            (json!({"title": "Alien", "year": 1979}), "$.year: Value 1979 is below the minimum"),
+            (json!({"title": "", "year": 1986}), "$.title: String is shorter"),
+            (json!({"title": "Alien", "year": 1986, "genre": "drama"}), "$.genre: Value \"drama\""),
+            (json!({"title": "Alien", "year": 1986.5}), "$.year: Expected integer"),
+            (json!({"title": "Alien", "year": 1986, "tags": ["spaceship"]}), "$.tags[0]: String is longer"),
+            (json!({"title": "Alien", "year": 1986, "rating": 5}), "Unexpected field 'rating'"),
+            (json!({"year": 1986}), "Required field 'title' is missing"),
+        ] {
+            match validate_against_schema(&schema, &invalid) {
+                Err(crate::error::Error::SchemaValidationError(msg)) => {
+                    assert!(msg.contains(expected), "{} does not contain {}", msg, expected)
+                }
+                other => panic!("Expected a SchemaValidationError, got {:?}", other),
+            }
+        }
+
+        // Top-level keywords round-trip through the typed definition.
+        let definition: JsonSchemaDefinition =
+            serde_json::from_value(json!({"type": "object", "properties": {}, "const": {}})).unwrap();
+        assert_eq!(definition.const_value, Some(json!({})));
+        assert_eq!(serde_json::to_value(&definition).unwrap()["const"], json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_structured_output_resolves_schema_refs() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::models::structured::JsonSchemaConfig;
+        use crate::test_util::MockClient;
+
+        let schema = JsonSchemaConfig {
+            name: "cast".to_string(),
+            strict: true,
+            schema: serde_json::from_value(json!({
+                "type": "object",
+                "properties": {
+                    "lead": {"$ref": "#/$defs/Actor"},
+                    "supporting": {"type": "array", "items": {"$ref": "#/$defs/Actor"}}
+                },
+                "required": ["lead"],
+                "$defs": {
+                    "Actor": {
+                        "type": "object",
+                        "properties": {"name": {"type": "string", "minLength": 1}},
+                        "required": ["name"]
+                    }
+                }
+            }))?,
+        };
+        let messages = vec![Message::user("Cast a movie")];
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content(r#"{"lead": {"name": "Sigourney"}, "supporting": [{"name": "Ian"}]}"#)
+            .await;
+        let cast: Value = mock
+            .client()?
+            .structured()?
+            .generate("mock/model", messages.clone(), schema.clone())
+            .await?;
+        assert_eq!(cast["lead"]["name"], "Sigourney");
+
+        let body = &mock.received_bodies().await[0];
+        assert_eq!(body["response_format"]["json_schema"]["name"], "cast");
+        assert_eq!(
+            body["response_format"]["json_schema"]["schema"]["$defs"]["Actor"]["required"],
+            json!(["name"])
+        );
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content(r#"{"lead": {"name": "Sigourney"}, "supporting": [{}]}"#)
+            .await;
+        let result: crate::error::Result<Value> = mock
+            .client()?
+            .structured()?
+            .generate("mock/model", messages, schema)
+            .await;
+        match result {
+            Err(crate::error::Error::SchemaValidationError(msg)) => {
+                assert!(msg.contains("$.supporting[0]: Required field 'name' is missing"), "{}", msg)
+            }
+            other => panic!("Expected a SchemaValidationError, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn test_schema_config_from_type() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::models::structured::JsonSchemaConfig;
+        use crate::utils::validation::validate_against_schema;
+
+        #[derive(serde::Deserialize, schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Director {
+            name: String,
+        }
+
+        #[derive(serde::Deserialize, schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct MovieRecommendation {
+            title: String,
+            year: u32,
+            director: Director,
+            genre: Option<String>,
+        }
+
+        let config = JsonSchemaConfig::from_type::<MovieRecommendation>("MovieRecommendation")?;
+        assert!(config.strict);
+        assert_eq!(config.schema.schema_type, "object");
+        assert!(config.schema.defs.as_ref().is_some_and(|defs| defs.contains_key("Director")));
+
+        let schema = serde_json::to_value(&config.schema)?;
+        let movie = json!({"title": "Alien", "year": 1979, "director": {"name": "Ridley Scott"}});
+        assert!(validate_against_schema(&schema, &movie).is_ok());
+        let missing_name = json!({"title": "Alien", "year": 1979, "director": {}});
+        assert!(validate_against_schema(&schema, &missing_name).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_structured_generate_stream() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::api::structured::StructuredEvent;
+        use crate::models::structured::JsonSchemaConfig;
+        use crate::test_util::{chat_chunk, MockClient};
+        use futures::StreamExt;
+
+        let schema = JsonSchemaConfig {
+            name: "answer".to_string(),
+            strict: true,
+            schema: serde_json::from_value(json!({
+                "type": "object",
+                "properties": {"answer": {"type": "integer"}},
+                "required": ["answer"]
+            }))?,
+        };
+        let messages = vec![Message::user("What is six times seven?")];
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_stream(&[chat_chunk("{\"answer\""), chat_chunk(": 42}")]).await;
+        let client = mock.client()?;
+        let structured = client.structured()?;
+        let events: Vec<StructuredEvent<Value>> = structured
+            .generate_stream("mock/model", messages, schema)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<crate::error::Result<_>>()?;
+
+        assert_eq!(
+            events,
+            vec![
+                StructuredEvent::Delta("{\"answer\"".to_string()),
+                StructuredEvent::Delta(": 42}".to_string()),
+                StructuredEvent::Complete(json!({"answer": 42})),
+            ]
+        );
+        let body = &mock.received_bodies().await[0];
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["response_format"]["json_schema"]["name"], "answer");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_response_keeps_raw_body() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_completion(json!({"unexpected": "shape"})).await;
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+        match mock.client()?.chat()?.chat_completion(request).await {
+            Err(crate::error::Error::DeserializationError { status, body, .. }) => {
+                assert_eq!(status, 200);
+                assert_eq!(serde_json::from_str::<Value>(&body)?, json!({"unexpected": "shape"}));
+            }
+            other => panic!("Expected a DeserializationError, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_fails_fast_after_repeated_failures() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{MockClient, MOCK_API_KEY};
+        use std::time::Duration;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("recovered").await;
+        mock.mock_chat_error(503, json!({"error": {"message": "provider down"}}), 2).await;
+
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_retry_config(RetryConfig {
+                max_retries: 0,
+                ..RetryConfig::default()
+            })
+            .with_circuit_breaker(2, Duration::from_millis(100))
+            .with_api_key(MOCK_API_KEY)?;
+        let chat = client.chat()?;
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                chat.chat_completion(request.clone()).await,
+                Err(crate::error::Error::ApiError { code: 503, .. })
+            ));
+        }
+
+        // The circuit is open: no request reaches the server.
+        assert!(matches!(
+            chat.chat_completion(request.clone()).await,
+            Err(crate::error::Error::ModelNotAvailable(_))
+        ));
+        assert_eq!(mock.received_bodies().await.len(), 2);
+
+        // Other models are unaffected, and the model recovers after the cooldown.
+        let mut other = request.clone();
+        other.model = "mock/other".to_string();
+        assert!(chat.chat_completion(other).await.is_ok());
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(chat.chat_completion(request).await?.content(), Some("recovered"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_base_url_rejects_unsupported_urls() {
+        for url in [
+            "htps://openrouter.ai/api/v1/",
+            "file:///etc/openrouter/",
+            "https://openrouter.ai/api/v1/?key=value",
+            "https://openrouter.ai/api/v1/#section",
+        ] {
+            assert!(
+                matches!(
+                    OpenRouterClient::new().with_base_url(url),
+                    Err(crate::error::Error::ConfigError(_))
+                ),
+                "{} should be rejected",
+                url
+            );
+        }
+        assert!(OpenRouterClient::new().with_base_url("http://localhost:8080/v1/").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_text_simple_completion() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        Mock::given(method("POST"))
+            .and(path("/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "cmpl-mock",
+                "choices": [{"text": " upon a time", "index": 0, "finish_reason": "stop"}]
+            })))
+            .up_to_n_times(1)
+            .mount(mock.server())
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"choices": []})))
+            .mount(mock.server())
+            .await;
+
+        let client = mock.client()?;
+        let completions = client.completions()?;
+        assert_eq!(completions.simple_completion("mock/model", "Once").await?, " upon a time");
+        assert_eq!(
+            mock.received_bodies().await[0],
+            json!({"model": "mock/model", "prompt": "Once"})
+        );
+        assert!(matches!(
+            completions.simple_completion("mock/model", "Once").await,
+            Err(crate::error::Error::ApiError { code: 500, .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_max_tokens_and_context_warning() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{MockClient, MOCK_API_KEY};
+        use crate::types::models::ModelInfo;
+        use crate::utils::validation::{context_overflow, ContextOverflow};
+        use std::sync::{Arc, Mutex};
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("ok").await;
+        mock.mock_models(json!({"models": [
+            {"id": "mock/model", "provider": "mock", "context_length": 1000}
+        ]}))
+        .await;
+        let overflows: Arc<Mutex<Vec<ContextOverflow>>> = Arc::default();
+        let recorder = overflows.clone();
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_default_max_tokens(1024)
+            .with_context_overflow_handler(move |overflow| recorder.lock().unwrap().push(overflow.clone()))
+            .with_api_key(MOCK_API_KEY)?;
+        let chat = client.chat()?;
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+        chat.chat_completion(request.clone()).await?;
+        chat.chat_completion(request.clone().with_max_tokens(16)).await?;
+        let bodies = mock.received_bodies().await;
+        assert_eq!(bodies[0]["max_tokens"], 1024);
+        assert_eq!(bodies[1]["max_tokens"], 16);
+
+        // Once the model is cached, the handler hears about the default 1024 overflowing.
+        assert!(overflows.lock().unwrap().is_empty());
+        client.models()?.list_models_cached(None).await?;
+        chat.chat_completion(request.clone()).await?;
+        let reported = overflows.lock().unwrap().clone();
+        assert_eq!(reported.len(), 1);
+        assert_eq!((reported[0].max_tokens, reported[0].context_length), (1024, 1000));
+
+        let model: ModelInfo = serde_json::from_value(json!({
+            "id": "mock/model",
+            "provider": "mock",
+            "context_length": 1000
+        }))?;
+        let overflow = context_overflow(&request.clone().with_max_tokens(1000), &model).expect("overflows");
+        assert_eq!(overflow.model, "mock/model");
+        assert!(context_overflow(&request.clone().with_max_tokens(500), &model).is_none());
+        assert!(context_overflow(&request, &model).is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_key_is_stable_across_retries() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{MockClient, MOCK_API_KEY};
+
+        let header = |request: &wiremock::Request| {
+            request
+                .headers
+                .iter()
+                .find(|(name, _)| name.as_str().eq_ignore_ascii_case("idempotency-key"))
+                .map(|(_, values)| values.last().as_str().to_string())
+        };
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("ok").await;
+        mock.mock_chat_error(503, json!({"error": {"message": "busy"}}), 1).await;
+        let chat = mock.client()?.chat()?;
+        chat.chat_completion(request.clone()).await?;
+        chat.chat_completion(request.clone()).await?;
+
+        let received = mock.server().received_requests().await.unwrap_or_default();
+        let keys: Vec<_> = received.iter().map(header).collect();
+        assert_eq!(keys.len(), 3);
+        assert!(keys[0].is_some());
+        assert_eq!(keys[0], keys[1], "a retry reuses the key");
+        assert_ne!(keys[1], keys[2], "a new request gets a new key");
+
+        // Without idempotency keys, an error status that may have been acted on isn't retried.
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("ok").await;
+        mock.mock_chat_error(503, json!({"error": {"message": "busy"}}), 1).await;
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_idempotency(false)
+            .with_api_key(MOCK_API_KEY)?;
+        assert!(matches!(
+            client.chat()?.chat_completion(request).await,
+            Err(crate::error::Error::ApiError { code: 503, .. })
+        ));
+        let received = mock.server().received_requests().await.unwrap_or_default();
+        assert_eq!(received.len(), 1);
+        assert_eq!(header(&received[0]), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_supplied_http_client_keeps_its_headers() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{MockClient, MOCK_API_KEY};
+        use reqwest::header::{HeaderMap, HeaderValue};
+
+        let mut defaults = HeaderMap::new();
+        defaults.insert("X-Service", HeaderValue::from_static("billing"));
+        let shared = reqwest::Client::builder().default_headers(defaults).build()?;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("ok").await;
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_site_title("Billing")
+            .with_http_client(shared)
+            .with_api_key(MOCK_API_KEY)?;
+        assert_eq!(client.chat()?.simple_completion("mock/model", "Hello").await?, "ok");
+
+        let received = mock.server().received_requests().await.unwrap_or_default();
+        let header = |wanted: &str| {
+            received[0]
+                .headers
+                .iter()
+                .find(|(name, _)| name.as_str().eq_ignore_ascii_case(wanted))
+                .map(|(_, values)| values.last().as_str().to_string())
+        };
+        assert_eq!(header("x-service").as_deref(), Some("billing"));
+        assert_eq!(header("x-title").as_deref(), Some("Billing"));
+        assert_eq!(header("authorization"), Some(format!("Bearer {}", MOCK_API_KEY)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_with_rate_limit_metadata() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_response, MockClient};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(chat_response("ok"))
+                    .insert_header("X-RateLimit-Limit", "20")
+                    .insert_header("X-RateLimit-Remaining", "19")
+                    .insert_header("X-RateLimit-Reset", "1700000000000")
+                    .insert_header("X-Request-Id", "req-123"),
+            )
+            .mount(mock.server())
+            .await;
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+        let (response, meta) = mock.client()?.chat()?.chat_completion_with_meta(request).await?;
+        assert_eq!(response.content(), Some("ok"));
+        assert_eq!(meta.rate_limit_limit, Some(20));
+        assert_eq!(meta.rate_limit_remaining, Some(19));
+        assert_eq!(meta.rate_limit_reset, Some(1_700_000_000_000));
+        assert_eq!(meta.request_id.as_deref(), Some("req-123"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_errors_carry_structured_details() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::error::ApiErrorDetails;
+        use crate::test_util::MockClient;
+        use futures::StreamExt;
+
+        let body = json!({
+            "error": {
+                "code": 400,
+                "message": "Provider returned error",
+                "metadata": {"provider_name": "Mock"}
+            }
+        });
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_error(400, body.clone(), 2).await;
+        let client = mock.client()?;
+        let chat = client.chat()?;
+
+        let blocking = chat.chat_completion(request.clone()).await.unwrap_err();
+        let streaming = chat.chat_completion_stream(request).next().await.unwrap().unwrap_err();
+        for error in [blocking, streaming] {
+            let details = error.api_error_details().expect("structured details");
+            assert_eq!(details.code.as_deref(), Some("400"));
+            assert_eq!(details.message.as_deref(), Some("Provider returned error"));
+            assert_eq!(details.metadata, Some(json!({"provider_name": "Mock"})));
+        }
+
+        assert!(ApiErrorDetails::from_body("upstream timed out").is_none());
+        assert!(ApiErrorDetails::from_body("{}").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_api_key_rejects_malformed_keys() -> Result<(), Box<dyn std::error::Error>> {
+        let builder = || OpenRouterClient::new().with_base_url("https://openrouter.ai/api/v1/");
+
+        for key in ["", "   ", "sk-or"] {
+            assert!(
+                matches!(builder()?.with_api_key(key), Err(crate::error::Error::ConfigError(_))),
+                "{:?} should be rejected",
+                key
+            );
+        }
+        assert!(builder()?.with_api_key("sk-or-v1-0123456789").is_ok());
+
+        let client = builder()?.with_api_key_unchecked("short")?;
+        assert_eq!(client.config.api_key.as_deref(), Some("short"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_embedding_store_cosine_search() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::utils::embedding_store::EmbeddingStore;
+
+        let mut store = EmbeddingStore::new();
+        store.add("cats", "Cats purr.", vec![1.0, 0.0, 0.0])?;
+        store.add("dogs", "Dogs bark.", vec![10.0, 10.0, 0.0])?;
+        store.add("tax", "Taxes are due.", vec![0.0, 0.0, 3.0])?;
+
+        // Magnitude doesn't matter, only direction.
+        let results = store.search(&[2.0, 0.1, 0.0], 2)?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "cats");
+        assert_eq!(results[1].0, "dogs");
+        assert!((results[1].1 - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.05);
+        assert!(results[0].1 <= 1.0 + f32::EPSILON);
+
+        // Re-adding an ID replaces it.
+        store.add("tax", "Refunds arrive.", vec![1.0, 0.0, 0.0])?;
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.text("tax"), Some("Refunds arrive."));
+
+        assert!(store.add("bad", "", vec![1.0, 2.0]).is_err());
+        assert!(store.add("zero", "", vec![0.0, 0.0, 0.0]).is_err());
+        assert!(store.search(&[1.0], 1).is_err());
+        assert!(store.remove("cats") && !store.remove("cats"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_common_and_chat_message_conversions() {
+        use crate::types::chat::Message as ChatMessage;
+        use crate::types::common::Message as CommonMessage;
+
+        let common = CommonMessage {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+        };
+        let chat: ChatMessage = common.into();
+        assert_eq!(chat.role, "user");
+        assert_eq!(chat.content, "Hello");
+        assert!(chat.tool_calls.is_none() && chat.tool_call_id.is_none() && chat.name.is_none());
+
+        let chat = ChatMessage {
+            role: "tool".to_string(),
+            content: "42".into(),
+            name: Some("calculator".to_string()),
+            tool_call_id: Some("call_1".to_string()),
+            tool_calls: None,
+            reasoning: Some("thinking".to_string()),
+        };
+        let common = CommonMessage::from(chat);
+        assert_eq!(common.role, "tool");
+        assert_eq!(common.content, "42");
+    }
+
+    #[test]
+    fn test_chat_message_round_trips_tool_conversation() {
+        use crate::models::tool::{FunctionCall, ToolCall};
+        use crate::types::chat::Message;
+
+        let assistant = ChatMessage {
+            role: ChatRole::Assistant,
+            content: String::new(),
+            tool_call_id: None,
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_string(),
+                kind: "function".to_string(),
+                function_call: FunctionCall {
+                    name: "get_weather".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            }]),
+        };
+        let tool = ChatMessage {
+            role: ChatRole::Tool,
+            content: "sunny".to_string(),
+            tool_call_id: Some("call_1".to_string()),
+            tool_calls: None,
+        };
+
+        let api: Message = assistant.into();
+        assert_eq!(api.role, "assistant");
+        let back = ChatMessage::try_from(api).unwrap();
+        assert_eq!(back.role, ChatRole::Assistant);
+        assert_eq!(back.tool_calls.unwrap()[0].id, "call_1");
+
+        let api: Message = tool.into();
+        assert_eq!(api.role, "tool");
+        let back = ChatMessage::try_from(api).unwrap();
+        assert_eq!(back.role, ChatRole::Tool);
+        assert_eq!(back.tool_call_id.as_deref(), Some("call_1"));
+
+        let developer = Message::new("developer", "Be brief.");
+        assert_eq!(ChatMessage::try_from(developer).unwrap().role, ChatRole::Developer);
+
+        let unknown = Message::new("narrator", "");
+        assert!(matches!(
+            ChatMessage::try_from(unknown),
+            Err(crate::error::Error::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_conversation_builder_enforces_tool_ordering() {
+        use crate::models::tool::{FunctionCall, ToolCall};
+        use crate::types::conversation::ConversationBuilder;
+
+        let call = |id: &str| ToolCall {
+            id: id.to_string(),
+            kind: "function".to_string(),
+            function_call: FunctionCall {
+                name: "lookup".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+
+        let messages = ConversationBuilder::new()
+            .system("Be helpful.")
+            .user("Look up a and b.")
+            .assistant_tool_calls(vec![call("a"), call("b")])
+            .tool("b", "B")
+            .tool("a", "A")
+            .assistant("Done.")
+            .build()
+            .unwrap();
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "tool", "tool", "assistant"]);
+        assert_eq!(messages[2].tool_calls.as_ref().unwrap().len(), 2);
+        assert_eq!(messages[3].tool_call_id.as_deref(), Some("b"));
+
+        // A tool result without a preceding tool call.
+        assert!(ConversationBuilder::new().user("hi").tool("a", "A").build().is_err());
+        // Answering the same call twice.
+        assert!(ConversationBuilder::new()
+            .assistant_tool_calls(vec![call("a")])
+            .tool("a", "A")
+            .tool("a", "A")
+            .build()
+            .is_err());
+        // A tool result after the turn has moved on.
+        assert!(ConversationBuilder::new()
+            .assistant_tool_calls(vec![call("a")])
+            .user("never mind")
+            .tool("a", "A")
+            .build()
+            .is_err());
+        assert!(ConversationBuilder::new().assistant_tool_calls(vec![]).build().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_api_key_rotation_on_auth_failure() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("hello").await;
+        for (key, status) in [("sk-or-key-one-xxxx", 401), ("sk-or-key-two-xxxx", 402)] {
+            Mock::given(method("POST"))
+                .and(path("/chat/completions"))
+                .and(header("Authorization", format!("Bearer {}", key).as_str()))
+                .respond_with(ResponseTemplate::new(status).set_body_json(json!({
+                    "error": {"code": status, "message": "key rejected"}
+                })))
+                .with_priority(1)
+                .mount(mock.server())
+                .await;
+        }
+
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_api_keys(vec![
+                "sk-or-key-one-xxxx".to_string(),
+                "sk-or-key-two-xxxx".to_string(),
+                "sk-or-key-three-xx".to_string(),
+            ])?;
+        assert_eq!(client.active_api_key_index(), Some(0));
+
+        let reply = client.chat()?.simple_completion("mock/model", "hi").await?;
+        assert_eq!(reply, "hello");
+        assert_eq!(client.active_api_key_index(), Some(2));
+
+        // The working key stays active for later requests.
+        client.chat()?.simple_completion("mock/model", "hi").await?;
+        let keys: Vec<String> = mock
+            .server()
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| request.headers.get(&"authorization".into()).unwrap().last().as_str().to_string())
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "Bearer sk-or-key-one-xxxx",
+                "Bearer sk-or-key-two-xxxx",
+                "Bearer sk-or-key-three-xx",
+                "Bearer sk-or-key-three-xx"
+            ]
+        );
+
+        // Once every key has been tried, the last rejection is returned.
+        let mock = MockClient::start().await;
+        mock.mock_chat_error(401, json!({"error": {"code": 401, "message": "bad key"}}), 10).await;
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_api_keys(vec!["sk-or-key-one-xxxx".to_string(), "sk-or-key-two-xxxx".to_string()])?;
+        let err = client.chat()?.simple_completion("mock/model", "hi").await.unwrap_err();
+        assert!(matches!(err, crate::error::Error::AuthenticationError { .. }));
+        assert_eq!(mock.received_bodies().await.len(), 2);
+
+        assert!(OpenRouterClient::new().with_base_url(mock.base_url())?.with_api_keys(vec![]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_debug_output_redacts_api_key() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::utils::http::{RequestLogger, RequestParts};
+
+        let request = ChatCompletionRequest::new("openai/gpt-4o", vec![Message::user("Hello")])
+        .with_max_tokens(16);
+        let pretty = request.to_pretty_json()?;
+        assert!(pretty.contains("\n  \"model\": \"openai/gpt-4o\""));
+        assert!(!pretty.contains("\"tools\""));
+
+        let config = OpenRouterClient::new()
+            .with_base_url("https://openrouter.ai/api/v1/")?
+            .with_api_key("sk-or-secret-key")?
+            .config;
+        let logged = RequestLogger::describe(&RequestParts {
+            method: reqwest::Method::POST,
+            url: config.base_url.join("chat/completions")?,
+            headers: config.build_headers()?,
+            body: Some(serde_json::to_vec(&request)?),
+        });
+        assert!(logged.contains("POST https://openrouter.ai/api/v1/chat/completions"));
+        assert!(logged.contains("authorization: <redacted>"));
+        assert!(!logged.contains("sk-or-secret-key"));
+        assert!(logged.contains("\"max_tokens\": 16"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_web_search_request_filters_serialize_only_when_set() {
+        use crate::types::web_search::{SearchDepth, SearchRecency, WebSearchRequest};
+
+        let bare = serde_json::to_value(WebSearchRequest::new("rust async")).unwrap();
+        assert_eq!(bare, json!({"query": "rust async"}));
+
+        let request = WebSearchRequest::new("rust async")
+            .with_num_results(5)
+            .with_search_depth(SearchDepth::Advanced)
+            .with_include_domains(["docs.rs", "rust-lang.org"])
+            .with_exclude_domains(vec!["example.com".to_string()])
+            .with_recency(SearchRecency::Week);
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            json!({
+                "query": "rust async",
+                "num_results": 5,
+                "search_depth": "advanced",
+                "include_domains": ["docs.rs", "rust-lang.org"],
+                "exclude_domains": ["example.com"],
+                "recency": "week"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_all_models_follows_cursors() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use crate::types::models::ModelsRequest;
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        mock.mock_models(json!({
+            "models": [{"id": "openai/gpt-4o", "provider": "openai"}],
+            "next_cursor": "page-2"
+        }))
+        .await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .and(query_param("cursor", "page-2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "models": [{"id": "anthropic/claude-3-opus", "provider": "anthropic"}]
+            })))
+            .with_priority(1)
+            .mount(mock.server())
+            .await;
+
+        let models = mock.client()?.models()?;
+        let all = models
+            .list_all_models(Some(ModelsRequest {
+                limit: Some(1),
+                ..ModelsRequest::default()
+            }))
+            .await?;
+        let ids: Vec<&str> = all.models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["openai/gpt-4o", "anthropic/claude-3-opus"]);
+        assert!(all.next_cursor.is_none());
+
+        let requests = mock.server().received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].url.query(), Some("limit=1"));
+        assert_eq!(requests[1].url.query(), Some("limit=1&cursor=page-2"));
+
+        // A single page exposes the cursor to callers paging by hand.
+        let page = models.list_models(None).await?;
+        assert_eq!(page.next_cursor.as_deref(), Some("page-2"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_models_filtered_by_context_length_and_modality() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use crate::types::models::ModelsRequest;
+
+        let mock = MockClient::start().await;
+        mock.mock_models(json!({"models": [
+            {"id": "a/vision-long", "provider": "a", "context_length": 200000,
+             "architecture": {"input_modalities": ["text", "image"]}},
+            {"id": "b/text-long", "provider": "b", "context_length": 128000,
+             "architecture": {"input_modalities": ["text"]}},
+            {"id": "c/vision-short", "provider": "c", "context_length": 8000,
+             "architecture": {"modality": "text+image->text"}},
+            {"id": "d/unknown", "provider": "d"}
+        ]}))
+        .await;
+        let models = mock.client()?.models()?;
+
+        let request = ModelsRequest {
+            min_context_length: Some(100_000),
+            modality: Some("image".to_string()),
+            ..ModelsRequest::default()
+        };
+        let ids = |response: crate::types::models::ModelsResponse| {
+            response.models.into_iter().map(|m| m.id).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(models.list_models(Some(request.clone())).await?), ["a/vision-long"]);
+        assert_eq!(ids(models.list_models_cached(Some(request)).await?), ["a/vision-long"]);
+
+        // Client-side filters are not sent to the server.
+        let received = mock.server().received_requests().await.unwrap();
+        assert_eq!(received[0].url.query(), None);
+
+        let long = models
+            .find_models(|m| m.context_length.unwrap_or(0) >= 100_000)
+            .await?;
+        assert_eq!(long.len(), 2);
+        let images = models.find_models(|m| m.accepts_input("image")).await?;
+        assert_eq!(images.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_success_bodies_are_deserialization_errors() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use crate::utils::auth::pkce::exchange_code_for_key_with;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        mock.mock_models(json!({"data": "not a model list"})).await;
+        Mock::given(method("POST"))
+            .and(path("/auth/keys"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"token": "unexpected"})))
+            .mount(mock.server())
+            .await;
+
+        let err = mock.client()?.models()?.list_models(None).await.unwrap_err();
+        assert!(
+            matches!(&err, crate::error::Error::DeserializationError { status: 200, body, .. } if body.contains("not a model list")),
+            "got {:?}",
+            err
+        );
+
+        let base_url = url::Url::parse(&mock.base_url())?;
+        let err = exchange_code_for_key_with(&reqwest::Client::new(), &base_url, "code", "verifier")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, crate::error::Error::DeserializationError { status: 200, .. }),
+            "got {:?}",
+            err
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_data_collection_and_require_parameters_reach_the_wire() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use crate::types::provider::ProviderPreferences;
+        use crate::types::routing::{PredefinedModelCoverageProfile, RouterConfig};
+
+        let message = Message::user("Hello");
+        let request = ChatCompletionRequest::new("mock/model", vec![message.clone()]).with_provider(
+            ProviderPreferences::new()
+                .with_require_parameters(true)
+                .with_data_collection("deny"),
+        );
+        assert_eq!(
+            serde_json::to_value(&request)?["provider"],
+            json!({"require_parameters": true, "data_collection": "deny"})
+        );
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("ok").await;
+        let mut client = mock.client()?;
+        client.router_config = Some(RouterConfig {
+            profile: PredefinedModelCoverageProfile::LowestCost,
+            provider_preferences: Some(
+                ProviderPreferences::new()
+                    .with_data_collection("deny")
+                    .with_require_parameters(true)
+                    .with_provider_option("openai", json!({"a": 1})),
+            ),
+        });
+        let chat = client.chat()?;
+
+        // Client-wide compliance settings survive a request that sets its own preferences.
+        chat.chat_completion(
+            ChatCompletionRequest::new("mock/model", vec![message.clone()]).with_provider(
+                ProviderPreferences::new()
+                    .with_sort("latency")
+                    .with_provider_option("openai", json!({"a": 2})),
+            ),
+        )
+        .await?;
+        // Settings on the request win over the client's.
+        chat.chat_completion(
+            ChatCompletionRequest::new("mock/model", vec![message])
+                .with_provider(ProviderPreferences::new().with_data_collection("allow")),
+        )
+        .await?;
+
+        let bodies = mock.received_bodies().await;
+        assert_eq!(
+            bodies[0]["provider"],
+            json!({
+                "require_parameters": true,
+                "data_collection": "deny",
+                "sort": "latency",
+                "provider_options": {"openai": {"a": 2}}
+            })
+        );
+        assert_eq!(bodies[1]["provider"]["data_collection"], "allow");
+        assert_eq!(bodies[1]["provider"]["require_parameters"], true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_route_fallback_serialization() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::chat::Route;
+
+        let request = ChatCompletionRequest::new("openai/gpt-4o", vec![]);
+        assert!(serde_json::to_value(&request)?.get("route").is_none());
+
+        let mut request = request.with_route(Route::Fallback);
+        request.models = Some(vec!["anthropic/claude-3-opus".to_string()]);
+        let body = serde_json::to_value(&request)?;
+        assert_eq!(body["route"], "fallback");
+        assert_eq!(body["models"], json!(["anthropic/claude-3-opus"]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_with_callback_assembles_stream() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_chunk, MockClient};
+
+        // Tool calls arrive in fragments: only the first names the call, the arguments
+        // are split across frames, and a second call starts at index 1.
+        let fragment = |delta: serde_json::Value, finish: Option<&str>| {
+            json!({
+                "id": "gen-mock",
+                "choices": [{"index": 0, "delta": delta, "finish_reason": finish}]
+            })
+        };
+        let mock = MockClient::start().await;
+        mock.mock_chat_stream(&[
+            chat_chunk("Hel"),
+            chat_chunk("lo"),
+            fragment(json!({"tool_calls": [{
+                "index": 0,
+                "id": "call_1",
+                "type": "function",
+                "function": {"name": "lookup", "arguments": ""}
+            }]}), None),
+            fragment(json!({"tool_calls": [{"index": 0, "function": {"arguments": "{\"q\":"}}]}), None),
+            fragment(json!({"tool_calls": [{"index": 0, "function": {"arguments": "\"rust\"}"}}]}), None),
+            fragment(json!({"tool_calls": [{
+                "index": 1,
+                "id": "call_2",
+                "type": "function",
+                "function": {"name": "fetch", "arguments": "{}"}
+            }]}), None),
+            fragment(json!({"content": null}), Some("tool_calls")),
+        ])
+        .await;
+
+        let mut deltas = Vec::new();
+        let mut tool_calls = Vec::new();
+        let response = mock
+            .client()?
+            .chat()?
+            .chat_completion_with_callback(
+                ChatCompletionRequest::new("mock/model", vec![Message::user("Hi")]),
+                |delta| deltas.push(delta.to_string()),
+                |call| {
+                    tool_calls.push((call.function_call.name.clone(), call.function_call.arguments.clone()))
+                },
+            )
+            .await?;
+
+        assert_eq!(deltas, ["Hel", "lo"]);
+        assert_eq!(
+            tool_calls,
+            [
+                ("lookup".to_string(), r#"{"q":"rust"}"#.to_string()),
+                ("fetch".to_string(), "{}".to_string())
+            ]
+        );
+        assert_eq!(response.id, "gen-mock");
+        assert_eq!(response.model, "mock/model");
+        assert_eq!(response.content(), Some("Hello"));
+        let calls = response.tool_calls().expect("tool calls were streamed");
+        assert_eq!(calls.len(), 2);
+        assert_eq!((calls[0].id.as_str(), calls[0].kind.as_str()), ("call_1", "function"));
+        assert_eq!(calls[1].id, "call_2");
+        assert_eq!(response.choices[0].finish_reason.as_deref(), Some("tool_calls"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_accumulator_merges_choices_by_index() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::chat::{ChatCompletionChunk, ChatStreamAccumulator};
+
+        let mut accumulator = ChatStreamAccumulator::new();
+        for frame in [
+            json!({"id": "gen-1", "choices": [
+                {"index": 1, "delta": {"content": "B"}},
+                {"index": 0, "delta": {"content": "A"}}
+            ]}),
+            // A proxy sending `message` instead of `delta`, with one choice per frame.
+            json!({"id": "gen-1", "choices": [{"index": 1, "message": {"content": "b"}, "finish_reason": "stop"}]}),
+            json!({"id": "gen-1", "choices": [{"index": 0, "delta": {"content": "a"}, "finish_reason": "stop"}]}),
+        ] {
+            accumulator.push(serde_json::from_value::<ChatCompletionChunk>(frame)?);
+        }
+
+        let response = accumulator.finish("mock/model");
+        let contents: Vec<_> = response.choices.iter().map(|choice| choice.message.content.text()).collect();
+        assert_eq!(contents, ["Aa", "Bb"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_structured_output_rejects_undeclared_nested_fields() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::models::structured::JsonSchemaConfig;
+        use crate::test_util::MockClient;
+        use crate::utils::validation::validate_against_schema;
+
+        #[derive(Debug, serde::Deserialize)]
+        struct Order {
+            #[allow(dead_code)]
+            items: Vec<serde_json::Value>,
+        }
+
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "items": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {"sku": {"type": "string"}},
+                        "additionalProperties": false
+                    }
+                }
+            },
+            "additionalProperties": false
+        });
+        let config = JsonSchemaConfig {
+            name: "order".to_string(),
+            strict: true,
+            schema: serde_json::from_value(schema.clone())?,
+        };
+
+        // serde would accept the extra key, but the schema forbids it.
+        let mock = MockClient::start().await;
+        mock.mock_chat_content(r#"{"items": [{"sku": "a1"}, {"sku": "b2", "discount": 0.5}]}"#)
+            .await;
+        let result = mock
+            .client()?
+            .structured()?
+            .generate::<Order>("mock/model", vec![], config)
+            .await;
+        match result {
+            Err(crate::error::Error::SchemaValidationError(msg)) => {
+                assert!(msg.contains("$.items[1]: Unexpected field 'discount'"), "{}", msg)
+            }
+            other => panic!("Expected a SchemaValidationError, got {:?}", other),
+        }
+
+        // A schema-valued additionalProperties checks the undeclared values instead.
+        let map_schema = json!({"type": "object", "additionalProperties": {"type": "integer"}});
+        assert!(validate_against_schema(&map_schema, &json!({"a": 1, "b": 2})).is_ok());
+        let err = validate_against_schema(&map_schema, &json!({"a": 1, "b": "two"})).unwrap_err();
+        assert!(err.to_string().contains("$.b: Expected integer"), "{}", err);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_system_prompt_is_prepended_unless_overridden() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{MockClient, MOCK_API_KEY};
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("ok").await;
+        let chat = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_default_system_prompt("You are a concise assistant.")
+            .with_api_key(MOCK_API_KEY)?
+            .chat()?;
+
+        chat.simple_completion("mock/model", "Hello").await?;
+        chat.simple_completion_with_system("mock/model", "Answer in French.", "Hello")
+            .await?;
+
+        let bodies = mock.received_bodies().await;
+        assert_eq!(bodies[0]["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(bodies[0]["messages"][0]["role"], "system");
+        assert_eq!(bodies[0]["messages"][0]["content"], "You are a concise assistant.");
+        assert_eq!(bodies[0]["messages"][1]["content"], "Hello");
+
+        assert_eq!(bodies[1]["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(bodies[1]["messages"][0]["content"], "Answer in French.");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_chunks_expose_created_and_model() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_chunk, MockClient};
+        use futures::StreamExt;
+
+        let mut first = chat_chunk("Hi");
+        first["created"] = json!(1_700_000_000);
+        first["model"] = json!("openai/gpt-4o-2024-08-06");
+        let mock = MockClient::start().await;
+        mock.mock_chat_stream(&[first, chat_chunk("!")]).await;
+
+        let chat = mock.client()?.chat()?;
+        let request = ChatCompletionRequest::new("openai/gpt-4o", vec![Message::user("Hello")]);
+        let chunks: Vec<_> = chat.chat_completion_stream(request.clone()).collect().await;
+        let first = chunks[0].as_ref().unwrap();
+        assert_eq!(first.created, Some(1_700_000_000));
+        assert_eq!(first.model.as_deref(), Some("openai/gpt-4o-2024-08-06"));
+        let second = chunks[1].as_ref().unwrap();
+        assert_eq!((second.created, second.model.as_deref()), (None, None));
+
+        // The assembled response reports the model that actually served the stream.
+        let response = chat.chat_completion_with_callback(request, |_| {}, |_| {}).await?;
+        assert_eq!(response.model, "openai/gpt-4o-2024-08-06");
+        assert_eq!(response.created, Some(1_700_000_000));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tool_result_message() -> Result<(), Box<dyn std::error::Error>> {
+        #[derive(serde::Serialize)]
+        struct Weather {
+            city: &'static str,
+            celsius: i32,
+        }
+
+        let message = Message::tool_result("call_1", Weather { city: "Oslo", celsius: -3 })?;
+        assert_eq!(message.role, "tool");
+        assert_eq!(message.tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&message.content.text())?,
+            json!({"city": "Oslo", "celsius": -3})
+        );
+
+        assert_eq!(Message::tool_result("call_2", "sunny")?.content, "sunny");
+        assert_eq!(Message::tool_result("call_3", 42)?.content, "42");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_web_search_request_validated_before_sending() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+        use crate::types::web_search::WebSearchRequest;
+        use crate::utils::validation::{validate_web_search_request, MAX_WEB_SEARCH_RESULTS};
+
+        assert!(validate_web_search_request(&WebSearchRequest::new("rust").with_num_results(10)).is_ok());
+        assert!(validate_web_search_request(&WebSearchRequest::new("  ")).is_err());
+        assert!(validate_web_search_request(
+            &WebSearchRequest::new("rust").with_num_results(MAX_WEB_SEARCH_RESULTS + 1)
+        )
+        .is_err());
+        assert!(validate_web_search_request(&WebSearchRequest::new("rust").with_include_domains([""])).is_err());
+
+        let mock = MockClient::start().await;
+        let err = mock
+            .client()?
+            .web_search()?
+            .search(WebSearchRequest::new("rust").with_num_results(0))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::error::Error::ConfigError(ref msg) if msg.contains("num_results")));
+        assert!(mock.received_bodies().await.is_empty());
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hi")])
+        .with_max_tokens(0);
+        assert!(crate::utils::validation::validate_chat_request(&request).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mcp_json_rpc_errors_keep_their_code() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::{MCPClient, INVALID_PARAMS, METHOD_NOT_FOUND};
+        use crate::mcp::transport::Transport;
+        use crate::mcp::types::{ClientCapabilities, GetResourceParams, MCP_PROTOCOL_VERSION};
+        use async_trait::async_trait;
+        use std::sync::Mutex;
+
+        // Answers `initialize`, then fails every request with the next queued error code.
+        struct FailingTransport {
+            codes: Mutex<Vec<i32>>,
+            pending: Mutex<Vec<Value>>,
+        }
+
+        #[async_trait]
+        impl Transport for FailingTransport {
+            async fn send(&self, message: Value) -> crate::error::Result<()> {
+                let response = if message["method"] == "initialize" {
+                    json!({"jsonrpc": "2.0", "id": message["id"], "result": {"protocol_version": "2025-03-26"}})
+                } else {
+                    let code = self.codes.lock().unwrap().remove(0);
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": message["id"],
+                        "error": {"code": code, "message": "nope", "data": {"method": message["method"]}}
+                    })
+                };
+                self.pending.lock().unwrap().push(response);
+                Ok(())
+            }
+
+            async fn receive(&self) -> crate::error::Result<Value> {
+                Ok(self.pending.lock().unwrap().remove(0))
+            }
+        }
+
+        let client = MCPClient::new(FailingTransport {
+            codes: Mutex::new(vec![METHOD_NOT_FOUND, INVALID_PARAMS]),
+            pending: Mutex::new(Vec::new()),
+        });
+        client
+            .initialize(ClientCapabilities {
+                protocol_version: MCP_PROTOCOL_VERSION.to_string(),
+                supports_sampling: None,
+            })
+            .await?;
+
+        let params = || GetResourceParams {
+            id: "doc".to_string(),
+            parameters: None,
+        };
+        match client.get_resource(params()).await {
+            Err(crate::error::Error::JsonRpcError { code, message, data }) => {
+                assert_eq!(code, -32601);
+                assert_eq!(message, "nope");
+                assert_eq!(data, Some(json!({"method": "getResource"})));
+            }
+            other => panic!("Expected a JsonRpcError, got {:?}", other),
+        }
+        let err = client.get_resource(params()).await.unwrap_err();
+        assert!(matches!(err, crate::error::Error::JsonRpcError { code: INVALID_PARAMS, .. }));
+        assert_eq!(err.to_string(), "JSON-RPC error -32602: nope");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mcp_initialize_rejects_protocol_mismatch() {
+        use crate::mcp::client::MCPClient;
+        use crate::mcp::transport::Transport;
+        use crate::mcp::types::{ClientCapabilities, MCP_PROTOCOL_VERSION};
+        use async_trait::async_trait;
+        use std::sync::Mutex;
+
+        // An older server that answers `initialize` with its own protocol version.
+        #[derive(Default)]
+        struct OldServer {
+            pending: Mutex<Vec<Value>>,
+        }
+
+        #[async_trait]
+        impl Transport for OldServer {
+            async fn send(&self, message: Value) -> crate::error::Result<()> {
+                self.pending.lock().unwrap().push(json!({
+                    "jsonrpc": "2.0",
+                    "id": message["id"],
+                    "result": {"protocol_version": "2024-11-05"}
+                }));
+                Ok(())
+            }
+
+            async fn receive(&self) -> crate::error::Result<Value> {
+                Ok(self.pending.lock().unwrap().remove(0))
+            }
+        }
+
+        let client = MCPClient::new(OldServer::default());
+        let err = client
+            .initialize(ClientCapabilities {
+                protocol_version: MCP_PROTOCOL_VERSION.to_string(),
+                supports_sampling: None,
+            })
+            .await
+            .unwrap_err();
+        match err {
+            crate::error::Error::ConfigError(msg) => {
+                assert!(msg.contains("2024-11-05") && msg.contains(MCP_PROTOCOL_VERSION), "{}", msg)
+            }
+            other => panic!("Expected a ConfigError, got {:?}", other),
+        }
+
+        // The client stays uninitialized rather than working against an incompatible server.
+        let err = client.list_resources().await.unwrap_err();
+        assert!(matches!(err, crate::error::Error::ConfigError(ref msg) if msg.contains("not initialized")));
+    }
+
+    #[tokio::test]
+    async fn test_mcp_client_clones_share_state_and_reinitialize() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::MCPClient;
+        use crate::mcp::transport::Transport;
+        use crate::mcp::types::{ClientCapabilities, MCP_PROTOCOL_VERSION};
+        use async_trait::async_trait;
+        use std::sync::Mutex;
+
+        // Advertises one more tool on every `initialize`, like a server that was upgraded.
+        #[derive(Default)]
+        struct GrowingServer {
+            initializations: Mutex<usize>,
+            pending: Mutex<Vec<Value>>,
+        }
+
+        #[async_trait]
+        impl Transport for GrowingServer {
+            async fn send(&self, message: Value) -> crate::error::Result<()> {
+                let mut count = self.initializations.lock().unwrap();
+                *count += 1;
+                let tools: Vec<Value> = (0..*count)
+                    .map(|i| json!({
+                        "id": format!("tool-{}", i),
+                        "name": format!("tool-{}", i),
+                        "parameter_schema": {},
+                        "return_schema": {}
+                    }))
+                    .collect();
+                self.pending.lock().unwrap().push(json!({
+                    "jsonrpc": "2.0",
+                    "id": message["id"],
+                    "result": {"protocol_version": MCP_PROTOCOL_VERSION, "tools": {"tools": tools}}
+                }));
+                Ok(())
+            }
+
+            async fn receive(&self) -> crate::error::Result<Value> {
+                Ok(self.pending.lock().unwrap().remove(0))
+            }
+        }
+
+        let client = MCPClient::new(GrowingServer::default());
+        assert!(client.reinitialize().await.is_err());
+
+        client
+            .initialize(ClientCapabilities {
+                protocol_version: MCP_PROTOCOL_VERSION.to_string(),
+                supports_sampling: None,
+            })
+            .await?;
+        let shared = client.clone();
+        let tool_count = |caps: Option<crate::mcp::types::ServerCapabilities>| {
+            caps.and_then(|caps| caps.tools).map_or(0, |tools| tools.tools.len())
+        };
+        assert_eq!(tool_count(shared.capabilities().await), 1);
+
+        // Refreshing through one handle updates what every clone sees.
+        let refreshed = tokio::spawn(async move { shared.reinitialize().await }).await??;
+        assert_eq!(refreshed.tools.map(|tools| tools.tools.len()), Some(2));
+        assert_eq!(tool_count(client.capabilities().await), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_structured_response_format_is_nested_under_json_schema() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::models::structured::JsonSchemaConfig;
+        use crate::test_util::MockClient;
+
+        let schema = json!({
+            "type": "object",
+            "properties": {"answer": {"type": "string"}},
+            "required": ["answer"],
+            "additionalProperties": false
+        });
+        let config = JsonSchemaConfig {
+            name: "answer".to_string(),
+            strict: true,
+            schema: serde_json::from_value(schema.clone())?,
+        };
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content(r#"{"answer": "42"}"#).await;
+        let answer: Value = mock
+            .client()?
+            .structured()?
+            .generate("openai/gpt-4o", vec![], config)
+            .await?;
+        assert_eq!(answer, json!({"answer": "42"}));
+
+        let body = &mock.received_bodies().await[0];
+        assert_eq!(
+            body["response_format"],
+            json!({
+                "type": "json_schema",
+                "json_schema": {"name": "answer", "strict": true, "schema": schema}
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mcp_client_fits_context_with_custom_token_counter() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::MCPClient;
+        use crate::mcp::strategy::TruncationStrategy;
+        use crate::mcp::transport::Transport;
+        use crate::utils::validation::TokenCounter;
+        use async_trait::async_trait;
+
+        struct NoTransport;
+
+        #[async_trait]
+        impl Transport for NoTransport {
+            async fn send(&self, _message: Value) -> crate::error::Result<()> {
+                Ok(())
+            }
+
+            async fn receive(&self) -> crate::error::Result<Value> {
+                Err(crate::error::Error::TransportError("nothing to receive".into()))
+            }
+        }
+
+        /// Counts one token per word
+        struct WordCounter;
+
+        #[async_trait]
+        impl TokenCounter for WordCounter {
+            async fn count_message_tokens(&self, message: &Message) -> crate::error::Result<usize> {
+                Ok(message.content.text().split_whitespace().count())
+            }
+        }
+
+        let messages: Vec<Message> = (0..3)
+            .map(|i| Message::user(format!("{}{}", i, " w".repeat(19))))
+            .collect();
+
+        // The heuristic puts each message at 12 tokens, so all three fit in 45
+        let heuristic = MCPClient::new(NoTransport).with_context_strategy(TruncationStrategy, 45);
+        assert_eq!(heuristic.fit_to_context(messages.clone()).await?.len(), 3);
+
+        // Counting the 20 words of each message leaves room for only two
+        let counted = MCPClient::new(NoTransport)
+            .with_context_strategy(TruncationStrategy, 45)
+            .with_token_counter(WordCounter);
+        let fitted = counted.fit_to_context(messages).await?;
+        let kept: Vec<&str> = fitted.iter().map(|msg| &msg.content.as_text().unwrap()[..1]).collect();
+        assert_eq!(kept, ["1", "2"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_per_call_timeout_overrides_client_timeout() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_response, MockClient};
+        use std::time::Duration;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(chat_response("slow"))
+                    .set_delay(Duration::from_millis(300)),
+            )
+            .mount(mock.server())
+            .await;
+
+        let message = Message::user("Hello");
+        let request = ChatCompletionRequest::new("mock/model", vec![message]);
+        let client = mock.client()?;
+
+        let result = client
+            .chat()?
+            .chat_completion_with_timeout(request.clone(), Duration::from_millis(50))
+            .await;
+        assert!(matches!(result, Err(crate::error::Error::HttpError(e)) if e.is_timeout()));
+
+        let impatient = client.clone().with_timeout(Duration::from_millis(50));
+        let result = impatient.chat()?.chat_completion(request.clone()).await;
+        assert!(matches!(result, Err(crate::error::Error::HttpError(e)) if e.is_timeout()));
+
+        // The original client keeps its default timeout
+        let response = client.chat()?.chat_completion(request).await?;
+        assert_eq!(response.choices[0].message.content, "slow");
+        Ok(())
+    }
+
+    #[test]
+    fn test_choice_exposes_per_choice_error() -> Result<(), Box<dyn std::error::Error>> {
+        let response: ChatCompletionResponse = serde_json::from_value(json!({
+            "id": "gen-1",
+            "choices": [
+                {
+                    "message": {"role": "assistant", "content": "Hello"},
+                    "finish_reason": "stop",
+                    "native_finish_reason": "stop"
+                },
+                {
+                    "message": {"role": "assistant", "content": ""},
+                    "finish_reason": "error",
+                    "native_finish_reason": null,
+                    "error": {"code": 502, "message": "Provider returned error"}
+                }
+            ],
+            "created": 0,
+            "model": "mock/model"
+        }))?;
+
+        assert!(response.choices[0].error.is_none());
+        let error = response.choices[1].error.as_ref().expect("second choice failed");
+        assert_eq!(error.code.as_deref(), Some("502"));
+        assert_eq!(error.message.as_deref(), Some("Provider returned error"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_provider_preferences_merge_lets_overrides_win() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::provider::ProviderPreferences;
+
+        let defaults = ProviderPreferences::new()
+            .with_order(["OpenAI", "Anthropic"])
+            .with_data_collection("deny")
+            .with_provider_option("OpenAI", json!({"a": 1}))
+            .with_provider_option("Anthropic", json!({"b": 1}));
+        let overrides = ProviderPreferences::new()
+            .with_order(["Anthropic"])
+            .with_allow_fallbacks(false)
+            .with_provider_option("OpenAI", json!({"a": 2}));
+
+        let merged = defaults.merge(overrides);
+        assert_eq!(
+            serde_json::to_value(&merged)?,
+            json!({
+                "order": ["Anthropic"],
+                "allow_fallbacks": false,
+                "data_collection": "deny",
+                "provider_options": {"OpenAI": {"a": 2}, "Anthropic": {"b": 1}}
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_provider_preferences_reject_conflicting_order_and_ignore() {
+        let valid = ProviderPreferences::new()
+            .with_order(["OpenAI", "Anthropic"])
+            .with_ignore(["Together"]);
+        assert!(valid.validate().is_ok());
+
+        let conflicting = ProviderPreferences::new()
+            .with_order(["OpenAI", "Anthropic"])
+            .with_ignore(["Anthropic"]);
+        match conflicting.validate() {
+            Err(crate::error::Error::ConfigError(message)) => assert!(message.contains("Anthropic")),
+            other => panic!("expected a ConfigError, got {:?}", other),
+        }
+
+        let empty_ignore = ProviderPreferences::new().with_ignore(Vec::<String>::new());
+        assert!(matches!(empty_ignore.validate(), Err(crate::error::Error::ConfigError(_))));
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_blocking_client_completes_chat_synchronously() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+
+        let runtime = tokio::runtime::Runtime::new()?;
+        let mock = runtime.block_on(async {
+            let mock = MockClient::start().await;
+            mock.mock_chat_content("Hi there").await;
+            mock
+        });
+
+        let message = Message::user("Hello");
+        let request = ChatCompletionRequest::new("mock/model", vec![message]);
+
+        let client = mock.client()?.blocking()?;
+        let response = client.chat_completion(request.clone())?;
+        assert_eq!(response.choices[0].message.content, "Hi there");
+
+        // Inside an async runtime the call fails instead of panicking
+        let result = runtime.block_on(async { client.chat_completion(request) });
+        assert!(matches!(result, Err(crate::error::Error::ConfigError(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_response_bytes_rejects_oversized_bodies() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_chunk, MockClient, MOCK_API_KEY};
+        use futures::StreamExt;
+
+        let message = Message::user("Hello");
+        let request = ChatCompletionRequest::new("mock/model", vec![message]);
+        let huge = "x".repeat(4096);
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content(&huge).await;
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_max_response_bytes(1024)
+            .with_api_key(MOCK_API_KEY)?;
+        let result = client.chat()?.chat_completion(request.clone()).await;
+        assert!(matches!(result, Err(crate::error::Error::ResponseTooLarge { limit: 1024 })));
+
+        // Small responses are unaffected
+        let small = MockClient::start().await;
+        small.mock_chat_content("Hi").await;
+        let client = OpenRouterClient::new()
+            .with_base_url(small.base_url())?
+            .with_max_response_bytes(1024)
+            .with_api_key(MOCK_API_KEY)?;
+        assert_eq!(client.chat()?.chat_completion(request.clone()).await?.choices[0].message.content, "Hi");
+
+        // A stream fails on the first line longer than the limit
+        let streaming = MockClient::start().await;
+        streaming.mock_chat_stream(&[chat_chunk("Hi"), chat_chunk(&huge)]).await;
+        let client = OpenRouterClient::new()
+            .with_base_url(streaming.base_url())?
+            .with_max_response_bytes(1024)
+            .with_api_key(MOCK_API_KEY)?;
+        let results: Vec<_> = client.chat()?.chat_completion_stream(request).collect().await;
+        assert!(results[0].is_ok());
+        assert!(matches!(results.last(), Some(Err(crate::error::Error::ResponseTooLarge { limit: 1024 }))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_reports_truncation_and_stop_sequence() -> Result<(), Box<dyn std::error::Error>> {
+        let response = |choices: Value| -> serde_json::Result<ChatCompletionResponse> {
+            serde_json::from_value(json!({
+                "id": "gen-1",
+                "choices": choices,
+                "created": 0,
+                "model": "mock/model"
+            }))
+        };
+        let choice = |finish_reason: &str, stop_reason: Value| {
+            json!({
+                "message": {"role": "assistant", "content": "Hello"},
+                "finish_reason": finish_reason,
+                "native_finish_reason": finish_reason,
+                "stop_reason": stop_reason
+            })
+        };
+
+        let stopped = response(json!([choice("stop", json!("\n\n"))]))?;
+        assert!(!stopped.was_truncated());
+        assert_eq!(stopped.stopped_at().as_deref(), Some("\n\n"));
+
+        let truncated = response(json!([choice("stop", Value::Null), choice("length", Value::Null)]))?;
+        assert!(truncated.was_truncated());
+        assert_eq!(truncated.stopped_at(), None);
+
+        // Token IDs are not stop sequences
+        let by_token = response(json!([choice("stop", json!(50256))]))?;
+        assert_eq!(by_token.stopped_at(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tool_messages_round_trip_and_require_tool_call_id() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::utils::validation::validate_chat_request;
+
+        let result = Message::tool_result("call_1", json!({"temperature": 21}))?;
+        let value = serde_json::to_value(&result)?;
+        assert_eq!(
+            value,
+            json!({"role": "tool", "content": "{\"temperature\":21}", "tool_call_id": "call_1"})
+        );
+        let parsed: Message = serde_json::from_value(value)?;
+        assert_eq!(parsed.tool_call_id.as_deref(), Some("call_1"));
+
+        let user = Message::user("Weather?");
+        let unanswered = Message { tool_call_id: None, ..result.clone() };
+        let misplaced = Message { tool_call_id: Some("call_1".to_string()), ..user.clone() };
+
+        assert!(validate_chat_request(&ChatCompletionRequest::new("openai/gpt-4o", vec![user.clone(), result])).is_ok());
+        for message in [unanswered, misplaced] {
+            let request = ChatCompletionRequest::new("openai/gpt-4o", vec![user.clone(), message]);
+            assert!(matches!(validate_chat_request(&request), Err(crate::error::Error::ConfigError(_))));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_continue_conversation_appends_both_turns() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("Paris").await;
+        let chat = mock.client()?.chat()?;
+
+        let mut history = Vec::new();
+        let reply = chat
+            .continue_conversation(&mut history, "Capital of France?", "mock/model")
+            .await?;
+        assert_eq!(reply.content, "Paris");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].role, "user");
+        assert_eq!(history[1].role, "assistant");
+
+        // The whole history is sent with the next turn
+        chat.continue_conversation(&mut history, "And of Italy?", "mock/model").await?;
+        let body = &mock.received_bodies().await[1];
+        assert_eq!(body["messages"].as_array().map(Vec::len), Some(3));
+        assert_eq!(history.len(), 4);
+
+        // A failed turn leaves the history untouched
+        mock.mock_chat_error(400, json!({"error": {"message": "bad request"}}), 1).await;
+        assert!(chat.continue_conversation(&mut history, "And Spain?", "mock/model").await.is_err());
+        assert_eq!(history.len(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reserved_completion_tokens_must_fit_context() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{MockClient, MOCK_API_KEY};
+        use crate::types::models::ModelInfo;
+        use crate::utils::validation::validate_completion_budget;
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+        let model: ModelInfo = serde_json::from_value(json!({
+            "id": "mock/model",
+            "provider": "mock",
+            "context_length": 1000
+        }))?;
+        assert!(validate_completion_budget(&request, &model, None).is_ok());
+        assert!(validate_completion_budget(&request.clone().with_max_tokens(900), &model, None).is_ok());
+        match validate_completion_budget(&request.clone().with_max_tokens(100), &model, Some(995)) {
+            Err(crate::error::Error::ContextLengthExceeded { model, message }) => {
+                assert_eq!(model, "mock/model");
+                assert!(message.contains("tokens short"), "{}", message);
+            }
+            other => panic!("expected ContextLengthExceeded, got {:?}", other),
+        }
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("ok").await;
+        mock.mock_models(json!({"models": [{"id": "mock/model", "provider": "mock", "context_length": 1000}]}))
+            .await;
+        for (reserve, fits) in [(995, false), (900, true)] {
+            let client = OpenRouterClient::new()
+                .with_base_url(mock.base_url())?
+                .with_reserved_completion_tokens(reserve)
+                .with_api_key(MOCK_API_KEY)?;
+            client.models()?.list_models_cached(None).await?;
+            let result = client.chat()?.chat_completion(request.clone()).await;
+            assert_eq!(result.is_ok(), fits, "reserve {}: {:?}", reserve, result.err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_chat_response_tolerates_provider_quirks() -> Result<(), Box<dyn std::error::Error>> {
+        let string_created: ChatCompletionResponse = serde_json::from_value(json!({
+            "id": "gen-1",
+            "created": "1700000000",
+            "model": "mock/model",
+            "choices": [{
+                "message": {"role": "assistant", "content": null, "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "lookup", "arguments": "{}"}
+                }]},
+                "finish_reason": "tool_calls"
+            }],
+            "system_fingerprint": "fp_123"
+        }))?;
+        assert_eq!(string_created.created, Some(1_700_000_000));
+        assert_eq!(string_created.choices[0].message.content, "");
+        assert_eq!(string_created.tool_calls().map(<[_]>::len), Some(1));
+
+        let sparse: ChatCompletionResponse = serde_json::from_value(json!({
+            "choices": [{"message": {"role": "assistant", "content": "Hi"}}]
+        }))?;
+        assert_eq!((sparse.id.as_str(), sparse.model.as_str(), sparse.created), ("", "", None));
+        assert_eq!(sparse.content(), Some("Hi"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_summarizing_processor_chunks_long_histories() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::ContextProcessor;
+        use crate::mcp::context::SummarizingProcessor;
+        use crate::test_util::MockClient;
+        use std::sync::Arc;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("partial").await;
+        let client = Arc::new(mock.client()?);
+
+        // Six messages of about 27 estimated tokens each; two fit a 60-token chunk
+        let history: Vec<Message> = (0..6)
+            .map(|i| Message::new(if i % 2 == 0 { "user" } else { "assistant" }, format!("turn {} {}", i, "x".repeat(92))))
+            .collect();
+
+        let processor = SummarizingProcessor::new(client.clone(), "mock/model").with_chunk_budget(60);
+        let summary = processor.summarize(history.clone()).await?;
+        assert_eq!(summary.content, "Previous conversation summary: partial");
+
+        // Three chunk summaries, then one call combining them
+        let bodies = mock.received_bodies().await;
+        assert_eq!(bodies.len(), 4);
+        for (body, turns) in bodies[..3].iter().zip([["turn 0", "turn 1"], ["turn 2", "turn 3"], ["turn 4", "turn 5"]]) {
+            let transcript = body["messages"][1]["content"].as_str().unwrap_or_default();
+            assert!(turns.iter().all(|turn| transcript.contains(turn)), "{}", transcript);
+        }
+        assert!(bodies[3]["messages"][0]["content"].as_str().unwrap_or_default().contains("partial summaries"));
+
+        // Without a budget or cached context length the history goes out in one prompt
+        SummarizingProcessor::new(client, "mock/model").summarize(history).await?;
+        assert_eq!(mock.received_bodies().await.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_app_attribution_falls_back_to_environment() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::client::{ClientConfig, HTTP_REFERER_ENV, SITE_TITLE_ENV};
+        use crate::test_util::MOCK_API_KEY;
+
+        // A stand-in for the process environment, which other tests read concurrently
+        let environment = |name: &str| match name {
+            HTTP_REFERER_ENV => Some("https://env.example.com".to_string()),
+            SITE_TITLE_ENV => Some("Env App".to_string()),
+            _ => None,
+        };
+        let no_auth = || OpenRouterClient::new().with_base_url("https://openrouter.ai/api/v1/");
+        let attribution = |mut config: ClientConfig| {
+            config.fill_app_attribution_from(environment);
+            (config.http_referer, config.site_title)
+        };
+
+        assert_eq!(
+            attribution(OpenRouterClient::new().config),
+            (Some("https://env.example.com".to_string()), Some("Env App".to_string()))
+        );
+        assert_eq!(
+            attribution(no_auth()?.with_app_attribution("https://app.example.com", "My App").config),
+            (Some("https://app.example.com".to_string()), Some("My App".to_string()))
+        );
+        assert_eq!(
+            attribution(no_auth()?.with_site_title("Partial App").config),
+            (Some("https://env.example.com".to_string()), Some("Partial App".to_string()))
+        );
+
+        // Blank values count as unset
+        let mut config = OpenRouterClient::new().config;
+        config.fill_app_attribution_from(|_| Some("  ".to_string()));
+        assert_eq!((config.http_referer, config.site_title), (None, None));
+
+        let explicit = no_auth()?
+            .with_app_attribution("https://app.example.com", "My App")
+            .with_api_key(MOCK_API_KEY)?;
+        let headers = explicit.config.build_headers()?;
+        assert_eq!(headers["Referer"], "https://app.example.com");
+        assert_eq!(headers["X-Title"], "My App");
+        Ok(())
+    }
+
+    #[test]
+    fn test_models_selected_by_capability() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::models::{ModelCapability, ModelsResponse};
+
+        let catalog: ModelsResponse = serde_json::from_value(json!({
+            "models": [
+                {"id": "a/chat", "provider": "a", "capabilities": ["chat"]},
+                {"id": "b/tools", "provider": "b", "capabilities": ["chat", "tool"]},
+                {"id": "c/vision", "provider": "c", "capabilities": ["chat", "tool", "vision"]},
+                {"id": "d/unknown", "provider": "d"}
+            ]
+        }))?;
+        let ids = |models: Vec<&crate::types::models::ModelInfo>| {
+            models.into_iter().map(|model| model.id.clone()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(catalog.with_capability(ModelCapability::Tool)), ["b/tools", "c/vision"]);
+        assert!(catalog.with_capability(ModelCapability::Embedding).is_empty());
+
+        // The first preference that supports the capability wins, ignoring suffixes
+        let pick = |capability, prefer: &[&str]| catalog.first_supporting(capability, prefer).map(|m| m.id.as_str());
+        assert_eq!(pick(ModelCapability::Tool, &["a/chat", "c/vision:nitro", "b/tools"]), Some("c/vision"));
+        // Without a supporting preference, the catalog order decides
+        assert_eq!(pick(ModelCapability::Vision, &["a/chat"]), Some("c/vision"));
+        assert_eq!(pick(ModelCapability::Embedding, &["a/chat"]), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_structured_output_rejected_for_unsupporting_models() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::models::structured::JsonSchemaConfig;
+        use crate::test_util::MockClient;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content(r#"{"answer": "42"}"#).await;
+        mock.mock_models(json!({"models": [
+            {"id": "plain/model", "provider": "plain", "supported_parameters": ["temperature", "max_tokens"]},
+            {"id": "json/model", "provider": "json", "supported_parameters": ["temperature", "structured_outputs"]}
+        ]}))
+        .await;
+        let client = mock.client()?;
+        client.models()?.list_models_cached(None).await?;
+
+        let config = || -> Result<JsonSchemaConfig, serde_json::Error> {
+            Ok(JsonSchemaConfig {
+                name: "answer".to_string(),
+                strict: true,
+                schema: serde_json::from_value(json!({
+                    "type": "object",
+                    "properties": {"answer": {"type": "string"}},
+                    "required": ["answer"]
+                }))?,
+            })
+        };
+        let structured = client.structured()?;
+
+        let rejected = structured.generate::<Value>("plain/model", vec![], config()?).await;
+        assert!(matches!(rejected, Err(crate::error::Error::StructuredOutputNotSupported)));
+        let requests = mock.server().received_requests().await.unwrap_or_default();
+        assert!(requests.iter().all(|request| request.url.path() == "/models"));
+
+        // Supporting and uncached models are sent as before
+        assert_eq!(structured.generate::<Value>("json/model", vec![], config()?).await?["answer"], "42");
+        assert_eq!(structured.generate::<Value>("other/model", vec![], config()?).await?["answer"], "42");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timed_chat_completions_report_latency() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_chunk, chat_response, MockClient};
+        use std::time::Duration;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hi")]);
+
+        let mock = MockClient::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(chat_response("Hello"))
+                    .set_delay(Duration::from_millis(50)),
+            )
+            .mount(mock.server())
+            .await;
+        let (response, elapsed) = mock.client()?.chat()?.chat_completion_timed(request.clone()).await?;
+        assert_eq!(response.choices[0].message.content, "Hello");
+        assert!(elapsed >= Duration::from_millis(50));
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_stream(&[chat_chunk(""), chat_chunk("Hel"), chat_chunk("lo")]).await;
+        let (response, timing) = mock.client()?.chat()?.chat_completion_stream_timed(request).await?;
+        assert_eq!(response.choices[0].message.content, "Hello");
+        let first_token = timing.time_to_first_token.expect("content was streamed");
+        assert!(first_token <= timing.total);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_ignores_stray_stream_flag() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::MockClient;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_content("Hello").await;
+
+        let mut request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hi")]);
+        request.stream = Some(true);
+        let response = mock.client()?.chat()?.chat_completion(request).await?;
+
+        assert_eq!(response.choices[0].message.content, "Hello");
+        assert_eq!(mock.received_bodies().await[0]["stream"], json!(false));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_quantization_deserializes() {
+        use crate::models::provider_preferences::Quantization;
+
+        let preferences: ProviderPreferences =
+            serde_json::from_value(json!({"quantizations": ["fp8", "fp4"]})).unwrap();
+        let quantizations = preferences.quantizations.unwrap();
+        assert!(matches!(quantizations[..], [Quantization::Fp8, Quantization::Unknown]));
+    }
+
+    #[test]
+    fn test_request_serializes_cached_content_parts() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::types::chat::{ContentPart, MessageContent};
+
+        let request = ChatCompletionRequest::new(
+            "anthropic/claude-3.5-sonnet",
+            vec![
+                Message::system(vec![
+                    ContentPart::text("You answer questions about the attached manual."),
+                    ContentPart::text("A very long manual").cacheable(),
+                ]),
+                Message::user("How do I reset it?"),
+            ],
+        );
+
+        let value = serde_json::to_value(&request)?;
+        assert_eq!(
+            value["messages"],
+            json!([
+                {
+                    "role": "system",
+                    "content": [
+                        {"type": "text", "text": "You answer questions about the attached manual."},
+                        {"type": "text", "text": "A very long manual", "cache_control": {"type": "ephemeral"}}
+                    ]
+                },
+                {"role": "user", "content": "How do I reset it?"}
+            ])
+        );
+
+        let parsed: Message = serde_json::from_value(value["messages"][0].clone())?;
+        assert!(matches!(parsed.content, MessageContent::Parts(ref parts) if parts.len() == 2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_reports_malformed_frames() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_chunk, MockClient};
+        use futures::StreamExt;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_stream(&[chat_chunk("Hel"), json!({"choices": "garbled"}), chat_chunk("lo")]).await;
+
+        let chat = mock.client()?.chat()?;
+        let mut stream = chat.chat_completion_stream(ChatCompletionRequest::new("mock/model", vec![Message::user("Hi")]));
+        assert_eq!(stream.next().await.expect("first chunk")?.choices[0].delta.content, "Hel");
+        match stream.next().await {
+            Some(Err(crate::error::Error::DeserializationError { status, body, .. })) => {
+                assert_eq!(status, 200);
+                assert!(body.contains("garbled"));
+            }
+            other => panic!("Expected a deserialization error, got {:?}", other),
+        }
+        assert!(stream.next().await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_and_non_stream_fit_context_the_same_way() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::strategy::SlidingWindowStrategy;
+        use crate::test_util::{chat_chunk, MockClient, MOCK_API_KEY};
+        use futures::StreamExt;
+
+        let plain = MockClient::start().await;
+        plain.mock_chat_content("ok").await;
+        let streamed = MockClient::start().await;
+        streamed.mock_chat_stream(&[chat_chunk("ok")]).await;
+
+        let request = ChatCompletionRequest::new(
+            "openai/gpt-4o",
+            ["user", "assistant", "user", "assistant"]
+                .iter()
+                .enumerate()
+                .map(|(i, role)| Message::new(*role, format!("message {}", i)))
+                .collect(),
+        );
+        let client = |base_url: String| {
+            OpenRouterClient::new()
+                .with_base_url(base_url)?
+                .with_default_system_prompt("Be brief.")
+                .with_context_strategy(SlidingWindowStrategy::new(2, false), 1_000)
+                .with_api_key(MOCK_API_KEY)
+        };
+
+        client(plain.base_url())?.chat()?.chat_completion(request.clone()).await?;
+        let mut chunks = client(streamed.base_url())?.chat()?.chat_completion_stream(request);
+        while let Some(chunk) = chunks.next().await {
+            chunk?;
+        }
+
+        // The system prompt is added after fitting, so the window cannot push it out.
+        let sent = plain.received_bodies().await[0]["messages"].clone();
+        assert_eq!(sent[0]["content"], "Be brief.");
+        assert_eq!(sent.as_array().map(Vec::len), Some(3));
+        assert_eq!(streamed.received_bodies().await[0]["messages"], sent);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_strategy_estimate_budgets_unless_a_counter_is_set() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::mcp::client::{ContextStrategy, MCPClient};
+        use crate::mcp::transport::Transport;
+        use crate::utils::validation::TokenCounter;
+        use async_trait::async_trait;
+
+        struct NoTransport;
+
+        #[async_trait]
+        impl Transport for NoTransport {
+            async fn send(&self, _message: Value) -> crate::error::Result<()> {
+                Ok(())
+            }
+
+            async fn receive(&self) -> crate::error::Result<Value> {
+                Err(crate::error::Error::TransportError("nothing to receive".into()))
+            }
+        }
+
+        /// Drops the oldest messages while `counter` puts them over budget
+        struct DropOldest;
+
+        #[async_trait]
+        impl ContextStrategy for DropOldest {
+            async fn fit_to_context(&self, messages: Vec<Message>, _max_tokens: usize) -> crate::error::Result<Vec<Message>> {
+                Ok(messages)
+            }
+
+            async fn fit_to_context_with_counter(
+                &self,
+                mut messages: Vec<Message>,
+                max_tokens: usize,
+                counter: &dyn TokenCounter,
+            ) -> crate::error::Result<Vec<Message>> {
+                while counter.count_tokens(&messages).await? > max_tokens {
+                    messages.remove(0);
+                }
+                Ok(messages)
+            }
+
+            async fn compress(&self, messages: Vec<Message>) -> crate::error::Result<Vec<Message>> {
+                Ok(messages)
+            }
+
+            fn estimate_token_count(&self, messages: &[Message]) -> usize {
+                messages.len() * 10
+            }
+        }
+
+        struct OneTokenPerMessage;
+
+        #[async_trait]
+        impl TokenCounter for OneTokenPerMessage {
+            async fn count_message_tokens(&self, _message: &Message) -> crate::error::Result<usize> {
+                Ok(1)
+            }
+        }
+
+        let messages = vec![Message::user("hi"); 3];
+
+        // Ten tokens a message by the strategy's own estimate leaves room for two
+        let estimated = MCPClient::new(NoTransport).with_context_strategy(DropOldest, 25);
+        assert_eq!(estimated.fit_to_context(messages.clone()).await?.len(), 2);
+
+        // A configured counter takes precedence over the estimate
+        let counted = MCPClient::new(NoTransport)
+            .with_context_strategy(DropOldest, 25)
+            .with_token_counter(OneTokenPerMessage);
+        assert_eq!(counted.fit_to_context(messages).await?.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_decode_to_the_same_variant_everywhere() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::error::Error;
+        use crate::test_util::MockClient;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, ResponseTemplate};
+
+        let body = json!({"error": {"code": 429, "message": "slow down"}});
+        let mock = MockClient::start().await;
+        Mock::given(method("GET"))
+            .and(path("/models"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(body.clone()))
+            .mount(mock.server())
+            .await;
+
+        match mock.client()?.models()?.list_models(None).await {
+            Err(error @ Error::RateLimitExceeded { .. }) => {
+                assert_eq!(error.api_error_details().and_then(|d| d.message).as_deref(), Some("slow down"));
+            }
+            other => panic!("Expected RateLimitExceeded, got {:?}", other),
+        }
+        assert!(matches!(
+            Error::from_status(429, "busy".to_string()),
+            Error::RateLimitExceeded { metadata: None, .. }
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_key_rotation_covers_every_endpoint() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{chat_chunk, MockClient};
+        use futures::StreamExt;
+        use wiremock::matchers::header;
+        use wiremock::{Mock, ResponseTemplate};
+
+        let mock = MockClient::start().await;
+        mock.mock_models(json!({"models": [{"id": "mock/model", "provider": "mock"}]})).await;
+        mock.mock_chat_stream(&[chat_chunk("streamed")]).await;
+        Mock::given(header("Authorization", "Bearer sk-or-key-one-xxxx"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "error": {"code": 401, "message": "key rejected"}
+            })))
+            .with_priority(1)
+            .mount(mock.server())
+            .await;
+
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_api_keys(vec!["sk-or-key-one-xxxx".to_string(), "sk-or-key-two-xxxx".to_string()])?;
+        let models = client.models()?.list_models(None).await?;
+        assert_eq!(models.models[0].id, "mock/model");
+        assert_eq!(client.active_api_key_index(), Some(1));
+
+        // Streams rotate too: a rejected first key moves on to the next one.
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_api_keys(vec!["sk-or-key-one-xxxx".to_string(), "sk-or-key-two-xxxx".to_string()])?;
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("hi")]);
+        let chunks: Vec<_> = client.chat()?.chat_completion_stream(request).collect().await;
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].as_ref().map(|c| c.choices[0].delta.content.clone()).ok().as_deref(), Some("streamed"));
+
+        let requests = mock.server().received_requests().await.unwrap();
+        let paths: Vec<_> = requests.iter().map(|r| r.url.path().to_string()).collect();
+        assert_eq!(paths, ["/models", "/models", "/chat/completions", "/chat/completions"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_definition_keeps_every_root_keyword() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::models::structured::{AdditionalProperties, JsonSchemaDefinition};
+        use crate::utils::validation::validate_against_schema;
+
+        let written = json!({
+            "type": "object",
+            "description": "Word counts",
+            "properties": {"total": {"type": "integer"}},
+            "additionalProperties": {"type": "integer"},
+            "anyOf": [{"required": ["total"]}]
+        });
+        let definition: JsonSchemaDefinition = serde_json::from_value(written.clone())?;
+        assert_eq!(
+            definition.additional_properties,
+            Some(AdditionalProperties::Schema(Box::new(json!({"type": "integer"}))))
+        );
+        assert_eq!(definition.extra["description"], "Word counts");
+
+        // The schema sent is the one written, and the root additionalProperties is enforced
+        let sent = serde_json::to_value(&definition)?;
+        assert_eq!(sent, written);
+        assert!(validate_against_schema(&sent, &json!({"total": 2, "the": 1})).is_ok());
+        assert!(validate_against_schema(&sent, &json!({"total": 2, "the": "one"})).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_validation_enforces_pattern() {
+        use crate::utils::validation::validate_against_schema;
+
+        let schema = json!({
+            "type": "object",
+            "properties": {"sku": {"type": "string", "pattern": "^[A-Z]{3}-\\d{4}$"}}
+        });
+        assert!(validate_against_schema(&schema, &json!({"sku": "ABC-1234"})).is_ok());
+        match validate_against_schema(&schema, &json!({"sku": "abc-12"})) {
+            Err(crate::error::Error::SchemaValidationError(msg)) => {
+                assert!(msg.starts_with("$.sku: String does not match pattern"), "{}", msg)
+            }
+            other => panic!("Expected a SchemaValidationError, got {:?}", other),
+        }
+
+        // Unanchored patterns match anywhere, and a broken pattern is reported
+        let loose = json!({"type": "string", "pattern": "\\d"});
+        assert!(validate_against_schema(&loose, &json!("room 7")).is_ok());
+        assert!(validate_against_schema(&json!({"type": "string", "pattern": "("}), &json!("x")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_covers_streams() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::models::structured::JsonSchemaConfig;
+        use crate::test_util::{MockClient, MOCK_API_KEY};
+        use futures::StreamExt;
+        use std::time::Duration;
+
+        let mock = MockClient::start().await;
+        mock.mock_chat_error(503, json!({"error": {"message": "provider down"}}), 2).await;
+
+        let client = OpenRouterClient::new()
+            .with_base_url(mock.base_url())?
+            .with_retry_config(RetryConfig {
+                max_retries: 0,
+                ..RetryConfig::default()
+            })
+            .with_circuit_breaker(2, Duration::from_secs(60))
+            .with_api_key(MOCK_API_KEY)?;
+        let request = ChatCompletionRequest::new("mock/model", vec![Message::user("Hello")]);
+
+        // Failed streams count towards opening the circuit.
+        for _ in 0..2 {
+            let chunks: Vec<_> = client.chat()?.chat_completion_stream(request.clone()).collect().await;
+            assert!(matches!(chunks[..], [Err(crate::error::Error::ApiError { code: 503, .. })]));
+        }
+
+        // Once open, chat and structured streams fail fast without reaching the server.
+        let chunks: Vec<_> = client.chat()?.chat_completion_stream(request.clone()).collect().await;
+        assert!(matches!(chunks[..], [Err(crate::error::Error::ModelNotAvailable(_))]));
+        let schema: JsonSchemaConfig = serde_json::from_value(json!({
+            "name": "answer",
+            "strict": true,
+            "schema": {"type": "object", "properties": {}}
+        }))?;
+        let events: Vec<_> = client
+            .structured()?
+            .generate_stream::<Value>("mock/model", request.messages.clone(), schema.clone())
+            .collect()
+            .await;
+        assert!(matches!(events[..], [Err(crate::error::Error::ModelNotAvailable(_))]));
+        let generated = client.structured()?.generate::<Value>("mock/model", request.messages, schema).await;
+        assert!(matches!(generated, Err(crate::error::Error::ModelNotAvailable(_))));
+        assert_eq!(mock.received_bodies().await.len(), 2);
+        Ok(())
+    }
 }
@@ -1,5 +1,7 @@
 use crate::models::tool::ToolCall;
+use crate::types::plugin::Plugin;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
 /// Defines the role of a chat message (user, assistant, or system).
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,7 +16,10 @@ pub enum ChatRole {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
-    pub content: String,
+    /// Message text or content parts; a `null` or missing content, as sent alongside tool
+    /// calls, parses as empty text.
+    #[serde(default, deserialize_with = "deserialize_nullable_content")]
+    pub content: MessageContent,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -22,6 +27,305 @@ pub struct Message {
     // Optionally include tool_calls when the assistant message contains a tool call.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
+    /// Reasoning text returned separately from `content` by reasoning models.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+}
+
+impl Message {
+    /// A message with the given role and content and no other fields set.
+    pub fn new(role: impl Into<String>, content: impl Into<MessageContent>) -> Self {
+        Self {
+            role: role.into(),
+            content: content.into(),
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+            reasoning: None,
+        }
+    }
+
+    /// A `system` message.
+    pub fn system(content: impl Into<MessageContent>) -> Self {
+        Self::new("system", content)
+    }
+
+    /// A `user` message.
+    pub fn user(content: impl Into<MessageContent>) -> Self {
+        Self::new("user", content)
+    }
+
+    /// An `assistant` message.
+    pub fn assistant(content: impl Into<MessageContent>) -> Self {
+        Self::new("assistant", content)
+    }
+
+    /// A `tool` message answering the tool call `tool_call_id` with `result`.
+    ///
+    /// The result is serialized to JSON for the message content, except that a string
+    /// result is used as is rather than quoted.
+    pub fn tool_result(
+        tool_call_id: impl Into<String>,
+        result: impl Serialize,
+    ) -> crate::error::Result<Self> {
+        let content = match serde_json::to_value(result).map_err(crate::error::Error::SerializationError)? {
+            serde_json::Value::String(text) => text,
+            value => value.to_string(),
+        };
+        Ok(Self {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new("tool", content)
+        })
+    }
+}
+
+/// The content of a message: plain text, or an array of parts such as text with a cache
+/// directive or images.
+///
+/// Text serializes as a plain JSON string, as before parts were supported.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    /// The content if it is plain text.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            MessageContent::Text(text) => Some(text),
+            MessageContent::Parts(_) => None,
+        }
+    }
+
+    /// The text, with the text parts of multipart content joined by newlines.
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            MessageContent::Text(text) => Cow::Borrowed(text),
+            MessageContent::Parts(parts) => Cow::Owned(
+                parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text { text, .. } => Some(text.as_str()),
+                        ContentPart::ImageUrl { .. } => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        }
+    }
+
+    /// Like [`text`](Self::text), consuming the content.
+    pub fn into_text(self) -> String {
+        match self {
+            MessageContent::Text(text) => text,
+            parts => parts.text().into_owned(),
+        }
+    }
+
+    /// Whether there is no text and no parts.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            MessageContent::Text(text) => text.is_empty(),
+            MessageContent::Parts(parts) => parts.is_empty(),
+        }
+    }
+
+    /// Appends `text`, to the last part if it is text or as a new text part otherwise.
+    pub fn push_str(&mut self, text: &str) {
+        match self {
+            MessageContent::Text(content) => content.push_str(text),
+            MessageContent::Parts(parts) => match parts.last_mut() {
+                Some(ContentPart::Text { text: last, .. }) => last.push_str(text),
+                _ => parts.push(ContentPart::text(text)),
+            },
+        }
+    }
+}
+
+impl Default for MessageContent {
+    fn default() -> Self {
+        MessageContent::Text(String::new())
+    }
+}
+
+impl std::fmt::Display for MessageContent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text())
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        MessageContent::Text(text.to_string())
+    }
+}
+
+impl From<Vec<ContentPart>> for MessageContent {
+    fn from(parts: Vec<ContentPart>) -> Self {
+        MessageContent::Parts(parts)
+    }
+}
+
+impl PartialEq<str> for MessageContent {
+    fn eq(&self, other: &str) -> bool {
+        self.as_text() == Some(other)
+    }
+}
+
+impl PartialEq<&str> for MessageContent {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_text() == Some(*other)
+    }
+}
+
+impl PartialEq<String> for MessageContent {
+    fn eq(&self, other: &String) -> bool {
+        self.as_text() == Some(other.as_str())
+    }
+}
+
+/// Reasoning effort level for models that support it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+/// Reasoning configuration for a chat completion request.
+///
+/// Set either `effort` or `max_tokens`; `exclude` keeps the reasoning out of the response
+/// while still letting the model reason.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReasoningConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effort: Option<ReasoningEffort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<bool>,
+}
+
+impl ReasoningConfig {
+    /// Reason with the given effort level.
+    pub fn with_effort(effort: ReasoningEffort) -> Self {
+        Self {
+            effort: Some(effort),
+            ..Self::default()
+        }
+    }
+
+    /// Reason with a budget of `max_tokens` reasoning tokens.
+    pub fn with_max_tokens(max_tokens: u32) -> Self {
+        Self {
+            max_tokens: Some(max_tokens),
+            ..Self::default()
+        }
+    }
+
+    /// Omit the reasoning from the response.
+    pub fn excluded(mut self) -> Self {
+        self.exclude = Some(true);
+        self
+    }
+}
+
+/// Prompt-caching directive attached to a content part.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CacheControl {
+    /// Cache type; providers currently accept `"ephemeral"`.
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl CacheControl {
+    /// An ephemeral cache breakpoint: the prefix up to and including this part is cached.
+    pub fn ephemeral() -> Self {
+        Self {
+            kind: "ephemeral".to_string(),
+        }
+    }
+}
+
+/// Image reference used by an image content part.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ImageUrl {
+    /// An http(s) URL or a base64 `data:` URL.
+    pub url: String,
+    /// Optional detail level (`"auto"`, `"low"` or `"high"`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// One part of a multipart message content array.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    ImageUrl {
+        image_url: ImageUrl,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+}
+
+impl ContentPart {
+    /// A text part.
+    pub fn text(text: impl Into<String>) -> Self {
+        ContentPart::Text {
+            text: text.into(),
+            cache_control: None,
+        }
+    }
+
+    /// An image part referencing `url`.
+    pub fn image_url(url: impl Into<String>) -> Self {
+        ContentPart::ImageUrl {
+            image_url: ImageUrl {
+                url: url.into(),
+                detail: None,
+            },
+            cache_control: None,
+        }
+    }
+
+    /// Sets the part's cache directive.
+    pub fn with_cache_control(mut self, control: CacheControl) -> Self {
+        match &mut self {
+            ContentPart::Text { cache_control, .. } | ContentPart::ImageUrl { cache_control, .. } => {
+                *cache_control = Some(control)
+            }
+        }
+        self
+    }
+
+    /// Marks the part as an ephemeral cache breakpoint, e.g. at the end of a long
+    /// system prompt or document.
+    pub fn cacheable(self) -> Self {
+        self.with_cache_control(CacheControl::ephemeral())
+    }
+
+    /// The part's cache directive, if any.
+    pub fn cache_control(&self) -> Option<&CacheControl> {
+        match self {
+            ContentPart::Text { cache_control, .. } | ContentPart::ImageUrl { cache_control, .. } => {
+                cache_control.as_ref()
+            }
+        }
+    }
 }
 
 /// Chat completion request matching the OpenRouter API schema.
@@ -40,15 +344,153 @@ pub struct ChatCompletionRequest {
     /// (Optional) Tool calling field. Now uses our production‑ready tool types.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<crate::models::tool::Tool>>,
-    /// (Optional) Stub for provider preferences.
+    /// (Optional) Provider routing preferences. The client's
+    /// `RouterConfig::provider_preferences` fill in any settings left unset.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub provider: Option<String>,
+    pub provider: Option<crate::types::provider::ProviderPreferences>,
     /// (Optional) Fallback models.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub models: Option<Vec<String>>,
     /// (Optional) Message transforms.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transforms: Option<Vec<String>>,
+    /// (Optional) Reasoning configuration for reasoning models.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<ReasoningConfig>,
+    /// (Optional) Plugins such as web search or file parsing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<Vec<Plugin>>,
+    /// (Optional) Number of candidate completions to generate; must be at least 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    /// (Optional) End-user identifier for abuse monitoring. Unlike the client's
+    /// `X-User-ID` header, this is sent in the request body.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// (Optional) Predicted output, letting the model skip regenerating unchanged text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prediction: Option<Prediction>,
+    /// (Optional) Upper bound on generated tokens. When unset, the client's default
+    /// (if any) is applied.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// (Optional) Routing behavior across the request's `models`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<Route>,
+}
+
+/// How OpenRouter routes a request with several candidate models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Route {
+    /// Try the primary model first, falling back to the entries of `models` in order when
+    /// it is unavailable or errors.
+    Fallback,
+}
+
+/// A predicted output for edit-style requests, serialized as
+/// `{"type": "content", "content": ...}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "content")]
+pub struct Prediction {
+    /// The text the response is expected to largely match.
+    pub content: String,
+}
+
+impl Prediction {
+    /// A prediction that the response will largely match `content`.
+    pub fn new(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+        }
+    }
+}
+
+impl ChatCompletionRequest {
+    /// Creates a request for `model` with the given messages and no optional settings.
+    pub fn new(model: impl Into<String>, messages: Vec<Message>) -> Self {
+        Self {
+            model: model.into(),
+            messages,
+            stream: None,
+            response_format: None,
+            tools: None,
+            provider: None,
+            models: None,
+            transforms: None,
+            reasoning: None,
+            plugins: None,
+            n: None,
+            user: None,
+            prediction: None,
+            max_tokens: None,
+            route: None,
+        }
+    }
+
+    /// Checks that the request's model and any fallback `models` exist in `catalog`,
+    /// returning `Error::ModelNotAvailable` naming the unknown ones.
+    pub fn validate_models_against(
+        &self,
+        catalog: &crate::types::models::ModelsResponse,
+    ) -> crate::error::Result<()> {
+        let fallbacks = self.models.iter().flatten().map(String::as_str);
+        catalog.ensure_available(std::iter::once(self.model.as_str()).chain(fallbacks))
+    }
+
+    /// Adds a plugin to the request.
+    pub fn with_plugin(mut self, plugin: Plugin) -> Self {
+        self.plugins.get_or_insert_with(Vec::new).push(plugin);
+        self
+    }
+
+    /// Sets provider routing preferences for this request. They take precedence over the
+    /// client's, which still supply any settings left unset here.
+    pub fn with_provider(mut self, preferences: crate::types::provider::ProviderPreferences) -> Self {
+        self.provider = Some(preferences);
+        self
+    }
+
+    /// Caps the number of tokens the model may generate.
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Sets the routing behavior, e.g. [`Route::Fallback`] to fall back through `models`.
+    pub fn with_route(mut self, route: Route) -> Self {
+        self.route = Some(route);
+        self
+    }
+
+    /// Requests `n` candidate completions, returned as separate choices.
+    pub fn with_n(mut self, n: u32) -> Self {
+        self.n = Some(n);
+        self
+    }
+
+    /// Sets the end-user identifier sent in the request body.
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Sends `content` as the predicted output, e.g. the current text of a document being
+    /// lightly edited.
+    pub fn with_prediction(mut self, content: impl Into<String>) -> Self {
+        self.prediction = Some(Prediction::new(content));
+        self
+    }
+
+    /// Augments the request with live web search results using the `web` plugin.
+    pub fn with_web_search(self) -> Self {
+        self.with_plugin(Plugin::web())
+    }
+
+    /// The request body exactly as it is sent, pretty-printed for debugging.
+    pub fn to_pretty_json(&self) -> crate::error::Result<String> {
+        serde_json::to_string_pretty(self).map_err(crate::error::Error::SerializationError)
+    }
 }
 
 /// A choice returned by the chat API.
@@ -58,10 +500,18 @@ pub struct Choice {
     pub finish_reason: Option<String>,
     #[serde(rename = "native_finish_reason")]
     pub native_finish_reason: Option<String>,
+    /// Why this choice failed, when a provider reports a per-choice error in an otherwise
+    /// successful response, e.g. for one of several `n` candidates.
+    #[serde(default)]
+    pub error: Option<crate::error::ApiErrorDetails>,
+    /// What stopped generation, as reported by providers that echo it: the matched stop
+    /// sequence as a string, or a token ID.
+    #[serde(default)]
+    pub stop_reason: Option<serde_json::Value>,
 }
 
 /// Usage data returned from the API.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
@@ -69,18 +519,347 @@ pub struct Usage {
 }
 
 /// Chat completion response.
+///
+/// Parsing tolerates the quirks of some proxied providers: a missing `id` or `model`
+/// parses as empty, and `created` may be missing or sent as a string. `choices` is
+/// still required, so a body of some other shape is reported as undecodable.
 #[derive(Debug, Deserialize)]
 pub struct ChatCompletionResponse {
+    #[serde(default)]
     pub id: String,
     pub choices: Vec<Choice>,
-    pub created: i64,
+    /// Unix timestamp of when the completion was created, if the provider reports one.
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    pub created: Option<i64>,
+    #[serde(default)]
     pub model: String,
     pub usage: Option<Usage>,
 }
 
-/// A streaming chunk for chat completions (stub).
+impl ChatCompletionResponse {
+    /// The first choice, if any.
+    pub fn first_choice(&self) -> Option<&Choice> {
+        self.choices.first()
+    }
+
+    /// The content of the first choice's message, if any and if it is plain text.
+    pub fn content(&self) -> Option<&str> {
+        self.first_choice().and_then(|choice| choice.message.content.as_text())
+    }
+
+    /// The reasoning returned with the first choice's message, if any.
+    pub fn reasoning(&self) -> Option<&str> {
+        self.first_choice()
+            .and_then(|choice| choice.message.reasoning.as_deref())
+    }
+
+    /// Whether any choice was cut off by the token limit (finish reason `length`).
+    pub fn was_truncated(&self) -> bool {
+        self.choices
+            .iter()
+            .any(|choice| choice.finish_reason.as_deref() == Some("length"))
+    }
+
+    /// The stop sequence that ended the first choice, if the provider reports it.
+    pub fn stopped_at(&self) -> Option<String> {
+        self.first_choice()
+            .and_then(|choice| choice.stop_reason.as_ref())
+            .and_then(|reason| reason.as_str())
+            .map(str::to_string)
+    }
+
+    /// The tool calls requested in the first choice's message, if any.
+    pub fn tool_calls(&self) -> Option<&[ToolCall]> {
+        self.first_choice()
+            .and_then(|choice| choice.message.tool_calls.as_deref())
+    }
+}
+
+/// A streaming chunk for chat completions.
 #[derive(Debug, Deserialize)]
 pub struct ChatCompletionChunk {
     pub id: String,
-    pub choices: Vec<Choice>,
+    pub choices: Vec<ChunkChoice>,
+    /// Unix timestamp of when the completion was created, if the frame includes it.
+    #[serde(default, deserialize_with = "deserialize_timestamp")]
+    pub created: Option<i64>,
+    /// The model producing the output, if the frame includes it.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// One choice's progress in a streaming chunk.
+#[derive(Debug, Deserialize)]
+pub struct ChunkChoice {
+    /// The choice this chunk continues; missing indices fall back to the position in the
+    /// chunk.
+    #[serde(default)]
+    pub index: Option<usize>,
+    /// The new output. Streams carry it under `delta`; some proxies send `message` instead.
+    #[serde(alias = "message", default)]
+    pub delta: ChunkDelta,
+    pub finish_reason: Option<String>,
+    #[serde(default)]
+    pub native_finish_reason: Option<String>,
+    /// Why this choice failed, when a provider reports a per-choice error mid-stream.
+    #[serde(default)]
+    pub error: Option<crate::error::ApiErrorDetails>,
+    /// What stopped generation, as reported by providers that echo it.
+    #[serde(default)]
+    pub stop_reason: Option<serde_json::Value>,
+}
+
+/// The output added by one chunk to a choice.
+#[derive(Debug, Default, Deserialize)]
+pub struct ChunkDelta {
+    /// The role, usually only sent in the first chunk.
+    #[serde(default)]
+    pub role: Option<String>,
+    /// The next piece of content; `null` or missing parses as empty.
+    #[serde(default, deserialize_with = "deserialize_nullable_string")]
+    pub content: String,
+    /// The next piece of reasoning text.
+    #[serde(default)]
+    pub reasoning: Option<String>,
+    /// Fragments of tool calls, merged by their `index`.
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+/// A fragment of a streamed tool call.
+///
+/// The ID, type and function name usually arrive only in the first fragment of a call;
+/// the arguments arrive in pieces to be concatenated.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ToolCallDelta {
+    /// Position of the call among the choice's tool calls.
+    #[serde(default)]
+    pub index: Option<usize>,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(rename = "type", default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub function: Option<FunctionCallDelta>,
+}
+
+/// A fragment of a streamed function call.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FunctionCallDelta {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+/// A tool call still being streamed.
+#[derive(Debug)]
+struct PendingToolCall {
+    index: usize,
+    call: ToolCall,
+}
+
+/// A choice being assembled from chunks.
+#[derive(Debug)]
+struct ChoiceState {
+    index: usize,
+    choice: Choice,
+    /// The tool call still receiving fragments; earlier calls are complete and already
+    /// in `choice.message.tool_calls`.
+    pending: Option<PendingToolCall>,
+}
+
+impl ChoiceState {
+    /// Merges a tool call fragment, completing the pending call when a new one starts.
+    fn push_tool_call(&mut self, delta: ToolCallDelta) {
+        let index = delta.index.unwrap_or_else(|| match (&self.pending, &delta.id) {
+            (Some(pending), Some(id)) if !pending.call.id.is_empty() && *id != pending.call.id => {
+                pending.index + 1
+            }
+            (Some(pending), _) => pending.index,
+            (None, _) => self.choice.message.tool_calls.as_ref().map_or(0, Vec::len),
+        });
+        if self.pending.as_ref().is_some_and(|pending| pending.index != index) {
+            self.complete_tool_call();
+        }
+
+        let pending = self.pending.get_or_insert_with(|| PendingToolCall {
+            index,
+            call: ToolCall {
+                id: String::new(),
+                kind: "function".to_string(),
+                function_call: crate::models::tool::FunctionCall {
+                    name: String::new(),
+                    arguments: String::new(),
+                },
+            },
+        });
+        if let Some(id) = delta.id.filter(|id| !id.is_empty()) {
+            pending.call.id = id;
+        }
+        if let Some(kind) = delta.kind.filter(|kind| !kind.is_empty()) {
+            pending.call.kind = kind;
+        }
+        if let Some(function) = delta.function {
+            if let Some(name) = function.name.filter(|name| !name.is_empty()) {
+                pending.call.function_call.name = name;
+            }
+            if let Some(arguments) = function.arguments {
+                pending.call.function_call.arguments.push_str(&arguments);
+            }
+        }
+    }
+
+    /// Moves the pending tool call, if any, to the completed calls.
+    fn complete_tool_call(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.choice
+                .message
+                .tool_calls
+                .get_or_insert_with(Vec::new)
+                .push(pending.call);
+        }
+    }
+}
+
+/// Assembles the chunks of a streamed chat completion into a complete response.
+///
+/// Choices are matched by their `index`. Content and reasoning are concatenated per
+/// choice, tool call fragments are merged by their `index` with the arguments
+/// concatenated, and the last finish reason reported wins.
+#[derive(Debug, Default)]
+pub struct ChatStreamAccumulator {
+    id: String,
+    choices: Vec<ChoiceState>,
+    created: Option<i64>,
+    model: Option<String>,
+}
+
+impl ChatStreamAccumulator {
+    /// Creates an empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a chunk to the response.
+    pub fn push(&mut self, chunk: ChatCompletionChunk) {
+        if self.id.is_empty() {
+            self.id = chunk.id;
+        }
+        self.created = self.created.or(chunk.created);
+        self.model = self.model.take().or(chunk.model);
+        for (position, delta) in chunk.choices.into_iter().enumerate() {
+            let index = delta.index.unwrap_or(position);
+            let state = match self.choices.iter().position(|state| state.index == index) {
+                Some(existing) => &mut self.choices[existing],
+                None => {
+                    self.choices.push(ChoiceState {
+                        index,
+                        choice: Choice {
+                            message: Message::assistant(""),
+                            finish_reason: None,
+                            native_finish_reason: None,
+                            error: None,
+                            stop_reason: None,
+                        },
+                        pending: None,
+                    });
+                    self.choices.last_mut().expect("just pushed")
+                }
+            };
+
+            if let Some(role) = delta.delta.role.filter(|role| !role.is_empty()) {
+                state.choice.message.role = role;
+            }
+            state.choice.message.content.push_str(&delta.delta.content);
+            if let Some(reasoning) = delta.delta.reasoning {
+                state
+                    .choice
+                    .message
+                    .reasoning
+                    .get_or_insert_with(String::new)
+                    .push_str(&reasoning);
+            }
+            for tool_call in delta.delta.tool_calls.into_iter().flatten() {
+                state.push_tool_call(tool_call);
+            }
+            if delta.finish_reason.is_some() {
+                state.complete_tool_call();
+                state.choice.finish_reason = delta.finish_reason;
+            }
+            if delta.native_finish_reason.is_some() {
+                state.choice.native_finish_reason = delta.native_finish_reason;
+            }
+            if delta.error.is_some() {
+                state.choice.error = delta.error;
+            }
+            if delta.stop_reason.is_some() {
+                state.choice.stop_reason = delta.stop_reason;
+            }
+        }
+    }
+
+    /// The tool calls of the choice with `index` that have been fully received so far.
+    ///
+    /// A call is complete once the next call starts, the choice finishes or the stream
+    /// ends, since its arguments may arrive over several chunks.
+    pub fn completed_tool_calls(&self, index: usize) -> &[ToolCall] {
+        self.choices
+            .iter()
+            .find(|state| state.index == index)
+            .and_then(|state| state.choice.message.tool_calls.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// The assembled response.
+    ///
+    /// `created` and `model` come from the first chunks reporting them; otherwise `created`
+    /// is `None` and the model is `model`. Chunks carry no usage, so `usage` is `None`.
+    pub fn finish(mut self, model: impl Into<String>) -> ChatCompletionResponse {
+        self.choices.sort_by_key(|state| state.index);
+        ChatCompletionResponse {
+            id: self.id,
+            choices: self
+                .choices
+                .into_iter()
+                .map(|mut state| {
+                    state.complete_tool_call();
+                    state.choice
+                })
+                .collect(),
+            created: self.created,
+            model: self.model.unwrap_or_else(|| model.into()),
+            usage: None,
+        }
+    }
+}
+
+/// Deserializes a string that may be `null`, treating `null` as empty.
+fn deserialize_nullable_string<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Deserializes message content that may be `null`, treating `null` as empty text.
+fn deserialize_nullable_content<'de, D>(deserializer: D) -> std::result::Result<MessageContent, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<MessageContent>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Deserializes a Unix timestamp given as a number or a numeric string.
+///
+/// Values that are neither parse as `None` rather than failing the whole response.
+fn deserialize_timestamp<'de, D>(deserializer: D) -> std::result::Result<Option<i64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::Number(n)) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)),
+        Some(serde_json::Value::String(s)) => s.trim().parse().ok(),
+        _ => None,
+    })
 }
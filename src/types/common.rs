@@ -19,3 +19,20 @@ pub struct Choice {
     pub message: Message,
     pub finish_reason: Option<String>,
 }
+
+impl From<Message> for crate::types::chat::Message {
+    fn from(message: Message) -> Self {
+        Self::new(message.role, message.content)
+    }
+}
+
+/// Lossy: the name, tool fields and reasoning are dropped, and multipart content keeps
+/// only its text.
+impl From<crate::types::chat::Message> for Message {
+    fn from(message: crate::types::chat::Message) -> Self {
+        Self {
+            role: message.role,
+            content: message.content.into_text(),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::types::chat::Usage;
 use serde::{Deserialize, Serialize};
 
 /// Represents a text completion request. It minimally contains:
@@ -10,10 +11,31 @@ use serde::{Deserialize, Serialize};
 pub struct CompletionRequest {
     pub model: String,
     pub prompt: String,
+    /// Identifier of the end user, used by OpenRouter for abuse monitoring.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
     #[serde(flatten)]
     pub extra_params: serde_json::Value,
 }
 
+impl CompletionRequest {
+    /// Creates a request completing `prompt` with `model` and no extra parameters.
+    pub fn new(model: impl Into<String>, prompt: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            prompt: prompt.into(),
+            user: None,
+            extra_params: serde_json::json!({}),
+        }
+    }
+
+    /// Sets the end-user identifier sent in the request body.
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+}
+
 /// Represents a choice returned by the completions endpoint.
 #[derive(Debug, Deserialize)]
 pub struct CompletionChoice {
@@ -26,8 +48,12 @@ pub struct CompletionChoice {
 /// Represents the text completion response. It includes:
 /// - an optional `id` for the request
 /// - a list of choices with the completed text
+/// - the model, creation time and token usage, when the provider reports them
 #[derive(Debug, Deserialize)]
 pub struct CompletionResponse {
     pub id: Option<String>,
     pub choices: Vec<CompletionChoice>,
+    pub model: Option<String>,
+    pub created: Option<i64>,
+    pub usage: Option<Usage>,
 }
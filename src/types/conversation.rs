@@ -0,0 +1,155 @@
+//! A persistable conversation: the message history plus application metadata.
+
+use crate::error::{Error, Result};
+use crate::models::tool::ToolCall;
+use crate::types::chat::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A conversation that can be saved and restored across process restarts.
+///
+/// Fitting a conversation with a context strategy replaces its messages with the fitted
+/// context, so summaries produced by the strategy are persisted along with it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conversation {
+    /// The messages in the conversation, oldest first.
+    pub messages: Vec<Message>,
+    /// Application-defined metadata (e.g. a title, user id or timestamps).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, Value>,
+}
+
+impl Conversation {
+    /// Creates a conversation from existing messages.
+    pub fn new(messages: Vec<Message>) -> Self {
+        Self {
+            messages,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Appends a message to the conversation.
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// Sets a metadata entry.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Serializes the conversation to a JSON string.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).map_err(Error::SerializationError)
+    }
+
+    /// Restores a conversation from a JSON string produced by [`Conversation::to_json`].
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(Error::SerializationError)
+    }
+}
+
+/// Fluent builder for a `Vec<Message>`.
+///
+/// Ordering mistakes are recorded as they happen and reported by [`ConversationBuilder::build`]:
+/// a tool message must follow an assistant tool-call message (or another tool result for
+/// it) and answer one of its calls, each call at most once.
+///
+/// ```
+/// use openrouter_api::types::ConversationBuilder;
+///
+/// let messages = ConversationBuilder::new()
+///     .system("You are a helpful assistant.")
+///     .user("Hello!")
+///     .build()
+///     .unwrap();
+/// assert_eq!(messages.len(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConversationBuilder {
+    messages: Vec<Message>,
+    /// Tool call IDs from the latest assistant tool-call message not yet answered.
+    pending_tool_calls: Vec<String>,
+    error: Option<String>,
+}
+
+impl ConversationBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a system message.
+    pub fn system(self, text: impl Into<String>) -> Self {
+        self.push("system", text.into(), None, None)
+    }
+
+    /// Appends a user message.
+    pub fn user(self, text: impl Into<String>) -> Self {
+        self.push("user", text.into(), None, None)
+    }
+
+    /// Appends an assistant message.
+    pub fn assistant(self, text: impl Into<String>) -> Self {
+        self.push("assistant", text.into(), None, None)
+    }
+
+    /// Appends an assistant message requesting `tool_calls`, to be answered with [`Self::tool`].
+    pub fn assistant_tool_calls(mut self, tool_calls: Vec<ToolCall>) -> Self {
+        if tool_calls.is_empty() {
+            self.fail("assistant_tool_calls requires at least one tool call".to_string());
+            return self;
+        }
+        let ids = tool_calls.iter().map(|call| call.id.clone()).collect();
+        self = self.push("assistant", String::new(), None, Some(tool_calls));
+        self.pending_tool_calls = ids;
+        self
+    }
+
+    /// Appends the result of the tool call `tool_call_id`.
+    pub fn tool(mut self, tool_call_id: impl Into<String>, result: impl Into<String>) -> Self {
+        let tool_call_id = tool_call_id.into();
+        let Some(position) = self.pending_tool_calls.iter().position(|id| *id == tool_call_id) else {
+            self.fail(format!(
+                "Tool result for '{}' does not answer a pending assistant tool call",
+                tool_call_id
+            ));
+            return self;
+        };
+        self.pending_tool_calls.remove(position);
+        self.push("tool", result.into(), Some(tool_call_id), None)
+    }
+
+    /// Returns the messages, or `Error::ConfigError` describing the first ordering mistake.
+    pub fn build(self) -> Result<Vec<Message>> {
+        match self.error {
+            Some(error) => Err(Error::ConfigError(error)),
+            None => Ok(self.messages),
+        }
+    }
+
+    fn push(
+        mut self,
+        role: &str,
+        content: String,
+        tool_call_id: Option<String>,
+        tool_calls: Option<Vec<ToolCall>>,
+    ) -> Self {
+        // Any message other than a tool result closes the previous tool-call turn.
+        if role != "tool" {
+            self.pending_tool_calls.clear();
+        }
+        self.messages.push(Message {
+            tool_call_id,
+            tool_calls,
+            ..Message::new(role, content)
+        });
+        self
+    }
+
+    fn fail(&mut self, error: String) {
+        self.error.get_or_insert(error);
+    }
+}
@@ -1,7 +1,10 @@
 pub mod chat;
 pub mod common;
 pub mod completion;
+pub mod conversation;
 pub mod models;
+pub mod moderation;
+pub mod plugin;
 pub mod provider;
 pub mod routing;
 pub mod transform;
@@ -10,7 +13,10 @@ pub mod web_search;
 // Re-export common types
 pub use chat::*;
 pub use completion::*;
+pub use conversation::*;
 pub use models::*;
+pub use moderation::*;
+pub use plugin::*;
 pub use provider::*;
 pub use routing::*;
 pub use transform::*;
@@ -1,8 +1,11 @@
-use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 /// A model capability, such as "completion" or "chat".
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ModelCapability {
     Chat,
@@ -30,6 +33,190 @@ pub enum ModelFormatting {
     Other,
 }
 
+/// A model identifier of the form `provider/model`, optionally with a routing suffix
+/// such as `:online` or `:free`.
+///
+/// Converts into a `String`, so it can be passed anywhere a model id is accepted.
+///
+/// ```
+/// use openrouter_api::types::models::ModelId;
+///
+/// let id = ModelId::parse("openai/gpt-4o").unwrap().online();
+/// assert_eq!(id.to_string(), "openai/gpt-4o:online");
+/// assert_eq!(id.provider(), Some("openai"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ModelId {
+    provider: Option<String>,
+    model: String,
+    suffix: Option<String>,
+}
+
+impl ModelId {
+    /// Parses a model id, splitting off the provider prefix and any `:suffix`.
+    pub fn parse(id: &str) -> Result<Self> {
+        let id = id.trim();
+        let (base, suffix) = match id.rsplit_once(':') {
+            Some((base, suffix)) => (base, Some(suffix)),
+            None => (id, None),
+        };
+        let (provider, model) = match base.split_once('/') {
+            Some((provider, model)) => (Some(provider), model),
+            None => (None, base),
+        };
+
+        if model.is_empty()
+            || provider.is_some_and(str::is_empty)
+            || suffix.is_some_and(str::is_empty)
+        {
+            return Err(Error::ConfigError(format!("Invalid model id: '{}'", id)));
+        }
+
+        Ok(Self {
+            provider: provider.map(str::to_string),
+            model: model.to_string(),
+            suffix: suffix.map(str::to_string),
+        })
+    }
+
+    /// The provider prefix, e.g. `openai` in `openai/gpt-4o`.
+    pub fn provider(&self) -> Option<&str> {
+        self.provider.as_deref()
+    }
+
+    /// The model name without provider or suffix.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// The routing suffix without the leading colon, if any.
+    pub fn suffix(&self) -> Option<&str> {
+        self.suffix.as_deref()
+    }
+
+    /// The id without its routing suffix.
+    pub fn base(&self) -> Self {
+        Self {
+            suffix: None,
+            ..self.clone()
+        }
+    }
+
+    /// Replaces the routing suffix.
+    pub fn with_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = Some(suffix.into());
+        self
+    }
+
+    /// Adds web search results to every request (`:online`).
+    pub fn online(self) -> Self {
+        self.with_suffix("online")
+    }
+
+    /// Routes to the fastest providers (`:nitro`).
+    pub fn nitro(self) -> Self {
+        self.with_suffix("nitro")
+    }
+
+    /// Routes to the cheapest providers (`:floor`).
+    pub fn floor(self) -> Self {
+        self.with_suffix("floor")
+    }
+
+    /// Uses the free variant of the model (`:free`).
+    pub fn free(self) -> Self {
+        self.with_suffix("free")
+    }
+}
+
+impl fmt::Display for ModelId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(provider) = &self.provider {
+            write!(f, "{}/", provider)?;
+        }
+        write!(f, "{}", self.model)?;
+        if let Some(suffix) = &self.suffix {
+            write!(f, ":{}", suffix)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ModelId {
+    type Err = Error;
+
+    fn from_str(id: &str) -> Result<Self> {
+        Self::parse(id)
+    }
+}
+
+impl From<ModelId> for String {
+    fn from(id: ModelId) -> Self {
+        id.to_string()
+    }
+}
+
+impl From<&ModelId> for String {
+    fn from(id: &ModelId) -> Self {
+        id.to_string()
+    }
+}
+
+/// Prices in USD as reported by the `/models` endpoint.
+///
+/// OpenRouter sends rates as decimal strings; numbers are accepted too.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pricing {
+    /// Price per prompt token.
+    #[serde(default, deserialize_with = "deserialize_price", skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<f64>,
+
+    /// Price per completion token.
+    #[serde(default, deserialize_with = "deserialize_price", skip_serializing_if = "Option::is_none")]
+    pub completion: Option<f64>,
+
+    /// Price per input image.
+    #[serde(default, deserialize_with = "deserialize_price", skip_serializing_if = "Option::is_none")]
+    pub image: Option<f64>,
+
+    /// Fixed price per request.
+    #[serde(default, deserialize_with = "deserialize_price", skip_serializing_if = "Option::is_none")]
+    pub request: Option<f64>,
+}
+
+/// Input and output modalities of a model, from its `architecture` entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Architecture {
+    /// Summary such as `"text+image->text"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modality: Option<String>,
+
+    /// Accepted input modalities, e.g. `["text", "image"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub input_modalities: Vec<String>,
+
+    /// Produced output modalities.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_modalities: Vec<String>,
+}
+
+/// Deserializes a price given either as a number or as a numeric string.
+fn deserialize_price<'de, D>(deserializer: D) -> std::result::Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<serde_json::Value>::deserialize(deserializer)? {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::Number(n)) => Ok(n.as_f64()),
+        Some(serde_json::Value::String(s)) => s
+            .trim()
+            .parse::<f64>()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("invalid price: '{}'", s))),
+        Some(other) => Err(serde::de::Error::custom(format!("invalid price: {}", other))),
+    }
+}
+
 /// Information about a specific model.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelInfo {
@@ -59,25 +246,198 @@ pub struct ModelInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context_length: Option<u32>,
     
+    /// Prices for this model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
+    
+    /// Input and output modalities of this model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<Architecture>,
+    
     /// Additional model-specific metadata.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<HashMap<String, serde_json::Value>>,
+    
+    /// Request parameters supported by at least one provider of this model.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub supported_parameters: Vec<String>,
+}
+
+impl ModelInfo {
+    /// Whether the model accepts a `json_schema` response format, if the catalog says.
+    ///
+    /// `None` when the model lists no supported parameters at all.
+    pub fn supports_structured_output(&self) -> Option<bool> {
+        if self.supported_parameters.is_empty() {
+            return None;
+        }
+        Some(
+            self.supported_parameters
+                .iter()
+                .any(|param| param == "structured_outputs" || param == "response_format"),
+        )
+    }
+
+    /// Estimated cost in USD of a request with the given token counts, including any
+    /// fixed per-request price.
+    ///
+    /// Rates missing from the model's pricing count as free.
+    pub fn estimate_cost(&self, prompt_tokens: u32, completion_tokens: u32) -> f64 {
+        let pricing = self.pricing.clone().unwrap_or_default();
+        pricing.prompt.unwrap_or(0.0) * f64::from(prompt_tokens)
+            + pricing.completion.unwrap_or(0.0) * f64::from(completion_tokens)
+            + pricing.request.unwrap_or(0.0)
+    }
+
+    /// Whether the model lists `capability` among its capabilities.
+    pub fn supports(&self, capability: ModelCapability) -> bool {
+        self.capabilities.iter().flatten().any(|c| *c == capability)
+    }
+
+    /// Whether the model accepts `modality` (e.g. `"image"`) as input.
+    ///
+    /// Falls back to the `modality` summary when `input_modalities` is not reported.
+    pub fn accepts_input(&self, modality: &str) -> bool {
+        let Some(architecture) = &self.architecture else {
+            return false;
+        };
+
+        if !architecture.input_modalities.is_empty() {
+            return architecture.input_modalities.iter().any(|m| m == modality);
+        }
+
+        architecture
+            .modality
+            .as_deref()
+            .and_then(|summary| summary.split("->").next())
+            .is_some_and(|inputs| inputs.split('+').any(|m| m.trim() == modality))
+    }
+}
+
+/// A provider endpoint serving a model, from `models/{id}/endpoints`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderEndpoint {
+    /// The provider's display name, usable in provider preference lists.
+    pub provider_name: String,
+    
+    /// Endpoint name, typically "provider | model".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    
+    /// Maximum context length offered by this provider.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<u32>,
+    
+    /// Maximum number of completion tokens this provider returns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+    
+    /// Prices charged by this provider.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
+    
+    /// Weight quantization, e.g. "fp8".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantization: Option<String>,
+    
+    /// Request parameters this provider supports.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub supported_parameters: Vec<String>,
 }
 
 /// Request to list available models.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ModelsRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub capability: Option<ModelCapability>,
     
     #[serde(skip_serializing_if = "Option::is_none")]
     pub provider: Option<String>,
+
+    /// Maximum number of models to return in one page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+
+    /// Cursor from a previous response's `next_cursor`, to fetch the following page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+
+    /// Only keep models with at least this context length. Applied client-side.
+    #[serde(skip)]
+    pub min_context_length: Option<u32>,
+
+    /// Only keep models accepting this input modality, e.g. `"image"`. Applied client-side.
+    #[serde(skip)]
+    pub modality: Option<String>,
+}
+
+impl ModelsRequest {
+    /// Whether `model` passes the client-side filters. Models that don't report their
+    /// context length never pass a `min_context_length` filter.
+    pub fn matches(&self, model: &ModelInfo) -> bool {
+        let context_ok = self
+            .min_context_length
+            .is_none_or(|min| model.context_length.is_some_and(|length| length >= min));
+        let modality_ok = self
+            .modality
+            .as_deref()
+            .is_none_or(|modality| model.accepts_input(modality));
+        context_ok && modality_ok
+    }
 }
 
 /// Response containing available models.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ModelsResponse {
     /// A list of available models.
     pub models: Vec<ModelInfo>,
+    /// Cursor for the next page, when the listing is paginated and more models remain.
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+impl ModelsResponse {
+    /// Whether the catalog contains `id`, ignoring any routing suffix.
+    pub fn contains(&self, id: &str) -> bool {
+        let base = ModelId::parse(id)
+            .map(|id| id.base().to_string())
+            .unwrap_or_else(|_| id.to_string());
+        self.models.iter().any(|model| model.id == base)
+    }
+
+    /// The models listing `capability` among their capabilities, in catalog order.
+    pub fn with_capability(&self, capability: ModelCapability) -> Vec<&ModelInfo> {
+        self.models
+            .iter()
+            .filter(|model| model.supports(capability))
+            .collect()
+    }
+
+    /// The first model in `prefer` that supports `capability`, or else the first such
+    /// model in the catalog.
+    ///
+    /// Preferred ids may carry a routing suffix, which is ignored when matching.
+    pub fn first_supporting(&self, capability: ModelCapability, prefer: &[&str]) -> Option<&ModelInfo> {
+        let supporting = self.with_capability(capability);
+        prefer
+            .iter()
+            .find_map(|id| {
+                let base = ModelId::parse(id).ok()?.base().to_string();
+                supporting.iter().copied().find(|model| model.id == base)
+            })
+            .or_else(|| supporting.first().copied())
+    }
+
+    /// Checks that every id in `ids` is in the catalog, returning
+    /// [`Error::ModelNotAvailable`] naming the unknown ones otherwise.
+    pub fn ensure_available<'a>(&self, ids: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let unknown: Vec<&str> = ids.into_iter().filter(|id| !self.contains(id)).collect();
+
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ModelNotAvailable(unknown.join(", ")))
+        }
+    }
 }
 
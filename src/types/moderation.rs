@@ -0,0 +1,92 @@
+// File: openrouter_api/src/types/moderation.rs
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Text to screen, either a single string or a batch.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ModerationInput {
+    Text(String),
+    Batch(Vec<String>),
+}
+
+/// Request type for the moderation endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModerationRequest {
+    /// The text to classify.
+    pub input: ModerationInput,
+    /// Optionally specify the moderation model to use.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl ModerationRequest {
+    /// Screens a single piece of text with the default moderation model.
+    pub fn new(input: impl Into<String>) -> Self {
+        Self {
+            input: ModerationInput::Text(input.into()),
+            model: None,
+        }
+    }
+
+    /// Screens several pieces of text in one request; results come back in the same order.
+    pub fn batch(inputs: Vec<String>) -> Self {
+        Self {
+            input: ModerationInput::Batch(inputs),
+            model: None,
+        }
+    }
+
+    /// Selects the moderation model.
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+}
+
+/// Classification of a single input.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModerationResult {
+    /// Whether any category was flagged.
+    pub flagged: bool,
+    /// Per-category flags, e.g. "hate" or "self-harm".
+    #[serde(default)]
+    pub categories: HashMap<String, bool>,
+    /// Per-category confidence scores between 0 and 1.
+    #[serde(default)]
+    pub category_scores: HashMap<String, f64>,
+}
+
+impl ModerationResult {
+    /// Names of the flagged categories, sorted.
+    pub fn flagged_categories(&self) -> Vec<&str> {
+        let mut flagged: Vec<&str> = self
+            .categories
+            .iter()
+            .filter(|(_, flagged)| **flagged)
+            .map(|(category, _)| category.as_str())
+            .collect();
+        flagged.sort_unstable();
+        flagged
+    }
+}
+
+/// Response type returned by the moderation endpoint.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModerationResponse {
+    /// The moderation request ID.
+    #[serde(default)]
+    pub id: String,
+    /// The model used for classification.
+    #[serde(default)]
+    pub model: String,
+    /// One result per input.
+    pub results: Vec<ModerationResult>,
+}
+
+impl ModerationResponse {
+    /// Whether any input was flagged.
+    pub fn flagged(&self) -> bool {
+        self.results.iter().any(|result| result.flagged)
+    }
+}
@@ -0,0 +1,43 @@
+//! Plugins that augment a chat completion request on the OpenRouter side.
+
+use serde::{Deserialize, Serialize};
+
+/// A plugin entry in a chat request's `plugins` array, identified by its `id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "id")]
+pub enum Plugin {
+    /// Adds live web search results to the prompt.
+    #[serde(rename = "web")]
+    Web {
+        /// Maximum number of results to include.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_results: Option<u32>,
+        /// Prompt used to introduce the results to the model.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        search_prompt: Option<String>,
+    },
+    /// Parses attached files (such as PDFs) into text the model can read.
+    #[serde(rename = "file-parser")]
+    FileParser {
+        /// PDF parsing options.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pdf: Option<PdfParserOptions>,
+    },
+}
+
+impl Plugin {
+    /// The web search plugin with OpenRouter's defaults.
+    pub fn web() -> Self {
+        Plugin::Web {
+            max_results: None,
+            search_prompt: None,
+        }
+    }
+}
+
+/// Options for the file parser's PDF handling.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PdfParserOptions {
+    /// Parsing engine, e.g. `"pdf-text"`, `"mistral-ocr"` or `"native"`.
+    pub engine: String,
+}
@@ -1,6 +1,22 @@
+use crate::error::{Error, Result};
+use crate::models::provider_preferences::{provider_names, Provider};
 use serde::Serialize;
 use std::collections::HashMap;
 
+/// Accepted `data_collection` values.
+pub const DATA_COLLECTION_VALUES: &[&str] = &["allow", "deny"];
+
+/// Accepted `sort` values.
+pub const SORT_VALUES: &[&str] = &["price", "throughput", "latency"];
+
+/// Accepted `quantizations` entries.
+pub const QUANTIZATION_VALUES: &[&str] = &[
+    "int4", "int8", "fp4", "fp6", "fp8", "fp16", "bf16", "fp32", "unknown",
+];
+
+/// Accepted `route_optimizations` entries.
+pub const ROUTE_OPTIMIZATION_VALUES: &[&str] = &["price", "throughput", "latency"];
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ProviderPreferences {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -33,6 +49,12 @@ pub struct ProviderPreferences {
     pub route_optimizations: Option<Vec<String>>,
 }
 
+impl Default for ProviderPreferences {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Builder pattern for ProviderPreferences
 impl ProviderPreferences {
     pub fn new() -> Self {
@@ -49,8 +71,63 @@ impl ProviderPreferences {
         }
     }
     
-    pub fn with_order(mut self, order: Vec<String>) -> Self {
-        self.order = Some(order);
+    /// Checks the string-valued settings against the values OpenRouter accepts, so a
+    /// typo such as `"pricing"` for `"price"` is rejected instead of silently ignored.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(data_collection) = &self.data_collection {
+            check_allowed("data_collection", data_collection, DATA_COLLECTION_VALUES)?;
+        }
+
+        if let Some(sort) = &self.sort {
+            check_allowed("sort", sort, SORT_VALUES)?;
+        }
+
+        for quantization in self.quantizations.iter().flatten() {
+            check_allowed("quantizations", quantization, QUANTIZATION_VALUES)?;
+        }
+
+        for optimization in self.route_optimizations.iter().flatten() {
+            check_allowed("route_optimizations", optimization, ROUTE_OPTIMIZATION_VALUES)?;
+        }
+
+        Ok(())
+    }
+    
+    /// Fills every setting left unset here from `defaults`; settings already present win.
+    ///
+    /// Provider options are merged per provider. This is how a request's preferences are
+    /// combined with the client's, so a client-wide `data_collection: "deny"` still applies
+    /// to a request that only sets `sort`.
+    pub fn merged_with(mut self, defaults: &ProviderPreferences) -> Self {
+        self.order = self.order.or_else(|| defaults.order.clone());
+        self.allow_fallbacks = self.allow_fallbacks.or(defaults.allow_fallbacks);
+        self.require_parameters = self.require_parameters.or(defaults.require_parameters);
+        self.data_collection = self.data_collection.or_else(|| defaults.data_collection.clone());
+        self.ignore = self.ignore.or_else(|| defaults.ignore.clone());
+        self.quantizations = self.quantizations.or_else(|| defaults.quantizations.clone());
+        self.sort = self.sort.or_else(|| defaults.sort.clone());
+        self.route_optimizations = self
+            .route_optimizations
+            .or_else(|| defaults.route_optimizations.clone());
+        if let Some(default_options) = &defaults.provider_options {
+            let options = self.provider_options.get_or_insert_with(HashMap::new);
+            for (provider, value) in default_options {
+                options.entry(provider.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        self
+    }
+
+    /// Overlays every setting present in `overrides` onto these preferences.
+    ///
+    /// The mirror image of [`merged_with`](Self::merged_with): `defaults.merge(overrides)`
+    /// gives the same result as `overrides.merged_with(&defaults)`.
+    pub fn merge(self, overrides: ProviderPreferences) -> Self {
+        overrides.merged_with(&self)
+    }
+
+    pub fn with_order<P: Into<Provider>>(mut self, order: impl IntoIterator<Item = P>) -> Self {
+        self.order = Some(provider_names(order));
         self
     }
     
@@ -69,8 +146,8 @@ impl ProviderPreferences {
         self
     }
     
-    pub fn with_ignored_providers(mut self, ignore: Vec<String>) -> Self {
-        self.ignore = Some(ignore);
+    pub fn with_ignored_providers<P: Into<Provider>>(mut self, ignore: impl IntoIterator<Item = P>) -> Self {
+        self.ignore = Some(provider_names(ignore));
         self
     }
     
@@ -96,3 +173,17 @@ impl ProviderPreferences {
     }
 }
 
+
+/// Returns an error naming `field` unless `value` is one of `allowed`.
+fn check_allowed(field: &str, value: &str, allowed: &[&str]) -> Result<()> {
+    if allowed.contains(&value) {
+        return Ok(());
+    }
+
+    Err(Error::ConfigError(format!(
+        "Invalid {} value '{}'; expected one of: {}",
+        field,
+        value,
+        allowed.join(", ")
+    )))
+}
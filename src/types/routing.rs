@@ -1,3 +1,5 @@
+use crate::error::Result;
+use crate::types::models::ModelsResponse;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -29,8 +31,19 @@ pub struct ModelCoverageProfile {
     pub provider_options: Option<HashMap<String, serde_json::Value>>,
 }
 
+impl ModelCoverageProfile {
+    /// Checks that the primary model and every fallback exist in `catalog`.
+    ///
+    /// Returns `Error::ModelNotAvailable` naming each unknown id, so a typo is caught
+    /// before any request is sent.
+    pub fn validate_against(&self, catalog: &ModelsResponse) -> Result<()> {
+        let fallbacks = self.fallbacks.iter().flatten().map(String::as_str);
+        catalog.ensure_available(std::iter::once(self.primary.as_str()).chain(fallbacks))
+    }
+}
+
 /// Named profiles for common model coverage scenarios
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum PredefinedModelCoverageProfile {
     /// Optimizes for lowest latency across available models
@@ -47,7 +60,7 @@ pub enum PredefinedModelCoverageProfile {
 }
 
 /// Router configuration for model selection and fallback behavior
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RouterConfig {
     /// The model coverage profile to use
     pub profile: PredefinedModelCoverageProfile,
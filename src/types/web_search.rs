@@ -1,15 +1,98 @@
-//// File: openrouter_api/src/types/web_search.rs
+// File: openrouter_api/src/types/web_search.rs
 use serde::{Deserialize, Serialize};
 
 /// Request type for performing a web search.
-#[derive(Debug, Serialize)]
+///
+/// Only `query` is required. The filters `search_depth`, `include_domains`,
+/// `exclude_domains` and `recency` are sent only when set and forwarded
+/// unchanged, without any guarantee that the search backend applies them: one that does
+/// not support a filter ignores it rather than failing. Treat them as hints, and check the
+/// results (e.g. their URLs) when a filter matters.
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct WebSearchRequest {
     /// The search query string.
     pub query: String,
     /// Optionally specify the number of results to return.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub num_results: Option<u32>,
-    // Additional parameters can be added here in the future.
+    /// How thoroughly to search; `advanced` trades latency for better results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_depth: Option<SearchDepth>,
+    /// Only return results from these domains (e.g. `"arxiv.org"`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_domains: Option<Vec<String>>,
+    /// Never return results from these domains.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_domains: Option<Vec<String>>,
+    /// Only return results published within this window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recency: Option<SearchRecency>,
+}
+
+impl WebSearchRequest {
+    /// Creates a request for `query` with no filters.
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the number of results to return.
+    pub fn with_num_results(mut self, num_results: u32) -> Self {
+        self.num_results = Some(num_results);
+        self
+    }
+
+    /// Sets the search depth.
+    pub fn with_search_depth(mut self, depth: SearchDepth) -> Self {
+        self.search_depth = Some(depth);
+        self
+    }
+
+    /// Restricts results to `domains`.
+    pub fn with_include_domains<I, S>(mut self, domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Excludes results from `domains`.
+    pub fn with_exclude_domains<I, S>(mut self, domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Restricts results to those published within `recency`.
+    pub fn with_recency(mut self, recency: SearchRecency) -> Self {
+        self.recency = Some(recency);
+        self
+    }
+}
+
+/// How thoroughly a web search is performed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchDepth {
+    Basic,
+    Advanced,
+}
+
+/// How recent web search results must be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchRecency {
+    Day,
+    Week,
+    Month,
+    Year,
 }
 
 /// A single search result.
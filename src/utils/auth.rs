@@ -2,6 +2,13 @@
 
 use crate::error::{Error, Result};
 use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub mod pkce;
 
 /// Attempts to load an API key from environment variables.
 /// Checks for OPENROUTER_API_KEY and OR_API_KEY.
@@ -24,6 +31,76 @@ pub fn load_api_key_from_env() -> Result<String> {
     ))
 }
 
+/// Attempts to load an API key from a `.env`-style file.
+/// Checks for OPENROUTER_API_KEY and OR_API_KEY, in that order.
+pub fn load_api_key_from_dotenv(path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).map_err(|e| {
+        Error::MissingCredential(format!("Could not read {}: {}", path.display(), e))
+    })?;
+    
+    let vars = parse_dotenv(&contents);
+    for name in ["OPENROUTER_API_KEY", "OR_API_KEY"] {
+        if let Some((_, key)) = vars.iter().find(|(var, value)| *var == name && !value.is_empty()) {
+            return Ok(key.clone());
+        }
+    }
+    
+    Err(Error::MissingCredential(format!(
+        "API key not found in {} (expected OPENROUTER_API_KEY or OR_API_KEY)",
+        path.display()
+    )))
+}
+
+/// Attempts to load an API key from a file containing only the key, such as a Docker secret.
+/// Surrounding whitespace is trimmed.
+pub fn load_api_key_from_file(path: impl AsRef<Path>) -> Result<String> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).map_err(|e| {
+        Error::MissingCredential(format!("Could not read {}: {}", path.display(), e))
+    })?;
+    
+    let key = contents.trim();
+    if key.is_empty() {
+        return Err(Error::MissingCredential(format!("API key file {} is empty", path.display())));
+    }
+    
+    Ok(key.to_string())
+}
+
+/// Parses `KEY=value` assignments from `.env` contents.
+///
+/// Blank lines, `#` comments and an optional `export ` prefix are handled; values may be
+/// wrapped in single or double quotes, and unquoted values may carry a trailing ` # comment`.
+fn parse_dotenv(contents: &str) -> Vec<(&str, String)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=')?;
+            let value = value.trim();
+            
+            let quoted = ['"', '\''].iter().find_map(|&quote| {
+                value
+                    .strip_prefix(quote)
+                    .and_then(|rest| rest.split_once(quote))
+                    .map(|(inner, _)| inner)
+            });
+            let value = match quoted {
+                Some(inner) => inner,
+                None => value.split(" #").next().unwrap_or_default().trim(),
+            };
+            
+            Some((name.trim(), value.to_string()))
+        })
+        .collect()
+}
+
 /// Validates an API key format.
 /// Basic validation to check if the key is non-empty and has a reasonable length.
 pub fn validate_api_key(key: &str) -> Result<()> {
@@ -43,3 +120,69 @@ pub fn validate_api_key(key: &str) -> Result<()> {
     Ok(())
 }
 
+
+/// A list of API keys used in turn: requests use the active key until it fails to
+/// authenticate or runs out of credits, then move on to the next.
+///
+/// Clones share the active key, so every API created from a client rotates together.
+#[derive(Clone)]
+pub struct ApiKeyRing {
+    keys: Arc<Vec<String>>,
+    active: Arc<AtomicUsize>,
+}
+
+impl fmt::Debug for ApiKeyRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKeyRing")
+            .field("keys", &self.keys.len())
+            .field("active", &self.active_index())
+            .finish()
+    }
+}
+
+impl ApiKeyRing {
+    /// Creates a ring starting at the first key. Every key must pass `validate_api_key`.
+    pub fn new(keys: Vec<String>) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(Error::ConfigError("At least one API key is required".into()));
+        }
+        for key in &keys {
+            validate_api_key(key)?;
+        }
+        Ok(Self {
+            keys: Arc::new(keys),
+            active: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Index of the key currently in use.
+    pub fn active_index(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// The key currently in use.
+    pub fn active(&self) -> &str {
+        &self.keys[self.active_index()]
+    }
+
+    /// Number of keys in the ring.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the ring has no keys; never true for a ring built with `new`.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Moves past the key at `failed`, wrapping around to the first key.
+    ///
+    /// Does nothing if another request already rotated away from `failed`, so concurrent
+    /// failures of the same key only skip it once.
+    pub fn rotate_from(&self, failed: usize) {
+        let next = (failed + 1) % self.keys.len();
+        let _ = self
+            .active
+            .compare_exchange(failed, next, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
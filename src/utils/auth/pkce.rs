@@ -0,0 +1,111 @@
+//! OAuth PKCE flow for obtaining user-scoped OpenRouter API keys.
+//!
+//! 1. Call [`generate_pkce_challenge`] and keep the verifier.
+//! 2. Send the user to [`authorization_url`].
+//! 3. OpenRouter redirects to the callback URL with a `code` query parameter.
+//! 4. Trade the code and verifier for a key with [`exchange_code_for_key`].
+
+use crate::error::{Error, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+/// OpenRouter page where users authorize an application.
+const AUTH_URL: &str = "https://openrouter.ai/auth";
+
+/// Default API base URL used for the key exchange.
+const DEFAULT_BASE_URL: &str = "https://openrouter.ai/api/v1/";
+
+/// Length of generated code verifiers (RFC 7636 allows 43 to 128 characters).
+const VERIFIER_LENGTH: usize = 64;
+
+/// A PKCE code verifier and its S256 code challenge.
+#[derive(Debug, Clone)]
+pub struct PkceChallenge {
+    /// Secret kept by the application until the code exchange.
+    pub verifier: String,
+    /// Base64url-encoded SHA-256 hash of the verifier, sent in the authorization URL.
+    pub challenge: String,
+}
+
+impl PkceChallenge {
+    /// Derives the S256 challenge for an existing verifier.
+    pub fn from_verifier(verifier: impl Into<String>) -> Self {
+        let verifier = verifier.into();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        Self { verifier, challenge }
+    }
+}
+
+/// Generates a random code verifier and its S256 challenge.
+pub fn generate_pkce_challenge() -> PkceChallenge {
+    let verifier: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(VERIFIER_LENGTH)
+        .map(char::from)
+        .collect();
+    PkceChallenge::from_verifier(verifier)
+}
+
+/// Builds the URL that sends the user to OpenRouter to authorize the application.
+///
+/// After authorizing, OpenRouter redirects to `callback_url` with a `code` query parameter.
+pub fn authorization_url(callback_url: &str, challenge: &PkceChallenge) -> Result<Url> {
+    let callback_url = Url::parse(callback_url)
+        .map_err(|e| Error::ConfigError(format!("Invalid callback URL: {}", e)))?;
+
+    let mut url = Url::parse(AUTH_URL).expect("valid authorization URL");
+    url.query_pairs_mut()
+        .append_pair("callback_url", callback_url.as_str())
+        .append_pair("code_challenge", &challenge.challenge)
+        .append_pair("code_challenge_method", "S256");
+    Ok(url)
+}
+
+/// Exchanges an authorization code and its verifier for a user-scoped API key.
+pub async fn exchange_code_for_key(code: &str, verifier: &str) -> Result<String> {
+    let base_url = Url::parse(DEFAULT_BASE_URL).expect("valid default base URL");
+    exchange_code_for_key_with(&reqwest::Client::new(), &base_url, code, verifier).await
+}
+
+/// Like [`exchange_code_for_key`], using the given HTTP client and API base URL.
+/// The base URL must include a trailing slash.
+pub async fn exchange_code_for_key_with(
+    client: &reqwest::Client,
+    base_url: &Url,
+    code: &str,
+    verifier: &str,
+) -> Result<String> {
+    #[derive(Deserialize)]
+    struct KeyResponse {
+        key: String,
+    }
+
+    let url = base_url
+        .join("auth/keys")
+        .map_err(|e| Error::ConfigError(format!("Invalid URL: {}", e)))?;
+
+    let response = client
+        .post(url)
+        .json(&json!({
+            "code": code,
+            "code_verifier": verifier,
+            "code_challenge_method": "S256",
+        }))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(Error::from_response(response).await?);
+    }
+
+    let status = response.status();
+    let body = response.text().await?;
+    let key_response: KeyResponse = crate::utils::http::decode_json(status, body)?;
+    Ok(key_response.key)
+}
@@ -0,0 +1,89 @@
+//! Per-model circuit breaker for failing models and providers.
+//!
+//! After a model fails `failure_threshold` times in a row, requests to it fail fast with
+//! [`Error::ModelNotAvailable`] until `cooldown` has passed. The first request after the
+//! cooldown is let through; a success closes the circuit again, a failure reopens it.
+
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Consecutive failures and the open state of one model's circuit.
+#[derive(Debug, Default)]
+struct CircuitState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Circuit breaker keyed by model ID.
+///
+/// Clones share the same state, so every `ChatApi` created from a client sees the same
+/// open circuits.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    circuits: Arc<Mutex<HashMap<String, CircuitState>>>,
+}
+
+impl CircuitBreaker {
+    /// Opens a model's circuit for `cooldown` after `failure_threshold` consecutive failures.
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            circuits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Fails with `Error::ModelNotAvailable` while `model`'s circuit is open.
+    pub fn check(&self, model: &str) -> Result<()> {
+        let circuits = self.circuits.lock().unwrap_or_else(|e| e.into_inner());
+        let open_until = circuits.get(model).and_then(|state| state.open_until);
+        match open_until {
+            Some(open_until) if Instant::now() < open_until => Err(Error::ModelNotAvailable(format!(
+                "{} failed {} consecutive requests; retrying in {:?}",
+                model,
+                self.failure_threshold,
+                open_until - Instant::now()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records the outcome of a request to `model`.
+    ///
+    /// Only transient failures count towards opening the circuit: network errors, timeouts,
+    /// rate limits and server errors. Other errors leave the circuit unchanged.
+    pub fn record<T>(&self, model: &str, result: &Result<T>) {
+        let mut circuits = self.circuits.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(_) => {
+                circuits.remove(model);
+            }
+            Err(error) if is_transient(error) => {
+                let state = circuits.entry(model.to_string()).or_default();
+                state.failures += 1;
+                if state.failures >= self.failure_threshold {
+                    state.open_until = Some(Instant::now() + self.cooldown);
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Closes all circuits.
+    pub fn reset(&self) {
+        self.circuits.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+/// Whether `error` suggests the model or its provider is unhealthy.
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::HttpError(_) | Error::RateLimitExceeded { .. } | Error::TimeoutError(_) => true,
+        Error::ApiError { code, .. } => *code == 429 || *code >= 500,
+        _ => false,
+    }
+}
@@ -0,0 +1,124 @@
+//! Minimal in-memory vector store for prototyping semantic search.
+//!
+//! Vectors are normalized when added, so a search is a linear scan of dot products that
+//! yields cosine similarities. There is no persistence or indexing; reach for a vector
+//! database once the corpus outgrows a few thousand entries.
+
+use crate::error::{Error, Result};
+
+/// A stored text and its normalized embedding.
+#[derive(Debug, Clone)]
+struct Entry {
+    id: String,
+    text: String,
+    vector: Vec<f32>,
+}
+
+/// In-memory store of embeddings searchable by cosine similarity.
+#[derive(Debug, Clone, Default)]
+pub struct EmbeddingStore {
+    entries: Vec<Entry>,
+}
+
+impl EmbeddingStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `text` with its embedding under `id`, replacing any entry with the same ID.
+    ///
+    /// Fails if the vector is empty, has zero length, contains non-finite values, or has
+    /// a different dimension than the vectors already stored.
+    pub fn add(
+        &mut self,
+        id: impl Into<String>,
+        text: impl Into<String>,
+        vector: Vec<f32>,
+    ) -> Result<()> {
+        let entry = Entry {
+            id: id.into(),
+            text: text.into(),
+            vector: self.normalized(vector)?,
+        };
+
+        match self.entries.iter_mut().find(|existing| existing.id == entry.id) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+        Ok(())
+    }
+
+    /// Returns the IDs of the `k` entries most similar to `query`, with their cosine
+    /// similarity, best match first.
+    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<(String, f32)>> {
+        let query = self.normalized(query.to_vec())?;
+
+        let mut scored: Vec<(String, f32)> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let score = entry.vector.iter().zip(&query).map(|(a, b)| a * b).sum();
+                (entry.id.clone(), score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+        Ok(scored)
+    }
+
+    /// The text stored under `id`.
+    pub fn text(&self, id: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| entry.text.as_str())
+    }
+
+    /// Removes the entry stored under `id`, returning whether there was one.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.id != id);
+        self.entries.len() != before
+    }
+
+    /// Number of stored entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The dimension of the stored vectors, once one has been added.
+    pub fn dimension(&self) -> Option<usize> {
+        self.entries.first().map(|entry| entry.vector.len())
+    }
+
+    /// Scales `vector` to unit length after checking it can be compared with the store.
+    fn normalized(&self, mut vector: Vec<f32>) -> Result<Vec<f32>> {
+        if vector.is_empty() {
+            return Err(Error::ConfigError("Embedding vector cannot be empty".into()));
+        }
+        if let Some(dimension) = self.dimension() {
+            if vector.len() != dimension {
+                return Err(Error::ConfigError(format!(
+                    "Embedding has {} dimensions; the store holds {}-dimensional vectors",
+                    vector.len(),
+                    dimension
+                )));
+            }
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if !norm.is_finite() || norm == 0.0 {
+            return Err(Error::ConfigError(
+                "Embedding vector must have finite, non-zero length".into(),
+            ));
+        }
+        vector.iter_mut().for_each(|x| *x /= norm);
+        Ok(vector)
+    }
+}
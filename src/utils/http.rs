@@ -0,0 +1,315 @@
+//! Shared request path for API calls, with interceptor hooks.
+//!
+//! Every endpoint sends its requests through the same internal request path, which runs
+//! the interceptors registered on the client before the request goes out and after the
+//! response headers arrive, and compresses large bodies when the client asks for it.
+
+use crate::client::ClientConfig;
+use crate::error::{Error, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_ENCODING};
+use reqwest::{Method, StatusCode};
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Request bodies at least this large are gzipped when compression is enabled.
+pub const COMPRESSION_THRESHOLD_BYTES: usize = 16 * 1024;
+
+/// The parts of an outgoing request that interceptors may inspect or change.
+#[derive(Debug, Clone)]
+pub struct RequestParts {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    /// The request body, when it is held in memory.
+    pub body: Option<Vec<u8>>,
+}
+
+/// What interceptors see of a response once its headers have arrived.
+#[derive(Debug, Clone)]
+pub struct ResponseMeta {
+    pub method: Method,
+    pub url: Url,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// Time from sending the request to receiving the response headers.
+    pub elapsed: Duration,
+}
+
+/// Rate-limit and tracing metadata OpenRouter reports in response headers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseMetadata {
+    /// Requests allowed in the current window (`X-RateLimit-Limit`).
+    pub rate_limit_limit: Option<u64>,
+    /// Requests left in the current window (`X-RateLimit-Remaining`).
+    pub rate_limit_remaining: Option<u64>,
+    /// When the window resets, as a Unix timestamp in milliseconds (`X-RateLimit-Reset`).
+    pub rate_limit_reset: Option<u64>,
+    /// Identifier of the request for support and log correlation (`X-Request-Id`).
+    pub request_id: Option<String>,
+}
+
+impl ResponseMetadata {
+    /// Reads the metadata from `headers`; missing or malformed headers are left as `None`.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let text = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string())
+        };
+        let number = |name: &str| text(name).and_then(|value| value.parse().ok());
+
+        Self {
+            rate_limit_limit: number("x-ratelimit-limit"),
+            rate_limit_remaining: number("x-ratelimit-remaining"),
+            rate_limit_reset: number("x-ratelimit-reset"),
+            request_id: text("x-request-id"),
+        }
+    }
+}
+
+/// Hooks run around every API request, e.g. for logging, correlation IDs or metrics.
+///
+/// Both hooks default to doing nothing. Interceptors run in registration order.
+pub trait Interceptor: Send + Sync {
+    /// Called before the request is sent; changes to `request` are applied.
+    fn before_request(&self, _request: &mut RequestParts) {}
+
+    /// Called once the response headers have arrived, before the body is read.
+    fn after_response(&self, _response: &ResponseMeta) {}
+}
+
+/// The interceptors registered on a client.
+#[derive(Clone, Default)]
+pub struct Interceptors(Vec<Arc<dyn Interceptor>>);
+
+impl Interceptors {
+    /// Appends an interceptor.
+    pub fn push(&mut self, interceptor: Arc<dyn Interceptor>) {
+        self.0.push(interceptor);
+    }
+
+    /// Whether no interceptors are registered.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Interceptors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interceptors")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+/// Interceptor that writes every outgoing request to stderr for debugging: method, URL,
+/// headers and the pretty-printed JSON body.
+///
+/// The `Authorization` header is redacted. Registered by `with_request_logging`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestLogger;
+
+impl RequestLogger {
+    /// Renders `request` as it would be logged.
+    pub fn describe(request: &RequestParts) -> String {
+        let mut out = format!("[openrouter_api] debug: {} {}\n", request.method, request.url);
+        for (name, value) in &request.headers {
+            let value = if *name == reqwest::header::AUTHORIZATION {
+                "<redacted>"
+            } else {
+                value.to_str().unwrap_or("<binary>")
+            };
+            out.push_str(&format!("{}: {}\n", name, value));
+        }
+        if let Some(body) = &request.body {
+            let pretty = serde_json::from_slice::<serde_json::Value>(body)
+                .ok()
+                .and_then(|json| serde_json::to_string_pretty(&json).ok());
+            match pretty {
+                Some(pretty) => out.push_str(&pretty),
+                None => out.push_str(&String::from_utf8_lossy(body)),
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl Interceptor for RequestLogger {
+    fn before_request(&self, request: &mut RequestParts) {
+        eprint!("{}", Self::describe(request));
+    }
+}
+
+/// Decodes a response body as JSON.
+///
+/// A non-success status becomes the error from [`Error::from_status`], an empty body an
+/// [`Error::ApiError`], and a body that doesn't parse an [`Error::DeserializationError`].
+pub(crate) fn decode_json<T: serde::de::DeserializeOwned>(status: StatusCode, body: String) -> Result<T> {
+    if !status.is_success() {
+        return Err(Error::from_status(status.as_u16(), body));
+    }
+    if body.trim().is_empty() {
+        return Err(Error::ApiError {
+            code: status.as_u16(),
+            message: "Empty response body".into(),
+            metadata: None,
+        });
+    }
+    serde_json::from_str(&body).map_err(|e| Error::DeserializationError {
+        status: status.as_u16(),
+        body,
+        source: e,
+    })
+}
+
+/// Reads `response`'s body as text, failing with `Error::ResponseTooLarge` as soon as it
+/// exceeds the configured `max_response_bytes`.
+pub(crate) async fn read_body(mut response: reqwest::Response, config: &ClientConfig) -> Result<String> {
+    let Some(limit) = config.max_response_bytes else {
+        return Ok(response.text().await?);
+    };
+    if response.content_length().is_some_and(|length| length > limit as u64) {
+        return Err(Error::ResponseTooLarge { limit });
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(Error::ResponseTooLarge { limit });
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Builds and sends `builder` with `client`, running the configured interceptors.
+///
+/// With several API keys configured, a 401, 402 or 403 answer to an authenticated request
+/// rotates to the next key and the request is sent again, once per remaining key.
+pub(crate) async fn send(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    builder: reqwest::RequestBuilder,
+) -> Result<reqwest::Response> {
+    let mut request = builder.build()?;
+    let ring = config
+        .api_keys
+        .as_ref()
+        .filter(|ring| ring.len() > 1 && request.headers().contains_key(AUTHORIZATION));
+    let Some(ring) = ring else {
+        return send_once(client, config, request).await;
+    };
+
+    let mut rotations = 0;
+    loop {
+        let key_index = ring.active_index();
+        let bearer = HeaderValue::from_str(&format!("Bearer {}", ring.active()))
+            .map_err(|e| Error::ConfigError(format!("Invalid API key header format: {}", e)))?;
+        request.headers_mut().insert(AUTHORIZATION, bearer);
+
+        // Bodies that cannot be copied are sent once, with whichever key is active.
+        let retry = match request.try_clone() {
+            Some(retry) if rotations + 1 < ring.len() => retry,
+            _ => return send_once(client, config, request).await,
+        };
+        let response = send_once(client, config, request).await?;
+        if !(401..=403).contains(&response.status().as_u16()) {
+            return Ok(response);
+        }
+        // Callers can see the switch through `ApiKeyRing::active_index`.
+        ring.rotate_from(key_index);
+        rotations += 1;
+        request = retry;
+    }
+}
+
+/// Sends `request` once, running the configured interceptors around it.
+async fn send_once(
+    client: &reqwest::Client,
+    config: &ClientConfig,
+    mut request: reqwest::Request,
+) -> Result<reqwest::Response> {
+    if !config.interceptors.is_empty() {
+        let mut parts = RequestParts {
+            method: request.method().clone(),
+            url: request.url().clone(),
+            headers: std::mem::take(request.headers_mut()),
+            body: request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(<[u8]>::to_vec),
+        };
+        let had_body = parts.body.is_some();
+
+        for interceptor in &config.interceptors.0 {
+            interceptor.before_request(&mut parts);
+        }
+
+        *request.method_mut() = parts.method;
+        *request.url_mut() = parts.url;
+        *request.headers_mut() = parts.headers;
+        if had_body || parts.body.is_some() {
+            *request.body_mut() = parts.body.map(reqwest::Body::from);
+        }
+    }
+
+    if config.compress_requests {
+        compress_body(&mut request)?;
+    }
+
+    // An overridden timeout replaces the client's, unless the request has its own.
+    if let (Some(timeout), None) = (config.request_timeout, request.timeout()) {
+        *request.timeout_mut() = Some(timeout);
+    }
+
+    let method = request.method().clone();
+    let url = request.url().clone();
+    let started = Instant::now();
+    let response = client.execute(request).await?;
+
+    if !config.interceptors.is_empty() {
+        let meta = ResponseMeta {
+            method,
+            url,
+            status: response.status(),
+            headers: response.headers().clone(),
+            elapsed: started.elapsed(),
+        };
+        for interceptor in &config.interceptors.0 {
+            interceptor.after_response(&meta);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Gzips the request body if it is large enough and not already encoded.
+fn compress_body(request: &mut reqwest::Request) -> Result<()> {
+    if request.headers().contains_key(CONTENT_ENCODING) {
+        return Ok(());
+    }
+
+    let compressed = match request.body().and_then(|body| body.as_bytes()) {
+        Some(bytes) if bytes.len() >= COMPRESSION_THRESHOLD_BYTES => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(bytes)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| Error::TransportError(format!("Failed to compress request body: {}", e)))?
+        }
+        _ => return Ok(()),
+    };
+
+    *request.body_mut() = Some(compressed.into());
+    request
+        .headers_mut()
+        .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    Ok(())
+}
@@ -1,7 +1,10 @@
 pub mod auth;
+pub mod circuit_breaker;
+pub mod embedding_store;
+pub mod http;
 pub mod validation;
 
 // Re-export commonly used utilities
-pub use auth::load_api_key_from_env;
+pub use auth::{load_api_key_from_dotenv, load_api_key_from_env, load_api_key_from_file};
 pub use validation::{validate_chat_request, check_token_limits};
 
@@ -3,11 +3,20 @@
 use crate::error::{Error, Result};
 use crate::types::chat::{ChatCompletionRequest, Message};
 use crate::models::tool::Tool;
+use crate::types::models::ModelInfo;
+use crate::types::web_search::WebSearchRequest;
+use async_trait::async_trait;
+use serde_json::Value;
 use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
 
 /// Maximum allowed tokens in a chat completion request
 const MAX_TOKENS: u32 = 32_000;
 
+/// Maximum number of results a web search may request
+pub const MAX_WEB_SEARCH_RESULTS: u32 = 100;
+
 /// Validates a chat completion request for common errors.
 pub fn validate_chat_request(request: &ChatCompletionRequest) -> Result<()> {
     // Validate model is not empty
@@ -20,6 +29,15 @@ pub fn validate_chat_request(request: &ChatCompletionRequest) -> Result<()> {
         return Err(Error::ConfigError("Messages array cannot be empty".into()));
     }
     
+    // Validate the number of requested completions
+    if request.n == Some(0) {
+        return Err(Error::ConfigError("n must be at least 1".into()));
+    }
+
+    if request.max_tokens == Some(0) {
+        return Err(Error::ConfigError("max_tokens must be at least 1".into()));
+    }
+    
     // Validate message roles
     for (i, msg) in request.messages.iter().enumerate() {
         validate_message(msg, i)?;
@@ -33,6 +51,29 @@ pub fn validate_chat_request(request: &ChatCompletionRequest) -> Result<()> {
     Ok(())
 }
 
+/// Validates a web search request for common errors.
+pub fn validate_web_search_request(request: &WebSearchRequest) -> Result<()> {
+    if request.query.trim().is_empty() {
+        return Err(Error::ConfigError("Search query cannot be empty".into()));
+    }
+
+    if let Some(num_results) = request.num_results {
+        if !(1..=MAX_WEB_SEARCH_RESULTS).contains(&num_results) {
+            return Err(Error::ConfigError(format!(
+                "num_results must be between 1 and {}, got {}",
+                MAX_WEB_SEARCH_RESULTS, num_results
+            )));
+        }
+    }
+
+    let mut domains = request.include_domains.iter().chain(&request.exclude_domains).flatten();
+    if domains.any(|domain| domain.trim().is_empty()) {
+        return Err(Error::ConfigError("Search domain filters cannot contain empty domains".into()));
+    }
+
+    Ok(())
+}
+
 /// Validates a single message for errors.
 fn validate_message(message: &Message, index: usize) -> Result<()> {
     // Role validation
@@ -44,8 +85,26 @@ fn validate_message(message: &Message, index: usize) -> Result<()> {
         )),
     }
     
+    // Tool results must name the call they answer, and only tool results may
+    match (message.role.as_str(), message.tool_call_id.as_deref()) {
+        ("tool", None) => return Err(Error::ConfigError(
+            format!("Tool message at index {} is missing tool_call_id", index)
+        )),
+        ("tool", Some(id)) if id.trim().is_empty() => return Err(Error::ConfigError(
+            format!("Tool message at index {} has empty tool_call_id", index)
+        )),
+        ("tool", Some(_)) | (_, None) => {},
+        (role, Some(_)) => return Err(Error::ConfigError(
+            format!("Message at index {} has tool_call_id but role is '{}', not 'tool'", index, role)
+        )),
+    }
+    
     // Content validation 
-    if message.content.trim().is_empty() && message.tool_calls.is_none() {
+    let blank = match message.content.as_text() {
+        Some(text) => text.trim().is_empty(),
+        None => message.content.is_empty(),
+    };
+    if blank && message.tool_calls.is_none() {
         return Err(Error::ConfigError(
             format!("Message at index {} must have either non-empty content or tool_calls", index)
         ));
@@ -127,7 +186,7 @@ fn validate_tools(tools: &[Tool]) -> Result<()> {
 /// Estimates token count for a message (rough approximation).
 pub fn estimate_message_tokens(message: &Message) -> u32 {
     // Very rough approximation: 1 token per 4 characters
-    let content_tokens = message.content.len() as u32 / 4;
+    let content_tokens = message.content.text().len() as u32 / 4;
     
     // Add tokens for role
     let role_tokens = 3; // Typically "user", "assistant" or "system" is 1-3 tokens
@@ -147,6 +206,36 @@ pub fn estimate_message_tokens(message: &Message) -> u32 {
     role_tokens + content_tokens + tool_call_tokens
 }
 
+/// Counts the tokens messages occupy, for budgeting a conversation against a context window.
+///
+/// Counting is async so implementations can call a tokenizer service. Plug a model's real
+/// tokenizer in through [`crate::mcp::client::MCPClient::with_token_counter`].
+#[async_trait]
+pub trait TokenCounter: Send + Sync {
+    /// Count the tokens `message` occupies, including its role and tool calls.
+    async fn count_message_tokens(&self, message: &Message) -> Result<usize>;
+
+    /// Count the tokens `messages` occupy together.
+    async fn count_tokens(&self, messages: &[Message]) -> Result<usize> {
+        let mut total = 0;
+        for message in messages {
+            total += self.count_message_tokens(message).await?;
+        }
+        Ok(total)
+    }
+}
+
+/// [`TokenCounter`] using the same character-based heuristic as [`estimate_message_tokens`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenCounter;
+
+#[async_trait]
+impl TokenCounter for HeuristicTokenCounter {
+    async fn count_message_tokens(&self, message: &Message) -> Result<usize> {
+        Ok(estimate_message_tokens(message) as usize)
+    }
+}
+
 /// Estimates total token count for a request (rough approximation).
 pub fn estimate_request_tokens(request: &ChatCompletionRequest) -> u32 {
     // Sum tokens from all messages
@@ -198,3 +287,251 @@ pub fn check_token_limits(request: &ChatCompletionRequest) -> Result<()> {
     Ok(())
 }
 
+/// An estimated prompt plus `max_tokens` that exceeds a model's context length.
+///
+/// Providers typically shorten the output to fit, so the response may be cut off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextOverflow {
+    pub model: String,
+    pub prompt_tokens: u32,
+    pub max_tokens: u32,
+    pub context_length: u32,
+}
+
+impl fmt::Display for ContextOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "~{} prompt tokens plus max_tokens {} exceed the {}-token context of {}; the output may be truncated",
+            self.prompt_tokens, self.max_tokens, self.context_length, self.model
+        )
+    }
+}
+
+/// Callback told about chat requests that may overflow a cached model's context window,
+/// registered with `with_context_overflow_handler`.
+#[derive(Clone)]
+pub struct ContextOverflowHandler(Arc<dyn Fn(&ContextOverflow) + Send + Sync>);
+
+impl ContextOverflowHandler {
+    pub fn new(handler: impl Fn(&ContextOverflow) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(handler))
+    }
+
+    /// Passes `overflow` to the callback.
+    pub fn notify(&self, overflow: &ContextOverflow) {
+        (self.0)(overflow)
+    }
+}
+
+impl fmt::Debug for ContextOverflowHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ContextOverflowHandler")
+    }
+}
+
+/// Checks whether the estimated prompt plus `max_tokens` exceeds `model`'s context length.
+///
+/// Requests without `max_tokens` and models without a known context length never do.
+pub fn context_overflow(request: &ChatCompletionRequest, model: &ModelInfo) -> Option<ContextOverflow> {
+    let (Some(max_tokens), Some(context_length)) = (request.max_tokens, model.context_length) else {
+        return None;
+    };
+    let prompt_tokens = estimate_request_tokens(request);
+    (prompt_tokens.saturating_add(max_tokens) > context_length).then(|| ContextOverflow {
+        model: model.id.clone(),
+        prompt_tokens,
+        max_tokens,
+        context_length,
+    })
+}
+
+/// Checks that `model`'s context window leaves room for the completion after the
+/// estimated prompt.
+///
+/// The room needed is the larger of the request's `max_tokens` and `reserve_tokens`;
+/// without either there is nothing to check. Fails with `Error::ContextLengthExceeded`
+/// naming the shortfall. Models without a known context length always pass.
+pub fn validate_completion_budget(
+    request: &ChatCompletionRequest,
+    model: &ModelInfo,
+    reserve_tokens: Option<u32>,
+) -> Result<()> {
+    let completion_tokens = request.max_tokens.max(reserve_tokens);
+    let (Some(completion_tokens), Some(context_length)) = (completion_tokens, model.context_length) else {
+        return Ok(());
+    };
+    let prompt_tokens = estimate_request_tokens(request);
+    let needed = prompt_tokens.saturating_add(completion_tokens);
+    if needed > context_length {
+        return Err(Error::ContextLengthExceeded {
+            model: model.id.clone(),
+            message: format!(
+                "~{} prompt tokens leave {} of the {} completion tokens wanted in the {}-token context; {} tokens short",
+                prompt_tokens,
+                context_length.saturating_sub(prompt_tokens),
+                completion_tokens,
+                context_length,
+                needed - context_length
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Maximum number of `$ref` hops followed without descending into the data
+const MAX_REF_DEPTH: usize = 32;
+
+/// Validates `data` against a JSON Schema, descending into `properties` and `items`.
+///
+/// Supports `type`, `required`, `additionalProperties`, `enum`, `const`, `minimum`,
+/// `maximum`, `minLength`, `maxLength` and `pattern`. Other keywords are ignored.
+/// Local references like `#/$defs/Name` are resolved against `schema`.
+pub fn validate_against_schema(schema: &Value, data: &Value) -> Result<()> {
+    check_schema(schema, schema, data, "$")
+}
+
+/// Follows `schema`'s `$ref` chain within `root`, returning the schema it points to.
+fn resolve_ref<'a>(root: &'a Value, mut schema: &'a Value) -> Result<&'a Value> {
+    for _ in 0..MAX_REF_DEPTH {
+        let reference = match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => reference,
+            None => return Ok(schema),
+        };
+        let pointer = reference.strip_prefix('#').ok_or_else(|| {
+            Error::SchemaValidationError(format!("Only local references are supported: {}", reference))
+        })?;
+        schema = root.pointer(pointer).ok_or_else(|| {
+            Error::SchemaValidationError(format!("Unresolved schema reference: {}", reference))
+        })?;
+    }
+    Err(Error::SchemaValidationError("Schema references are nested too deeply".into()))
+}
+
+/// Validates `data` at `path` against `schema`, recursing into nested schemas.
+fn check_schema(root: &Value, schema: &Value, data: &Value, path: &str) -> Result<()> {
+    let schema = resolve_ref(root, schema)?
+        .as_object()
+        .ok_or_else(|| Error::SchemaValidationError("Schema must be an object".into()))?;
+    let fail = |message: String| Err(Error::SchemaValidationError(format!("{}: {}", path, message)));
+
+    // Check type; a list of types accepts any of them
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(schema_type)) => vec![schema_type.as_str()],
+        Some(Value::Array(schema_types)) => schema_types.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|schema_type| matches_type(schema_type, data)) {
+        return fail(format!("Expected {} but received {}", types.join(" or "), type_name(data)));
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(data) {
+            return fail(format!("Value {} is not one of the allowed values", data));
+        }
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if expected != data {
+            return fail(format!("Expected constant {} but received {}", expected, data));
+        }
+    }
+
+    if let Some(number) = data.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                return fail(format!("Value {} is below the minimum of {}", number, minimum));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                return fail(format!("Value {} is above the maximum of {}", number, maximum));
+            }
+        }
+    }
+
+    if let Some(text) = data.as_str() {
+        let length = text.chars().count() as u64;
+        if let Some(min_length) = schema.get("minLength").and_then(Value::as_u64) {
+            if length < min_length {
+                return fail(format!("String is shorter than {} characters", min_length));
+            }
+        }
+        if let Some(max_length) = schema.get("maxLength").and_then(Value::as_u64) {
+            if length > max_length {
+                return fail(format!("String is longer than {} characters", max_length));
+            }
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+            // Like JSON Schema, the pattern may match anywhere in the string
+            let regex = regex::Regex::new(pattern).map_err(|e| {
+                Error::SchemaValidationError(format!("Invalid pattern '{}': {}", pattern, e))
+            })?;
+            if !regex.is_match(text) {
+                return fail(format!("String does not match pattern '{}'", pattern));
+            }
+        }
+    }
+
+    if let Some(object) = data.as_object() {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for field in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(field) {
+                    return fail(format!("Required field '{}' is missing", field));
+                }
+            }
+        }
+
+        // Undeclared keys are rejected by `additionalProperties: false` and checked against
+        // `additionalProperties` when it is a schema.
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties");
+        for (key, value) in object {
+            let field_path = format!("{}.{}", path, key);
+            match (properties.and_then(|properties| properties.get(key)), additional) {
+                (Some(property_schema), _) => check_schema(root, property_schema, value, &field_path)?,
+                (None, Some(Value::Bool(false))) => {
+                    return fail(format!("Unexpected field '{}'", key));
+                }
+                (None, Some(additional_schema)) if additional_schema.is_object() => {
+                    check_schema(root, additional_schema, value, &field_path)?
+                }
+                (None, _) => {}
+            }
+        }
+    }
+
+    if let (Some(items), Some(item_schema)) = (data.as_array(), schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            check_schema(root, item_schema, item, &format!("{}[{}]", path, i))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `data` is an instance of the JSON Schema type `schema_type`.
+fn matches_type(schema_type: &str, data: &Value) -> bool {
+    match schema_type {
+        "object" => data.is_object(),
+        "array" => data.is_array(),
+        "string" => data.is_string(),
+        "number" => data.is_number(),
+        "integer" => data.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "boolean" => data.is_boolean(),
+        "null" => data.is_null(),
+        _ => true,
+    }
+}
+
+/// The JSON Schema type name of `data`, for error messages.
+fn type_name(data: &Value) -> &'static str {
+    match data {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}